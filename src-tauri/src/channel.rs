@@ -7,7 +7,10 @@ use parking_lot::Mutex;
 use serde::Serialize;
 use tauri::ipc::Channel;
 
-use crate::{common::JsSafeHash, pak::tree::RenderTreeNode};
+use crate::{
+    common::JsSafeHash,
+    pak::{report::JobReport, tree::RenderTreeNode},
+};
 
 const PROGRESS_EVENT_INTERVAL: Duration = Duration::from_millis(100);
 
@@ -58,6 +61,10 @@ pub struct UnpackProgressData {
     path: String,
     hash: JsSafeHash,
     finish_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report: Option<JobReport>,
 }
 
 pub type UnpackProgressChannel = UnpackProgressChannelImpl<UnpackProgressData>;
@@ -85,13 +92,14 @@ impl UnpackProgressChannelImpl<UnpackProgressData> {
         }
     }
 
-    pub fn file_done(&self, path: &str, hash: u64, _err_msg: Option<String>) {
+    /// Failed files are always sent, others are throttled.
+    pub fn file_done(&self, path: &str, hash: u64, err_msg: Option<String>) {
         let finish_count = self
             .finish_count
             .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
             + 1;
 
-        if !self.throttle.should_emit() {
+        if err_msg.is_none() && !self.throttle.should_emit() {
             return;
         }
 
@@ -101,14 +109,32 @@ impl UnpackProgressChannelImpl<UnpackProgressData> {
                 path: path.to_string(),
                 hash: JsSafeHash::from_u64(hash),
                 finish_count,
+                error: err_msg,
+                report: None,
             }))
         {
             log::error!("Failed to send file done event: {}", e);
         }
     }
 
-    pub fn work_finished(&self) {
-        if let Err(e) = self.channel.send(WorkProgressEvent::WorkFinished(None)) {
+    /// Number of files reported done so far.
+    #[cfg(test)]
+    pub fn finish_count(&self) -> u32 {
+        self.finish_count.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    pub fn work_finished(&self, report: JobReport) {
+        let finish_count = self.finish_count.load(std::sync::atomic::Ordering::SeqCst);
+        if let Err(e) =
+            self.channel
+                .send(WorkProgressEvent::WorkFinished(Some(UnpackProgressData {
+                    path: "".to_string(),
+                    hash: JsSafeHash::from_u64(0),
+                    finish_count,
+                    error: None,
+                    report: Some(report),
+                })))
+        {
             log::error!("Failed to send work finished event: {}", e);
         }
     }
@@ -164,6 +190,8 @@ impl FileTreeProgressChannelImpl<Vec<RenderTreeNode>> {
 pub struct TextureExportProgressData {
     path: String,
     finish_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report: Option<JobReport>,
 }
 
 pub type TextureExportProgressChannel = TextureExportProgressChannelImpl<TextureExportProgressData>;
@@ -206,14 +234,22 @@ impl TextureExportProgressChannelImpl<TextureExportProgressData> {
             .send(WorkProgressEvent::FileDone(TextureExportProgressData {
                 path: path.to_string(),
                 finish_count,
+                report: None,
             }))
         {
             log::error!("Failed to send texture export file done event: {}", e);
         }
     }
 
-    pub fn work_finished(&self) {
-        if let Err(e) = self.channel.send(WorkProgressEvent::WorkFinished(None)) {
+    pub fn work_finished(&self, report: JobReport) {
+        let finish_count = self.finish_count.load(std::sync::atomic::Ordering::SeqCst);
+        if let Err(e) = self.channel.send(WorkProgressEvent::WorkFinished(Some(
+            TextureExportProgressData {
+                path: "".to_string(),
+                finish_count,
+                report: Some(report),
+            },
+        ))) {
             log::error!("Failed to send texture export finished event: {}", e);
         }
     }
@@ -412,3 +448,80 @@ impl PackProgressChannelImpl<PackProgressData> {
         self.throttle.should_emit()
     }
 }
+
+// Script progress
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptProgressData {
+    /// Line printed by the script.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    done: u64,
+    total: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+pub type ScriptProgressChannel = ScriptProgressChannelImpl<ScriptProgressData>;
+pub type ScriptProgressChannelInner = Channel<WorkProgressEvent<ScriptProgressData>>;
+
+#[derive(Clone)]
+pub struct ScriptProgressChannelImpl<T> {
+    channel: Channel<WorkProgressEvent<T>>,
+    throttle: ProgressThrottle,
+}
+
+impl ScriptProgressChannelImpl<ScriptProgressData> {
+    pub fn new(channel: Channel<WorkProgressEvent<ScriptProgressData>>) -> Self {
+        Self {
+            channel,
+            throttle: ProgressThrottle::new(PROGRESS_EVENT_INTERVAL),
+        }
+    }
+
+    /// Printed lines are always sent.
+    pub fn output(&self, line: &str) {
+        if let Err(e) = self
+            .channel
+            .send(WorkProgressEvent::FileDone(ScriptProgressData {
+                output: Some(line.to_string()),
+                done: 0,
+                total: 0,
+                message: None,
+            }))
+        {
+            log::error!("Failed to send script output event: {}", e);
+        }
+    }
+
+    pub fn progress(&self, done: u64, total: u64, message: Option<String>) {
+        if done < total && !self.throttle.should_emit() {
+            return;
+        }
+
+        if let Err(e) = self
+            .channel
+            .send(WorkProgressEvent::FileDone(ScriptProgressData {
+                output: None,
+                done,
+                total,
+                message,
+            }))
+        {
+            log::error!("Failed to send script progress event: {}", e);
+        }
+    }
+
+    pub fn work_finished(&self) {
+        if let Err(e) = self.channel.send(WorkProgressEvent::WorkFinished(None)) {
+            log::error!("Failed to send script finished event: {}", e);
+        }
+    }
+
+    pub fn error(&self, error: String) {
+        if let Err(e) = self.channel.send(WorkProgressEvent::Error { error }) {
+            log::error!("Failed to send script error event: {}", e);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use crate::service::{
+    pak::PakService,
+    script::{ScriptObserver, ScriptRunOptions, ScriptService},
+};
+
+const SCRIPT_USAGE: &str =
+    "usage: app script <script.rhai> [--pak <path>]... [--list <path>]... [-- <args>...]";
+
+/// Prints script output to stdout and progress to stderr.
+struct ConsoleObserver;
+
+impl ScriptObserver for ConsoleObserver {
+    fn output(&self, line: &str) {
+        println!("{line}");
+    }
+
+    fn progress(&self, done: u64, total: u64, message: Option<String>) {
+        match message {
+            Some(message) => eprintln!("[{done}/{total}] {message}"),
+            None => eprintln!("[{done}/{total}]"),
+        }
+    }
+}
+
+/// Run a command line invocation and return its exit code.
+///
+/// Returns `None` if no command is given and the window should open.
+pub fn run(args: &[String]) -> Option<i32> {
+    let command = args.get(1)?;
+    let result = match command.as_str() {
+        "script" => {
+            #[cfg(target_os = "windows")]
+            crate::utility::attach_parent_console();
+            run_script(&args[2..])
+        }
+        _ => return None,
+    };
+
+    Some(match result {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("error: {error}");
+            1
+        }
+    })
+}
+
+/// Load paks and file lists, then run a script.
+///
+/// The first list is loaded as the `base` name layer, later ones as layers named by path.
+fn run_script(args: &[String]) -> Result<(), String> {
+    let mut script_path = None;
+    let mut paks = vec![];
+    let mut lists = vec![];
+    let mut script_args = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pak" => paks.push(args.next().ok_or(SCRIPT_USAGE)?.clone()),
+            "--list" => lists.push(args.next().ok_or(SCRIPT_USAGE)?.clone()),
+            "--" => script_args.extend(args.by_ref().cloned()),
+            _ if script_path.is_none() => script_path = Some(arg.clone()),
+            _ => return Err(SCRIPT_USAGE.to_string()),
+        }
+    }
+    let script_path = script_path.ok_or(SCRIPT_USAGE)?;
+
+    let pak_service = PakService::get();
+    for (index, list) in lists.iter().enumerate() {
+        pak_service
+            .load_file_table(list, (index > 0).then_some(list.as_str()))
+            .map_err(|e| e.to_string())?;
+    }
+    for pak in &paks {
+        pak_service.open_path(pak).map_err(|e| e.to_string())?;
+    }
+
+    let options = ScriptRunOptions {
+        path: Some(script_path),
+        source: None,
+        args: script_args,
+    };
+    let report = ScriptService::get()
+        .run(&options, Arc::new(ConsoleObserver))
+        .map_err(|e| e.to_string())?;
+    if !report.result.is_empty() {
+        println!("{}", report.result);
+    }
+    Ok(())
+}
@@ -1,29 +1,39 @@
-use ree_pak_core::filename::FileNameTable;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, time::Instant};
+use std::{collections::HashMap, sync::Arc, time::Instant};
 use tauri::AppHandle;
 
 use crate::{
     channel::{
         AudioExportProgressChannel, AudioExportProgressChannelInner, FileTreeProgressChannel,
         FileTreeProgressChannelInner, PackProgressChannel, PackProgressChannelInner,
-        TextureExportProgressChannel, TextureExportProgressChannelInner, UnpackProgressChannel,
-        UnpackProgressChannelInner,
+        ScriptProgressChannel, ScriptProgressChannelInner, TextureExportProgressChannel,
+        TextureExportProgressChannelInner, UnpackProgressChannel, UnpackProgressChannelInner,
     },
     common::JsSafeHash,
     external_tools::{self, VgmstreamStatus},
     pak::{
-        ExtractFileInfo, ExtractOptions, PakId, PakInfo,
+        EntryHistory, EntryQuery, ExtractFileInfo, ExtractOptions, PakId, PakInfo,
+        list_analysis::ListAnalysisReport,
+        name_layers::{NameLayerInfo, NameLookup},
+        path_list::{ExtractListOptions, ExtractListReport},
+        stats::{PakGroupStats, PakStatsOptions},
+        suffix_resolver::{SuffixResolveOptions, SuffixResolveReport},
         tree::{FileTree, RenderTreeOptions},
+        tree_index::{TreeChildrenPage, TreeChildrenQuery},
+        variant::VariantSummary,
     },
     service::{
+        api_server::{ApiServerInfo, ApiServerService},
         audio::{AudioContainerInfo, AudioExtractBatchOptions, AudioService, AudioSourceRef},
+        format::{FormatCapabilities, FormatRegistry},
         model_insight::{
             ModelInsightLoadMeshAssetsOptions, ModelInsightLoadTexturePreviewsOptions,
             ModelInsightMeshAssets, ModelInsightService, ModelInsightTexturePreview,
         },
         pak::{PackConflictInfo, PakHeaderInfo, PakService},
-        preview::{PreviewService, TextureExportFormat},
+        preview::{FileMetadata, PreviewService},
+        preview_cache::{PreviewCache, PreviewCacheStatus},
+        script::{ScriptRunOptions, ScriptRunReport, ScriptService},
         update::{AppUpdateInfo, AppUpdateProgressChannel},
     },
     utility, warp_result_elapsed,
@@ -69,11 +79,13 @@ pub fn pak_list_all() -> Result<Vec<PakInfo>, String> {
     Ok(pak_service.list_all_paks())
 }
 
-/// Open a Pak file.
+/// Open a Pak file, or all paks and loose `natives/` files inside a zip mod archive.
+///
+/// Returns loaded pak ids in load order.
 #[tauri::command]
-pub fn pak_open(path: &str) -> Result<PakId, String> {
+pub fn pak_open(path: &str) -> Result<Vec<PakId>, String> {
     let pak_service = PakService::get();
-    pak_service.open_pak(path).map_err(|e| e.to_string())
+    pak_service.open_path(path).map_err(|e| e.to_string())
 }
 
 /// Close a Pak file.
@@ -131,6 +143,61 @@ pub async fn pak_read_file_tree_optimized(
     )
 }
 
+/// Read a page of directory children of the current Pak group.
+///
+/// Directories are loaded on demand, use `generation` of the returned page
+/// in later queries to detect tree changes.
+///
+/// Should load file name list first.
+#[tauri::command]
+pub async fn pak_read_tree_children(query: TreeChildrenQuery) -> Result<TreeChildrenPage, String> {
+    let pak_service = PakService::get();
+    warp_result_elapsed!(
+        pak_service.read_tree_children_async(query).await,
+        "read_tree_children spent {} ms"
+    )
+}
+
+/// Get statistics of loaded paks by extension, directory and pak.
+#[tauri::command]
+pub async fn pak_get_statistics(options: Option<PakStatsOptions>) -> Result<PakGroupStats, String> {
+    let pak_service = PakService::get();
+    warp_result_elapsed!(
+        pak_service
+            .get_statistics_async(options.unwrap_or_default())
+            .await,
+        "get_statistics spent {} ms"
+    )
+}
+
+/// Extract entries listed in a path list file.
+///
+/// Returns paths that were not found in loaded paks.
+#[tauri::command]
+pub async fn pak_extract_from_list(
+    options: ExtractListOptions,
+    on_event: UnpackProgressChannelInner,
+) -> Result<ExtractListReport, String> {
+    let pak_service = PakService::get();
+    let channel = UnpackProgressChannel::new(on_event);
+    pak_service
+        .extract_from_list(options, channel)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// List languages and platforms of loaded files.
+///
+/// Should load file name list first.
+#[tauri::command]
+pub async fn pak_list_variants() -> Result<VariantSummary, String> {
+    let pak_service = PakService::get();
+    warp_result_elapsed!(
+        pak_service.get_variant_summary_async().await,
+        "list_variants spent {} ms"
+    )
+}
+
 /// Extract all loaded paks.
 #[tauri::command]
 pub async fn pak_extract_all(
@@ -152,6 +219,31 @@ pub async fn pak_extract_all(
     Ok(())
 }
 
+/// Get every version of an entry across the load order, with the active one last.
+#[tauri::command]
+pub fn pak_get_entry_history(query: EntryQuery) -> Result<EntryHistory, String> {
+    let pak_service = PakService::get();
+    pak_service
+        .get_entry_history(&query)
+        .map_err(|e| e.to_string())
+}
+
+/// Extract a specific version of an entry from the given pak.
+#[tauri::command]
+pub async fn pak_extract_entry_version(
+    hash: JsSafeHash,
+    belongs_to: PakId,
+    output_path: String,
+) -> Result<(), String> {
+    let pak_service = PakService::get();
+    tokio::task::spawn_blocking(move || {
+        pak_service.unpack_file_by_hash(hash.hash_u64(), Some(belongs_to), &output_path)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+    .map_err(|error| error.to_string())
+}
+
 /// Terminate the current extraction process.
 #[tauri::command]
 pub fn pak_terminate_extraction() -> Result<(), String> {
@@ -227,16 +319,24 @@ pub fn pak_terminate_pack() -> Result<(), String> {
     Ok(())
 }
 
+/// Load a list file as a name layer.
+///
+/// Replaces the layer of the same name, `base` if no name is given.
 #[tauri::command]
-pub fn file_table_load(path: &str) -> Result<(), String> {
+pub fn file_table_load(path: &str, name: Option<String>) -> Result<(), String> {
     let pak_service = PakService::get();
-    log_sync_command("file_table_load", Some(format!("path={path}")), || {
-        let table = FileNameTable::from_list_file(path).map_err(|e| e.to_string())?;
-        pak_service.set_file_name_table(table);
-        Ok::<(), String>(())
-    })
+    log_sync_command(
+        "file_table_load",
+        Some(format!("path={path} name={name:?}")),
+        || {
+            pak_service
+                .load_file_table(path, name.as_deref())
+                .map_err(|e| e.to_string())
+        },
+    )
 }
 
+/// Push paths into the `manual` name layer.
 #[tauri::command]
 pub fn file_table_push_paths(file_path_list: Vec<String>) -> Result<(), String> {
     let pak_service = PakService::get();
@@ -244,20 +344,203 @@ pub fn file_table_push_paths(file_path_list: Vec<String>) -> Result<(), String>
     Ok(())
 }
 
+/// List name layers from bottom to top.
+#[tauri::command]
+pub fn file_table_list_layers() -> Result<Vec<NameLayerInfo>, String> {
+    let pak_service = PakService::get();
+    Ok(pak_service.list_name_layers())
+}
+
+#[tauri::command]
+pub fn file_table_set_layer_enabled(name: String, enabled: bool) -> Result<(), String> {
+    let pak_service = PakService::get();
+    pak_service
+        .set_name_layer_enabled(&name, enabled)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn file_table_remove_layer(name: String) -> Result<(), String> {
+    let pak_service = PakService::get();
+    pak_service
+        .remove_name_layer(&name)
+        .map_err(|e| e.to_string())
+}
+
+/// Find the name of a hash and the layer it was resolved from.
+#[tauri::command]
+pub fn file_table_lookup(hash: JsSafeHash) -> Result<Option<NameLookup>, String> {
+    let pak_service = PakService::get();
+    Ok(pak_service.lookup_name(hash.hash_u64()))
+}
+
+/// Export names of enabled layers as one list file.
+///
+/// Returns the number of names written.
+#[tauri::command]
+pub fn file_table_export(output_path: String) -> Result<usize, String> {
+    let pak_service = PakService::get();
+    log_sync_command(
+        "file_table_export",
+        Some(format!("output_path={output_path}")),
+        || {
+            pak_service
+                .export_file_table(&output_path)
+                .map_err(|e| e.to_string())
+        },
+    )
+}
+
+/// Report exact duplicates, case-only duplicates and hash collisions in a list file.
+#[tauri::command]
+pub async fn file_table_analyze(path: String) -> Result<ListAnalysisReport, String> {
+    let pak_service = PakService::get();
+    warp_result_elapsed!(
+        pak_service.analyze_list_file_async(path).await,
+        "analyze_list_file spent {} ms"
+    )
+}
+
+/// Resolve unknown entries by trying version, platform and language suffixes on base paths.
+///
+/// Resolved paths are pushed into the file name table.
+#[tauri::command]
+pub async fn file_table_resolve_suffixes(
+    options: SuffixResolveOptions,
+) -> Result<SuffixResolveReport, String> {
+    let pak_service = PakService::get();
+    warp_result_elapsed!(
+        pak_service.resolve_suffixes_async(options).await,
+        "resolve_suffixes spent {} ms"
+    )
+}
+
 /// Get preview file path.
 ///
+/// If `belongs_to` is set, preview the version stored in that pak instead of the active one.
+///
 /// Will return error if the file is not supported.
 #[tauri::command]
-pub async fn get_preview_file(hash: JsSafeHash) -> Result<String, String> {
+pub async fn get_preview_file(
+    hash: JsSafeHash,
+    belongs_to: Option<PakId>,
+) -> Result<String, String> {
     let preview_service = PreviewService::get();
 
     preview_service
-        .get_preview_file(hash.hash_u64())
+        .get_preview_file(hash.hash_u64(), belongs_to)
         .await
         .map_err(|e| e.to_string())
         .map(|p| p.to_string_lossy().to_string())
 }
 
+#[tauri::command]
+pub fn preview_cache_status() -> Result<PreviewCacheStatus, String> {
+    Ok(PreviewCache::get().status())
+}
+
+/// Set the preview cache size limit in bytes, evicting files if the cache is larger.
+#[tauri::command]
+pub fn preview_cache_set_limit(size_limit: u64) -> Result<PreviewCacheStatus, String> {
+    log_sync_command(
+        "preview_cache_set_limit",
+        Some(format!("size_limit={size_limit}")),
+        || {
+            PreviewCache::get()
+                .set_size_limit(size_limit)
+                .map_err(|e| e.to_string())
+        },
+    )
+}
+
+#[tauri::command]
+pub fn preview_cache_clear() -> Result<PreviewCacheStatus, String> {
+    log_sync_command("preview_cache_clear", None, || {
+        PreviewCache::get().clear().map_err(|e| e.to_string())
+    })
+}
+
+/// List the registered format handlers and what they support.
+#[tauri::command]
+pub fn format_list_handlers() -> Result<Vec<FormatCapabilities>, String> {
+    Ok(FormatRegistry::get().capabilities())
+}
+
+/// Describe an entry with the metadata of its format handler.
+#[tauri::command]
+pub async fn get_file_metadata(
+    hash: JsSafeHash,
+    belongs_to: Option<PakId>,
+) -> Result<FileMetadata, String> {
+    let preview_service = PreviewService::get();
+
+    warp_result_elapsed!(
+        preview_service
+            .get_file_metadata(hash.hash_u64(), belongs_to)
+            .await,
+        "get_file_metadata spent {} ms"
+    )
+}
+
+/// Start the local HTTP API, or return the running one.
+///
+/// Uses the default port if `port` is not set, `0` picks a free port.
+#[tauri::command]
+pub fn api_server_start(port: Option<u16>) -> Result<ApiServerInfo, String> {
+    let api_server_service = ApiServerService::get();
+    log_sync_command("api_server_start", Some(format!("port={port:?}")), || {
+        api_server_service.start(port).map_err(|e| e.to_string())
+    })
+}
+
+#[tauri::command]
+pub fn api_server_stop() -> Result<(), String> {
+    let api_server_service = ApiServerService::get();
+    api_server_service.stop();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn api_server_status() -> Result<Option<ApiServerInfo>, String> {
+    let api_server_service = ApiServerService::get();
+    Ok(api_server_service.status())
+}
+
+/// Run a Rhai script against the loaded paks.
+///
+/// Printed lines and `progress` calls are sent through `on_event`.
+#[tauri::command]
+pub async fn script_run(
+    options: ScriptRunOptions,
+    on_event: ScriptProgressChannelInner,
+) -> Result<ScriptRunReport, String> {
+    let script_service = ScriptService::get();
+    let channel = ScriptProgressChannel::new(on_event);
+    let observer = Arc::new(channel.clone());
+    let result = tokio::task::spawn_blocking(move || script_service.run(&options, observer))
+        .await
+        .map_err(|error| error.to_string())?;
+
+    match result {
+        Ok(report) => {
+            channel.work_finished();
+            Ok(report)
+        }
+        Err(error) => {
+            channel.error(error.to_string());
+            Err(error.to_string())
+        }
+    }
+}
+
+#[tauri::command]
+pub fn script_terminate() -> Result<(), String> {
+    let script_service = ScriptService::get();
+    script_service.terminate();
+    log::warn!("Script terminated.");
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn audio_list_container(source: AudioSourceRef) -> Result<AudioContainerInfo, String> {
     let audio_service = AudioService::get();
@@ -386,6 +669,9 @@ pub struct TextureExportOptions {
     pub output_path: String,
     pub format: String,
     pub files: Vec<ExtractFileInfo>,
+    /// Save the job report as JSON in the output directory.
+    #[serde(default)]
+    pub save_report: bool,
 }
 
 #[tauri::command]
@@ -394,16 +680,18 @@ pub async fn export_texture_files(
     on_event: TextureExportProgressChannelInner,
 ) -> Result<usize, String> {
     let preview_service = PreviewService::get();
-    let format = match options.format.as_str() {
-        "dds" => TextureExportFormat::Dds,
-        "png" => TextureExportFormat::Png,
-        other => return Err(format!("Unsupported texture export format: {other}")),
-    };
     let progress = TextureExportProgressChannel::new(on_event);
 
     preview_service
-        .export_texture_files(format, &options.output_path, &options.files, progress)
+        .export_texture_files(
+            options.format,
+            &options.output_path,
+            &options.files,
+            options.save_report,
+            progress,
+        )
         .await
+        .map(|report| report.succeeded)
         .map_err(|e| e.to_string())
 }
 
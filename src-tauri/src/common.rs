@@ -10,6 +10,12 @@ impl UniqueId {
     }
 }
 
+impl std::fmt::Display for UniqueId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 /// Because JavaScript's int64 type loses precision, it needs to be split into two sets of data.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct JsSafeHash(u32, u32);
@@ -27,6 +27,10 @@ pub enum Error {
 
     #[error("Missing file list. Please load a file list first.")]
     MissingFileList,
+    #[error("File name layer not found: {0}")]
+    NameLayerNotFound(String),
+    #[error("Invalid list cache: {0}")]
+    InvalidListCache(String),
 
     #[error("Pak ID not found: id = {0:?}")]
     PakIdNotFound(PakId),
@@ -38,6 +42,12 @@ pub enum Error {
     UnpackAlreadyRunning,
     #[error("File tree load already running.")]
     FileTreeAlreadyRunning,
+    #[error("Tree node not found: id = {0}")]
+    TreeNodeNotFound(u32),
+    #[error("File tree has changed, reload it.")]
+    TreeIndexOutdated,
+    #[error("Invalid extract filter: {0}")]
+    InvalidExtractFilter(String),
     #[error("Pack progress already running.")]
     PackAlreadyRunning,
     #[error("Texture export already running.")]
@@ -48,10 +58,16 @@ pub enum Error {
     PackWriter(#[from] ree_pak_core::write::PakWriteError),
     #[error("Terminated.")]
     Terminated,
+    #[error("No pak or natives files found in archive: {0}")]
+    ArchiveEmpty(String),
     #[error("Pak entry not found: {0}")]
     PakEntryNotFound(String),
     #[error("Preview file not supported: {0}")]
     PreviewFileNotSupported(String),
+    #[error("Format not supported: {0}")]
+    FormatNotSupported(String),
+    #[error("Model file parse error: {0}")]
+    ModelParse(String),
     #[error("Audio file not supported: {0}")]
     AudioFileNotSupported(String),
     #[error("Audio entry not found: {0}")]
@@ -60,6 +76,12 @@ pub enum Error {
     VgmstreamCliNotFound(String),
     #[error("vgmstream-cli failed: {0}")]
     VgmstreamCliFailed(String),
+    #[error("API server error: {0}")]
+    ApiServer(String),
+    #[error("Script error: {0}")]
+    Script(String),
+    #[error("Script already running.")]
+    ScriptAlreadyRunning,
 
     #[error("Internal error: {0}")]
     Internal(String),
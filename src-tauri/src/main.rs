@@ -15,10 +15,12 @@ use service::pak::PakService;
 use tauri::{AppHandle, Manager};
 
 use crate::service::{
-    audio::AudioService, model_insight::ModelInsightService, preview::PreviewService,
+    api_server::ApiServerService, audio::AudioService, model_insight::ModelInsightService,
+    preview::PreviewService, preview_cache::PreviewCache, script::ScriptService,
 };
 
 mod channel;
+mod cli;
 mod command;
 mod common;
 mod error;
@@ -101,6 +103,10 @@ fn handle_main_window_close_requested(window: &tauri::WebviewWindow, api: &tauri
         return;
     }
 
+    ApiServerService::get().stop();
+    if let Err(e) = PreviewCache::get().flush() {
+        log::warn!("Failed to save preview cache index: {}", e);
+    }
     release_preview_file_references(window);
     close_main_window_after_temp_cleanup(window.clone());
 }
@@ -132,8 +138,19 @@ fn main() {
     // initialize services
     let _ = PakService::initialize(PakGroup::new());
     let _ = AudioService::initialize();
+    let _ = PreviewCache::initialize();
     let _ = PreviewService::initialize();
     let _ = ModelInsightService::initialize();
+    let _ = ApiServerService::initialize();
+    let _ = ScriptService::initialize();
+
+    if let Some(exit_code) = cli::run(&std::env::args().collect::<Vec<_>>()) {
+        // process::exit skips destructors, save access times of cache hits first
+        if let Err(e) = PreviewCache::get().flush() {
+            log::warn!("Failed to save preview cache index: {}", e);
+        }
+        std::process::exit(exit_code);
+    }
 
     tauri::Builder::default()
         .plugin(tauri_plugin_process::init())
@@ -160,7 +177,13 @@ fn main() {
             command::pak_get_info,
             command::pak_read_file_tree,
             command::pak_read_file_tree_optimized,
+            command::pak_read_tree_children,
+            command::pak_get_statistics,
+            command::pak_list_variants,
             command::pak_extract_all,
+            command::pak_extract_from_list,
+            command::pak_get_entry_history,
+            command::pak_extract_entry_version,
             command::pak_terminate_extraction,
             command::pak_get_header,
             command::pak_analyze_conflicts,
@@ -168,7 +191,24 @@ fn main() {
             command::pak_terminate_pack,
             command::file_table_load,
             command::file_table_push_paths,
+            command::file_table_resolve_suffixes,
+            command::file_table_list_layers,
+            command::file_table_set_layer_enabled,
+            command::file_table_remove_layer,
+            command::file_table_lookup,
+            command::file_table_export,
+            command::file_table_analyze,
             command::get_preview_file,
+            command::get_file_metadata,
+            command::format_list_handlers,
+            command::preview_cache_status,
+            command::preview_cache_set_limit,
+            command::preview_cache_clear,
+            command::api_server_start,
+            command::api_server_stop,
+            command::api_server_status,
+            command::script_run,
+            command::script_terminate,
             command::audio_list_container,
             command::audio_extract_wems,
             command::audio_extract_wavs,
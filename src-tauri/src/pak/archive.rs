@@ -0,0 +1,323 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use ree_pak_core::{
+    utf16_hash::Utf16HashExt,
+    write::{FileOptions, PakWriter},
+};
+use zip::ZipArchive;
+
+use crate::{
+    TEMP_DIR_NAME,
+    error::{Error, Result},
+    get_local_dir,
+};
+
+const ZIP_MAGIC: [u8; 4] = *b"PK\x03\x04";
+const ARCHIVE_SPILL_DIR_NAME: &str = "archives";
+
+/// A zip mod archive containing pak files and/or loose `natives/` files.
+#[derive(Debug, Clone)]
+pub struct ModArchive {
+    path: PathBuf,
+    paks: Vec<String>,
+    loose_files: Vec<ArchiveLooseFile>,
+}
+
+/// A loose file inside a zip mod archive.
+#[derive(Debug, Clone)]
+pub struct ArchiveLooseFile {
+    /// Entry name inside the zip archive.
+    pub entry_name: String,
+    /// Path used for hashing, starting at `natives/` if present.
+    pub relative_path: String,
+    pub size: u64,
+}
+
+impl ModArchive {
+    /// Enumerate pak files and loose files inside a zip archive.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut zip = open_zip(path)?;
+
+        let mut paks = vec![];
+        let mut loose_files = vec![];
+        for i in 0..zip.len() {
+            let file = zip.by_index(i)?;
+            if file.is_dir() || file.enclosed_name().is_none() {
+                continue;
+            }
+
+            let entry_name = file.name().replace('\\', "/");
+            if entry_name.to_ascii_lowercase().ends_with(".pak") {
+                paks.push(entry_name);
+                continue;
+            }
+
+            loose_files.push(ArchiveLooseFile {
+                relative_path: natives_relative_path(&entry_name)
+                    .unwrap_or_else(|| entry_name.clone()),
+                entry_name,
+                size: file.size(),
+            });
+        }
+        paks.sort();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            paks,
+            loose_files,
+        })
+    }
+
+    /// Pak entry names, sorted by name.
+    pub fn paks(&self) -> &[String] {
+        &self.paks
+    }
+
+    /// All loose (non-pak) files.
+    pub fn loose_files(&self) -> &[ArchiveLooseFile] {
+        &self.loose_files
+    }
+
+    /// Loose files placed under a `natives/` directory.
+    pub fn natives_files(&self) -> impl Iterator<Item = &ArchiveLooseFile> {
+        self.loose_files
+            .iter()
+            .filter(|file| natives_relative_path(&file.entry_name).is_some())
+    }
+
+    /// Copy a zip entry into a writer.
+    pub fn copy_entry(&self, entry_name: &str, writer: &mut impl Write) -> Result<u64> {
+        ArchiveReader::open(&self.path)?.copy_entry(entry_name, writer)
+    }
+
+    /// Spill a pak entry into the temp directory so it can be opened as a `PakFile`.
+    pub fn spill_pak(&self, entry_name: &str) -> Result<PathBuf> {
+        let spill_path = self.spill_path(entry_name)?;
+        if !spill_path.exists() {
+            write_spill(&spill_path, |file| {
+                let mut writer = BufWriter::new(file);
+                self.copy_entry(entry_name, &mut writer)?;
+                writer.flush()?;
+                Ok(())
+            })?;
+        }
+
+        Ok(spill_path)
+    }
+
+    /// Pack loose `natives/` files into a temporary pak.
+    ///
+    /// Returns `None` if the archive has no `natives/` files.
+    pub fn spill_natives_as_pak(&self) -> Result<Option<PathBuf>> {
+        let natives_files = self.natives_files().collect::<Vec<_>>();
+        if natives_files.is_empty() {
+            return Ok(None);
+        }
+
+        let spill_path = self.spill_path("natives.pak")?;
+        if spill_path.exists() {
+            return Ok(Some(spill_path));
+        }
+
+        let mut zip = open_zip(&self.path)?;
+        write_spill(&spill_path, |file| {
+            let mut pak_writer = PakWriter::new(BufWriter::new(file), natives_files.len() as u64);
+
+            let mut write_files_into_pak = || -> Result<()> {
+                for file in &natives_files {
+                    let mut reader = zip.by_name(&file.entry_name)?;
+                    pak_writer
+                        .start_file_hash(file.relative_path.hash_mixed(), FileOptions::default())?;
+                    std::io::copy(&mut reader, &mut pak_writer)?;
+                }
+                Ok(())
+            };
+
+            let result = write_files_into_pak();
+            pak_writer.finish()?;
+            result
+        })?;
+
+        Ok(Some(spill_path))
+    }
+
+    fn spill_path(&self, entry_name: &str) -> Result<PathBuf> {
+        let spill_dir = archive_spill_dir();
+        if !spill_dir.exists() {
+            std::fs::create_dir_all(&spill_dir)?;
+        }
+
+        // include archive mtime so spills of an updated archive are not reused
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let key = format!("{}/{}/{}", self.path.display(), modified, entry_name);
+        let file_name = entry_name
+            .rsplit('/')
+            .find(|segment| !segment.is_empty())
+            .unwrap_or(entry_name);
+        Ok(spill_dir.join(format!(
+            "{:016X}-{}",
+            key.hash_mixed(),
+            sanitize_file_name(file_name)
+        )))
+    }
+}
+
+/// An opened zip archive, for copying many entries without reopening it.
+pub struct ArchiveReader {
+    zip: ZipArchive<BufReader<File>>,
+}
+
+impl ArchiveReader {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            zip: open_zip(path.as_ref())?,
+        })
+    }
+
+    /// Copy a zip entry into a writer.
+    pub fn copy_entry(&mut self, entry_name: &str, writer: &mut impl Write) -> Result<u64> {
+        let mut file = self.zip.by_name(entry_name)?;
+        Ok(std::io::copy(&mut file, writer)?)
+    }
+}
+
+/// Check if a file is a zip archive by its magic.
+pub fn is_zip_archive(path: impl AsRef<Path>) -> bool {
+    let mut magic = [0; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|_| magic == ZIP_MAGIC)
+}
+
+/// Build the virtual path of a file inside an archive.
+///
+/// Example: `C:/mods/foo.zip` + `bar/re_chunk_000.pak` -> `C:/mods/foo.zip/bar/re_chunk_000.pak`
+pub fn archive_virtual_path(archive_path: &str, entry_name: &str) -> String {
+    format!(
+        "{}/{}",
+        archive_path.trim_end_matches(['/', '\\']),
+        entry_name.trim_start_matches('/')
+    )
+}
+
+pub(crate) fn archive_spill_dir() -> PathBuf {
+    get_local_dir()
+        .join(TEMP_DIR_NAME)
+        .join(ARCHIVE_SPILL_DIR_NAME)
+}
+
+/// Write a spill file under a temp name and rename it once complete.
+///
+/// Spills are reused by path, a partial file left by a failed or interrupted write must not be.
+fn write_spill(spill_path: &Path, write: impl FnOnce(File) -> Result<()>) -> Result<()> {
+    let temp_path = spill_path.with_extension(format!("{}.part", uuid::Uuid::new_v4()));
+    let result = File::create(&temp_path)
+        .map_err(Error::from)
+        .and_then(write)
+        .and_then(|_| Ok(std::fs::rename(&temp_path, spill_path)?));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn open_zip(path: &Path) -> Result<ZipArchive<BufReader<File>>> {
+    let file = File::open(path).map_err(|source| Error::FileIO {
+        path: path.display().to_string(),
+        source,
+    })?;
+    Ok(ZipArchive::new(BufReader::new(file))?)
+}
+
+/// Get the path starting at the `natives/` component.
+///
+/// Example: `MyMod/natives/STM/foo.tex.123` -> `natives/STM/foo.tex.123`
+fn natives_relative_path(entry_name: &str) -> Option<String> {
+    let components = entry_name
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<_>>();
+    let natives_index = components
+        .iter()
+        .position(|component| component.eq_ignore_ascii_case("natives"))?;
+    if natives_index + 1 >= components.len() {
+        return None;
+    }
+
+    Some(components[natives_index..].join("/"))
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|ch| match ch {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            other => other,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_natives_relative_path_strips_mod_root() {
+        assert_eq!(
+            natives_relative_path("MyMod/natives/STM/foo.tex.123"),
+            Some("natives/STM/foo.tex.123".to_string())
+        );
+        assert_eq!(
+            natives_relative_path(r"natives\STM\foo.tex.123"),
+            Some("natives/STM/foo.tex.123".to_string())
+        );
+        assert_eq!(natives_relative_path("MyMod/readme.txt"), None);
+        assert_eq!(natives_relative_path("MyMod/natives"), None);
+    }
+
+    #[test]
+    fn test_mod_archive_enumerates_paks_and_loose_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let archive_path = temp_dir.path().join("mod.zip");
+        {
+            let mut zip = zip::ZipWriter::new(File::create(&archive_path).unwrap());
+            let options = zip::write::SimpleFileOptions::default();
+            zip.start_file("MyMod/re_chunk_000.pak.patch_002.pak", options)
+                .unwrap();
+            zip.write_all(b"KPKA").unwrap();
+            zip.start_file("MyMod/natives/STM/foo.tex.123", options)
+                .unwrap();
+            zip.write_all(b"TEX").unwrap();
+            zip.start_file("MyMod/readme.txt", options).unwrap();
+            zip.write_all(b"readme").unwrap();
+            zip.finish().unwrap();
+        }
+
+        assert!(is_zip_archive(&archive_path));
+        let archive = ModArchive::open(&archive_path).unwrap();
+        assert_eq!(archive.paks(), ["MyMod/re_chunk_000.pak.patch_002.pak"]);
+        assert_eq!(archive.loose_files().len(), 2);
+
+        let natives = archive.natives_files().collect::<Vec<_>>();
+        assert_eq!(natives.len(), 1);
+        assert_eq!(natives[0].relative_path, "natives/STM/foo.tex.123");
+        assert_eq!(natives[0].size, 3);
+    }
+
+    #[test]
+    fn test_archive_virtual_path() {
+        assert_eq!(
+            archive_virtual_path("C:/mods/foo.zip", "bar/a.pak"),
+            "C:/mods/foo.zip/bar/a.pak"
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+    path::{Component, Path},
+};
+
+use parking_lot::Mutex;
+use serde::Deserialize;
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+use crate::error::{Error, Result};
+
+/// Archive format to extract into, instead of loose files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExtractArchiveFormat {
+    /// Zip with deflate compression.
+    Zip,
+    /// Uncompressed tar.
+    Tar,
+}
+
+enum ArchiveSink {
+    Zip(Box<ZipWriter<BufWriter<File>>>),
+    Tar(tar::Builder<BufWriter<File>>),
+}
+
+/// Entries up to this size are read into memory before the archive is locked.
+const BUFFERED_ENTRY_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Writes extracted entries into a single archive file.
+///
+/// Entries may be extracted in parallel, writes are serialized. Workers read entries up to
+/// [`BUFFERED_ENTRY_SIZE`] without holding the lock, larger entries are decompressed while
+/// holding it, so other workers wait for them.
+pub struct ExtractArchiveWriter {
+    sink: Mutex<ArchiveSink>,
+}
+
+impl ExtractArchiveWriter {
+    pub fn create(
+        path: impl AsRef<Path>,
+        format: ExtractArchiveFormat,
+        r#override: bool,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut open_options = std::fs::OpenOptions::new();
+        open_options.write(true).create(true);
+        if r#override {
+            open_options.truncate(true);
+        } else {
+            open_options.create_new(true);
+        }
+        let file = open_options.open(path).map_err(|e| Error::FileIO {
+            path: path.to_string_lossy().to_string(),
+            source: e,
+        })?;
+        let writer = BufWriter::new(file);
+
+        let sink = match format {
+            ExtractArchiveFormat::Zip => ArchiveSink::Zip(Box::new(ZipWriter::new(writer))),
+            ExtractArchiveFormat::Tar => {
+                let mut builder = tar::Builder::new(writer);
+                builder.mode(tar::HeaderMode::Deterministic);
+                ArchiveSink::Tar(builder)
+            }
+        };
+        Ok(Self {
+            sink: Mutex::new(sink),
+        })
+    }
+
+    /// Add an entry to the archive.
+    ///
+    /// `size` is the uncompressed size of the entry, required by tar headers.
+    /// Returns the number of bytes written, an error if the reader holds another size.
+    /// A large entry that ends early has already been written to the archive by then.
+    pub fn append(&self, path: &Path, size: u64, reader: &mut dyn Read) -> io::Result<u64> {
+        let name = archive_entry_name(path);
+        let mut limited = (&mut *reader).take(size);
+        if size <= BUFFERED_ENTRY_SIZE {
+            let mut data = Vec::with_capacity(size as usize);
+            limited.read_to_end(&mut data)?;
+            check_entry_size(&name, size, data.len() as u64, reader)?;
+            self.write_entry(&name, size, &mut data.as_slice())?;
+        } else {
+            self.write_entry(&name, size, &mut limited)?;
+            let read = size - limited.limit();
+            check_entry_size(&name, size, read, reader)?;
+        }
+        Ok(size)
+    }
+
+    fn write_entry(&self, name: &str, size: u64, reader: &mut dyn Read) -> io::Result<()> {
+        match &mut *self.sink.lock() {
+            ArchiveSink::Zip(zip) => {
+                let options = SimpleFileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .large_file(size >= u32::MAX as u64);
+                zip.start_file(name, options).map_err(io::Error::other)?;
+                io::copy(reader, zip.as_mut())?;
+            }
+            ArchiveSink::Tar(tar) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(size);
+                header.set_mode(0o644);
+                tar.append_data(&mut header, name, reader)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the archive trailer and flush.
+    pub fn finish(self) -> Result<()> {
+        let mut writer = match self.sink.into_inner() {
+            ArchiveSink::Zip(zip) => (*zip).finish()?,
+            ArchiveSink::Tar(tar) => tar.into_inner()?,
+        };
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Check that `read` bytes of an entry of `size` were read and `reader` has nothing left.
+fn check_entry_size(name: &str, size: u64, read: u64, reader: &mut dyn Read) -> io::Result<()> {
+    if read < size {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("{name} ended after {read} of {size} bytes"),
+        ));
+    }
+    if reader.read(&mut [0])? != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{name} is larger than {size} bytes"),
+        ));
+    }
+    Ok(())
+}
+
+/// Entry name with `/` separators, without root or parent components.
+fn archive_entry_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use zip::ZipArchive;
+
+    use super::*;
+
+    #[test]
+    fn zip_output_keeps_entry_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.zip");
+        let writer = ExtractArchiveWriter::create(&path, ExtractArchiveFormat::Zip, false).unwrap();
+        writer
+            .append(Path::new("natives/STM/a.tex"), 3, &mut Cursor::new(b"abc"))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let mut zip = ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let mut data = String::new();
+        zip.by_name("natives/STM/a.tex")
+            .unwrap()
+            .read_to_string(&mut data)
+            .unwrap();
+        assert_eq!(data, "abc");
+
+        assert!(ExtractArchiveWriter::create(&path, ExtractArchiveFormat::Zip, false).is_err());
+    }
+
+    #[test]
+    fn tar_output_keeps_entry_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.tar");
+        let writer = ExtractArchiveWriter::create(&path, ExtractArchiveFormat::Tar, false).unwrap();
+        let long_name = format!("natives/STM/{}/a.tex", "d".repeat(120));
+        writer
+            .append(Path::new(&long_name), 3, &mut Cursor::new(b"abc"))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let mut archive = tar::Archive::new(File::open(&path).unwrap());
+        let mut entry = archive.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(entry.path().unwrap().to_string_lossy(), long_name);
+        let mut data = String::new();
+        entry.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "abc");
+    }
+
+    #[test]
+    fn entries_of_another_size_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.tar");
+        let writer = ExtractArchiveWriter::create(&path, ExtractArchiveFormat::Tar, false).unwrap();
+
+        let error = writer
+            .append(Path::new("a.tex"), 5, &mut Cursor::new(b"abc"))
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        let error = writer
+            .append(Path::new("b.tex"), 2, &mut Cursor::new(b"abc"))
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        writer
+            .append(Path::new("c.tex"), 3, &mut Cursor::new(b"abc"))
+            .unwrap();
+        writer.finish().unwrap();
+
+        // rejected entries are not written
+        let mut archive = tar::Archive::new(File::open(&path).unwrap());
+        let names = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["c.tex"]);
+    }
+}
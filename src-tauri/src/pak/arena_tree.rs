@@ -0,0 +1,761 @@
+use hashbrown::HashMap;
+use ree_pak_core::pak::{CompressionType, PakEntry};
+use rustc_hash::FxBuildHasher;
+use smol_str::SmolStr;
+
+use crate::common::JsSafeHash;
+
+use super::{
+    PakId,
+    file_type::FileType,
+    streaming::StreamingPair,
+    tree::{PakTreeStats, SortableNode},
+};
+
+/// Id of a node in an [`ArenaTree`].
+pub type NodeId = u32;
+/// Id of an interned path segment.
+pub type SegmentId = u32;
+
+/// Id of the virtual root node.
+pub const ROOT_NODE_ID: NodeId = 0;
+
+/// Entry data stored in file nodes.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryData {
+    pub hash: u64,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub is_compressed: bool,
+    pub belongs_to: PakId,
+    /// Type detected from the magic, only for entries missing from the file list.
+    pub file_type: Option<FileType>,
+}
+
+impl EntryData {
+    pub fn from_entry(belongs_to: PakId, entry: &PakEntry) -> Self {
+        Self {
+            hash: entry.hash(),
+            compressed_size: entry.compressed_size(),
+            uncompressed_size: entry.uncompressed_size(),
+            is_compressed: entry.compression_type() != CompressionType::None,
+            belongs_to,
+            file_type: None,
+        }
+    }
+}
+
+/// Path segments, directory names are interned as they repeat a lot across entries.
+///
+/// File names are mostly unique and are stored without deduplication.
+#[derive(Debug, Clone, Default)]
+struct SegmentInterner {
+    ids: HashMap<SmolStr, SegmentId, FxBuildHasher>,
+    segments: Vec<SmolStr>,
+}
+
+impl SegmentInterner {
+    fn intern(&mut self, segment: &str) -> SegmentId {
+        if let Some(id) = self.ids.get(segment) {
+            return *id;
+        }
+
+        let id = self.push(SmolStr::new(segment));
+        self.ids.insert(self.segments[id as usize].clone(), id);
+        id
+    }
+
+    fn push(&mut self, segment: SmolStr) -> SegmentId {
+        let id = self.segments.len() as SegmentId;
+        self.segments.push(segment);
+        id
+    }
+
+    fn get(&self, id: SegmentId) -> &SmolStr {
+        &self.segments[id as usize]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct ArenaNode {
+    pub(super) segment: SegmentId,
+    pub(super) is_dir: bool,
+    pub(super) is_overridden: bool,
+    pub(super) hash: Option<u64>,
+    /// Entry size, or total size of active children for directories.
+    pub(super) compressed_size: u64,
+    /// Entry size, or total size of active children for directories.
+    pub(super) uncompressed_size: u64,
+    pub(super) is_compressed: bool,
+    pub(super) belongs_to: Option<PakId>,
+    pub(super) file_type: Option<FileType>,
+    pub(super) children: Vec<NodeId>,
+}
+
+impl ArenaNode {
+    fn directory(segment: SegmentId) -> Self {
+        Self {
+            segment,
+            is_dir: true,
+            is_overridden: false,
+            hash: None,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            is_compressed: false,
+            belongs_to: None,
+            file_type: None,
+            children: vec![],
+        }
+    }
+
+    fn file(segment: SegmentId, entry: &EntryData) -> Self {
+        Self {
+            segment,
+            is_dir: false,
+            is_overridden: false,
+            hash: Some(entry.hash),
+            compressed_size: entry.compressed_size,
+            uncompressed_size: entry.uncompressed_size,
+            is_compressed: entry.is_compressed,
+            belongs_to: Some(entry.belongs_to),
+            file_type: entry.file_type,
+            children: vec![],
+        }
+    }
+}
+
+/// File tree stored in a flat arena, with interned path segments.
+///
+/// Each pak is built into its own tree, then trees are merged in load order.
+/// Overridden entries are always kept, marked with `is_overridden`.
+#[derive(Debug, Clone)]
+pub struct ArenaTree {
+    segments: SegmentInterner,
+    nodes: Vec<ArenaNode>,
+    /// (parent, segment) -> child directory, only kept while building.
+    lookup: HashMap<(NodeId, SegmentId), NodeId, FxBuildHasher>,
+    /// natives file -> file of the same path under `natives/<platform>/streaming/`.
+    streaming_pairs: HashMap<NodeId, NodeId, FxBuildHasher>,
+}
+
+impl Default for ArenaTree {
+    fn default() -> Self {
+        let mut segments = SegmentInterner::default();
+        let root_segment = segments.intern("");
+        Self {
+            segments,
+            nodes: vec![ArenaNode::directory(root_segment)],
+            lookup: HashMap::default(),
+            streaming_pairs: HashMap::default(),
+        }
+    }
+}
+
+impl ArenaTree {
+    /// Build the tree of a single pak.
+    ///
+    /// Paths are split by `/`, empty segments are ignored.
+    pub fn from_entries<P: AsRef<str>>(entries: impl IntoIterator<Item = (P, EntryData)>) -> Self {
+        let mut tree = Self::default();
+        for (path, entry) in entries {
+            tree.insert(path.as_ref(), &entry);
+        }
+        tree
+    }
+
+    /// Merge trees of paks given in load order, newer entries override older ones.
+    ///
+    /// The first tree is reused as the base, it is usually the largest one.
+    pub fn merge(parts: Vec<ArenaTree>) -> Self {
+        let mut parts = parts.into_iter();
+        let mut tree = parts.next().unwrap_or_default();
+
+        // hash -> active file node
+        let mut active_files = HashMap::with_capacity_and_hasher(tree.nodes.len(), FxBuildHasher);
+        tree.mark_overridden(0, &mut active_files);
+        for part in parts {
+            let first_new_node = tree.nodes.len();
+            tree.merge_part(&part);
+            tree.mark_overridden(first_new_node, &mut active_files);
+        }
+
+        tree.finish();
+        tree
+    }
+
+    /// Finish the tree of a single pak without merging.
+    ///
+    /// `is_overridden` tells if an entry is shadowed by a newer pak.
+    pub fn finish_single(mut self, is_overridden: impl Fn(u64) -> bool) -> Self {
+        for node in &mut self.nodes {
+            if let Some(hash) = node.hash {
+                node.is_overridden = is_overridden(hash);
+            }
+        }
+        self.finish();
+        self
+    }
+
+    /// Stats of all files in the tree, used for trees of single paks.
+    pub fn pak_stats(&self) -> PakTreeStats {
+        let mut stats = PakTreeStats::default();
+        for node in self.nodes.iter().filter(|node| !node.is_dir) {
+            stats.file_count += 1;
+            stats.compressed_size += node.compressed_size;
+            stats.uncompressed_size += node.uncompressed_size;
+            if node.is_overridden {
+                stats.overridden_count += 1;
+                stats.overridden_compressed_size += node.compressed_size;
+                stats.overridden_uncompressed_size += node.uncompressed_size;
+            }
+        }
+        stats
+    }
+
+    /// Streaming counterpart of an active file.
+    pub(super) fn streaming_pair(&self, id: NodeId) -> Option<StreamingPair> {
+        let streaming_id = self.streaming_pairs.get(&id)?;
+        let node = &self.nodes[id as usize];
+        let streaming = &self.nodes[*streaming_id as usize];
+        Some(StreamingPair {
+            hash: JsSafeHash::from_u64(streaming.hash?),
+            belongs_to: streaming.belongs_to?,
+            compressed_size: streaming.compressed_size,
+            uncompressed_size: streaming.uncompressed_size,
+            combined_compressed_size: node.compressed_size + streaming.compressed_size,
+            combined_uncompressed_size: node.uncompressed_size + streaming.uncompressed_size,
+        })
+    }
+
+    pub(super) fn node(&self, id: NodeId) -> Option<&ArenaNode> {
+        self.nodes.get(id as usize)
+    }
+
+    pub(super) fn node_ref(&self, id: NodeId) -> ArenaNodeRef<'_> {
+        ArenaNodeRef { tree: self, id }
+    }
+
+    pub(super) fn name(&self, id: NodeId) -> &SmolStr {
+        self.segments.get(self.nodes[id as usize].segment)
+    }
+
+    pub(super) fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.nodes[id as usize].children
+    }
+
+    /// Visible children, overridden entries are skipped unless `show_overridden` is set.
+    pub(super) fn visible_children(
+        &self,
+        id: NodeId,
+        show_overridden: bool,
+    ) -> impl Iterator<Item = NodeId> + '_ {
+        self.children(id)
+            .iter()
+            .copied()
+            .filter(move |child| show_overridden || !self.nodes[*child as usize].is_overridden)
+    }
+
+    fn insert(&mut self, path: &str, entry: &EntryData) {
+        let mut components = path
+            .split('/')
+            .filter(|component| !component.is_empty())
+            .peekable();
+
+        let mut parent = ROOT_NODE_ID;
+        while let Some(component) = components.next() {
+            if components.peek().is_some() {
+                let segment = self.segments.intern(component);
+                parent = self.get_or_insert_dir(parent, segment);
+            } else {
+                let segment = self.segments.push(SmolStr::new(component));
+                self.push_node(parent, ArenaNode::file(segment, entry));
+            }
+        }
+    }
+
+    fn get_or_insert_dir(&mut self, parent: NodeId, segment: SegmentId) -> NodeId {
+        if let Some(id) = self.lookup.get(&(parent, segment)) {
+            return *id;
+        }
+
+        let id = self.push_node(parent, ArenaNode::directory(segment));
+        self.lookup.insert((parent, segment), id);
+        id
+    }
+
+    fn push_node(&mut self, parent: NodeId, node: ArenaNode) -> NodeId {
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(node);
+        self.nodes[parent as usize].children.push(id);
+        id
+    }
+
+    /// Copy nodes of a newer pak into this tree.
+    fn merge_part(&mut self, part: &ArenaTree) {
+        // directory segments of the part, interned on first use
+        let mut dir_segments = vec![None; part.segments.segments.len()];
+
+        let mut stack = vec![(ROOT_NODE_ID, ROOT_NODE_ID)];
+        while let Some((part_parent, parent)) = stack.pop() {
+            for &part_child in part.children(part_parent) {
+                let node = &part.nodes[part_child as usize];
+                let name = part.segments.get(node.segment);
+                if node.is_dir {
+                    let segment = *dir_segments[node.segment as usize]
+                        .get_or_insert_with(|| self.segments.intern(name));
+                    stack.push((part_child, self.get_or_insert_dir(parent, segment)));
+                } else {
+                    let segment = self.segments.push(name.clone());
+                    self.push_node(
+                        parent,
+                        ArenaNode {
+                            segment,
+                            ..node.clone()
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Mark overridden files from `first_node` on, which all belong to the same pak.
+    ///
+    /// Within a pak the first entry wins, across paks the newer one wins.
+    fn mark_overridden(
+        &mut self,
+        first_node: usize,
+        active_files: &mut HashMap<u64, NodeId, FxBuildHasher>,
+    ) {
+        for id in first_node..self.nodes.len() {
+            let Some(hash) = self.nodes[id].hash else {
+                continue;
+            };
+
+            match active_files.insert(hash, id as NodeId) {
+                Some(previous) if previous as usize >= first_node => {
+                    active_files.insert(hash, previous);
+                    self.nodes[id].is_overridden = true;
+                }
+                Some(previous) => self.nodes[previous as usize].is_overridden = true,
+                None => {}
+            }
+        }
+    }
+
+    /// Pair active files with their mirrors under `natives/<platform>/streaming/`.
+    fn pair_streaming(&mut self) {
+        let mut stack = vec![];
+        for &natives in self.children(ROOT_NODE_ID) {
+            if !self.name(natives).eq_ignore_ascii_case("natives") {
+                continue;
+            }
+            for &platform in self.children(natives) {
+                if let Some(streaming) = self.find_child_dir(platform, "streaming") {
+                    stack.push((platform, streaming));
+                }
+            }
+        }
+
+        let mut pairs = HashMap::default();
+        while let Some((natives_dir, streaming_dir)) = stack.pop() {
+            let natives_children = self
+                .visible_children(natives_dir, false)
+                .map(|id| ((self.name(id).as_str(), self.nodes[id as usize].is_dir), id))
+                .collect::<HashMap<_, _>>();
+            for streaming_id in self.visible_children(streaming_dir, false) {
+                let is_dir = self.nodes[streaming_id as usize].is_dir;
+                let Some(&natives_id) =
+                    natives_children.get(&(self.name(streaming_id).as_str(), is_dir))
+                else {
+                    continue;
+                };
+                if is_dir {
+                    stack.push((natives_id, streaming_id));
+                } else {
+                    pairs.insert(natives_id, streaming_id);
+                }
+            }
+        }
+        self.streaming_pairs = pairs;
+    }
+
+    fn find_child_dir(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        self.children(id).iter().copied().find(|child| {
+            self.nodes[*child as usize].is_dir && self.name(*child).eq_ignore_ascii_case(name)
+        })
+    }
+
+    /// Aggregate directory sizes and drop build-only data.
+    ///
+    /// Children always have larger ids than their parent,
+    /// so visiting nodes in reverse aggregates bottom-up.
+    fn finish(&mut self) {
+        for id in (0..self.nodes.len()).rev() {
+            if !self.nodes[id].is_dir {
+                continue;
+            }
+
+            let (compressed_size, uncompressed_size) = self.nodes[id]
+                .children
+                .iter()
+                .map(|child_id| &self.nodes[*child_id as usize])
+                .filter(|child| !child.is_overridden)
+                .fold((0, 0), |(compressed, uncompressed), child| {
+                    (
+                        compressed + child.compressed_size,
+                        uncompressed + child.uncompressed_size,
+                    )
+                });
+            let node = &mut self.nodes[id];
+            node.compressed_size = compressed_size;
+            node.uncompressed_size = uncompressed_size;
+        }
+
+        self.pair_streaming();
+        self.lookup = HashMap::default();
+        self.segments.ids = HashMap::default();
+        self.nodes.shrink_to_fit();
+        self.segments.segments.shrink_to_fit();
+    }
+
+    /// Approximate heap usage in bytes.
+    #[cfg(test)]
+    fn heap_size(&self) -> usize {
+        let segments = self.segments.segments.capacity() * size_of::<SmolStr>()
+            + self.segments.ids.capacity() * (size_of::<SmolStr>() + size_of::<SegmentId>())
+            + self
+                .segments
+                .segments
+                .iter()
+                .filter(|segment| segment.is_heap_allocated())
+                .map(|segment| segment.len())
+                .sum::<usize>();
+        let nodes = self.nodes.capacity() * size_of::<ArenaNode>()
+            + self
+                .nodes
+                .iter()
+                .map(|node| node.children.capacity() * size_of::<NodeId>())
+                .sum::<usize>();
+        segments + nodes
+    }
+}
+
+/// A node borrowed with its tree, to resolve the interned name.
+pub(super) struct ArenaNodeRef<'a> {
+    tree: &'a ArenaTree,
+    id: NodeId,
+}
+
+impl ArenaNodeRef<'_> {
+    fn node(&self) -> &ArenaNode {
+        &self.tree.nodes[self.id as usize]
+    }
+}
+
+impl SortableNode for ArenaNodeRef<'_> {
+    fn is_dir(&self) -> bool {
+        self.node().is_dir
+    }
+
+    fn name(&self) -> &str {
+        self.tree.name(self.id)
+    }
+
+    fn compressed_size(&self) -> u64 {
+        self.node().compressed_size
+    }
+
+    fn uncompressed_size(&self) -> u64 {
+        self.node().uncompressed_size
+    }
+
+    fn belongs_to(&self) -> Option<PakId> {
+        self.node().belongs_to
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use rayon::prelude::*;
+
+    use super::*;
+    use crate::pak::{
+        FileTreeStats, insert_tree_path,
+        test_util::{entry, new_pak_id},
+        tree::{FileTreeNode, RenderTreeNode, RenderTreeOptions},
+    };
+
+    fn find_child(tree: &ArenaTree, parent: NodeId, name: &str) -> Vec<NodeId> {
+        tree.children(parent)
+            .iter()
+            .copied()
+            .filter(|id| tree.name(*id) == name)
+            .collect()
+    }
+
+    #[test]
+    fn arena_tree_shares_directories_and_segments() {
+        let pak = new_pak_id();
+        let part = ArenaTree::from_entries([
+            ("natives/stm/a.tex", entry(1, 10, pak)),
+            ("natives/stm/b.tex", entry(2, 20, pak)),
+            ("natives/x64/stm/c.tex", entry(3, 30, pak)),
+        ]);
+        let tree = ArenaTree::merge(vec![part]);
+
+        let natives = find_child(&tree, ROOT_NODE_ID, "natives");
+        assert_eq!(natives.len(), 1);
+        assert_eq!(tree.node(ROOT_NODE_ID).unwrap().uncompressed_size, 60);
+        assert_eq!(tree.children(natives[0]).len(), 2);
+        // "stm" is interned once, plus root, natives, a.tex, b.tex, x64, c.tex
+        assert_eq!(tree.segments.segments.len(), 7);
+    }
+
+    #[test]
+    fn arena_tree_merge_prefers_newer_paks() {
+        let old = new_pak_id();
+        let new = new_pak_id();
+        let parts = vec![
+            ArenaTree::from_entries([
+                ("A/x.tex", entry(1, 100, old)),
+                ("A/y.tex", entry(2, 5, old)),
+            ]),
+            ArenaTree::from_entries([("A/x.tex", entry(1, 12, new))]),
+        ];
+
+        let tree = ArenaTree::merge(parts);
+        let a = find_child(&tree, ROOT_NODE_ID, "A")[0];
+        assert_eq!(tree.node(a).unwrap().uncompressed_size, 17);
+
+        let x = find_child(&tree, a, "x.tex");
+        assert_eq!(x.len(), 2);
+        let (overridden, active): (Vec<_>, Vec<_>) = x
+            .iter()
+            .map(|id| tree.node(*id).unwrap())
+            .partition(|node| node.is_overridden);
+        assert_eq!(overridden[0].belongs_to, Some(old));
+        assert_eq!(active[0].belongs_to, Some(new));
+        assert_eq!(tree.visible_children(a, false).count(), 2);
+        assert_eq!(tree.visible_children(a, true).count(), 3);
+    }
+
+    #[test]
+    fn arena_tree_keeps_first_duplicate_within_a_pak() {
+        let pak = new_pak_id();
+        let part = ArenaTree::from_entries([
+            ("A/x.tex", entry(1, 1, pak)),
+            ("A/x.tex", entry(1, 100, pak)),
+        ]);
+        let tree = ArenaTree::merge(vec![part]);
+
+        assert_eq!(tree.node(ROOT_NODE_ID).unwrap().uncompressed_size, 1);
+    }
+
+    #[test]
+    fn arena_tree_pairs_streaming_files() {
+        let old = new_pak_id();
+        let new = new_pak_id();
+        let parts = vec![
+            ArenaTree::from_entries([
+                ("natives/STM/Art/a.tex.1", entry(1, 10, old)),
+                ("natives/STM/Art/b.tex.1", entry(2, 10, old)),
+            ]),
+            ArenaTree::from_entries([("natives/STM/streaming/Art/a.tex.1", entry(3, 90, new))]),
+        ];
+        let tree = ArenaTree::merge(parts);
+
+        let natives = find_child(&tree, ROOT_NODE_ID, "natives")[0];
+        let stm = find_child(&tree, natives, "STM")[0];
+        let art = find_child(&tree, stm, "Art")[0];
+        let a = find_child(&tree, art, "a.tex.1")[0];
+        let b = find_child(&tree, art, "b.tex.1")[0];
+
+        let pair = tree.streaming_pair(a).unwrap();
+        assert_eq!(pair.hash.hash_u64(), 3);
+        assert_eq!(pair.belongs_to, new);
+        assert_eq!(pair.combined_uncompressed_size, 100);
+        assert!(tree.streaming_pair(b).is_none());
+    }
+
+    const BENCH_ENTRY_COUNT: usize = 500_000;
+    const BENCH_PAK_COUNT: usize = 8;
+
+    fn synthetic_paths() -> Vec<String> {
+        (0..BENCH_ENTRY_COUNT)
+            .map(|i| {
+                let category = ["Character", "Environment", "Weapon", "Effect", "GUI"][i % 5];
+                format!(
+                    "natives/STM/Art/Model/{category}/{:03}/{:03}/m{i:07}_body.mesh.2109148288",
+                    i % 97,
+                    i % 53
+                )
+            })
+            .collect()
+    }
+
+    fn file_tree_heap_size(children: &HashMap<SmolStr, FileTreeNode>) -> usize {
+        children.capacity() * (size_of::<SmolStr>() + size_of::<FileTreeNode>())
+            + children
+                .iter()
+                .map(|(key, node)| {
+                    let key_heap = if key.is_heap_allocated() {
+                        key.len()
+                    } else {
+                        0
+                    };
+                    let name_heap = if node.info.relative_path.is_heap_allocated() {
+                        node.info.relative_path.len()
+                    } else {
+                        0
+                    };
+                    key_heap + name_heap + file_tree_heap_size(&node.children)
+                })
+                .sum::<usize>()
+    }
+
+    /// Convert the legacy tree into render nodes, as the old pipeline did.
+    fn legacy_render_node(node: &FileTreeNode) -> RenderTreeNode {
+        let children = node
+            .children
+            .values()
+            .map(legacy_render_node)
+            .collect::<Vec<_>>();
+        let (compressed_size, uncompressed_size) = if node.info.is_dir {
+            children.iter().filter(|child| !child.is_overridden).fold(
+                (0, 0),
+                |(compressed, uncompressed), child| {
+                    (
+                        compressed + child.compressed_size,
+                        uncompressed + child.uncompressed_size,
+                    )
+                },
+            )
+        } else {
+            (node.info.compressed_size, node.info.uncompressed_size)
+        };
+
+        RenderTreeNode {
+            is_dir: node.info.is_dir,
+            name: node.info.relative_path.clone(),
+            hash: node.info.hash,
+            compressed_size,
+            uncompressed_size,
+            belongs_to: node.info.belongs_to,
+            is_overridden: node.info.is_overridden,
+            file_type: node.info.file_type,
+            pak_stats: None,
+            variants: None,
+            streaming: None,
+            children,
+        }
+    }
+
+    fn best_of<T>(runs: usize, mut f: impl FnMut() -> T) -> (T, Duration) {
+        let mut best = None;
+        for _ in 0..runs {
+            let start = Instant::now();
+            let value = f();
+            let elapsed = start.elapsed();
+            if best.as_ref().is_none_or(|(_, best)| elapsed < *best) {
+                best = Some((value, elapsed));
+            }
+        }
+        best.unwrap()
+    }
+
+    /// Compare the legacy `HashMap` tree pipeline with the arena tree.
+    ///
+    /// Run with `cargo test --release bench_tree_build -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_tree_build() {
+        const RUNS: usize = 3;
+
+        let paths = synthetic_paths();
+        let paks = (0..BENCH_PAK_COUNT)
+            .map(|_| new_pak_id())
+            .collect::<Vec<_>>();
+        let pak_of = |i: usize| paks[i % BENCH_PAK_COUNT];
+        // sorting is the same for both pipelines
+        let options: RenderTreeOptions = serde_json::from_str(r#"{"sortByName": false}"#).unwrap();
+
+        let ((legacy_tree, stats), legacy_build) = best_of(RUNS, || {
+            let mut root_children = HashMap::new();
+            let mut stats = FileTreeStats::default();
+            for (i, path) in paths.iter().enumerate() {
+                insert_tree_path(
+                    &mut root_children,
+                    &mut stats,
+                    path,
+                    &entry(i as u64, 1, pak_of(i)),
+                    false,
+                );
+            }
+            (root_children, stats)
+        });
+        let (_, legacy_render) = best_of(RUNS, || {
+            legacy_tree
+                .values()
+                .map(legacy_render_node)
+                .collect::<Vec<_>>()
+        });
+        let legacy_heap = file_tree_heap_size(&legacy_tree);
+        drop(legacy_tree);
+
+        let build_parts = |parallel: bool| {
+            let build_part = |pak_index: usize| {
+                ArenaTree::from_entries(
+                    paths
+                        .iter()
+                        .enumerate()
+                        .skip(pak_index)
+                        .step_by(BENCH_PAK_COUNT)
+                        .map(|(i, path)| (path, entry(i as u64, 1, pak_of(i)))),
+                )
+            };
+            if parallel {
+                (0..BENCH_PAK_COUNT)
+                    .into_par_iter()
+                    .map(build_part)
+                    .collect()
+            } else {
+                (0..BENCH_PAK_COUNT).map(build_part).collect::<Vec<_>>()
+            }
+        };
+
+        let (sequential, sequential_build) = best_of(RUNS, || ArenaTree::merge(build_parts(false)));
+        let (parallel, parallel_build) = best_of(RUNS, || ArenaTree::merge(build_parts(true)));
+        let (render_nodes, arena_render) = best_of(RUNS, || {
+            RenderTreeNode::from_arena_tree(&parallel, &options, &paks)
+        });
+        let arena_heap = parallel.heap_size();
+
+        println!(
+            "entries: {BENCH_ENTRY_COUNT}, paks: {BENCH_PAK_COUNT}, threads: {}",
+            rayon::current_num_threads()
+        );
+        println!(
+            "legacy: build {} ms, render {} ms, tree ~{} MiB",
+            legacy_build.as_millis(),
+            legacy_render.as_millis(),
+            legacy_heap >> 20
+        );
+        println!(
+            "arena: build {} ms (sequential {} ms), render {} ms, tree ~{} MiB",
+            parallel_build.as_millis(),
+            sequential_build.as_millis(),
+            arena_render.as_millis(),
+            arena_heap >> 20
+        );
+
+        assert_eq!(sequential.nodes.len(), parallel.nodes.len());
+        assert_eq!(
+            parallel.node(ROOT_NODE_ID).unwrap().uncompressed_size,
+            stats.uncompressed_size
+        );
+        assert_eq!(render_nodes.len(), 1);
+        assert!(arena_heap < legacy_heap);
+    }
+}
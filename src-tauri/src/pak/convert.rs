@@ -0,0 +1,55 @@
+use serde::Deserialize;
+
+/// Conversion applied to entries as they are extracted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum ExtractConverter {
+    /// `.tex` to an image next to the extracted file.
+    Texture { format: ConvertTextureFormat },
+    /// `.bnk` and `.pck` containers to one `.wav` per wem.
+    Audio,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConvertTextureFormat {
+    Png,
+    Dds,
+}
+
+impl ConvertTextureFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Dds => "dds",
+        }
+    }
+}
+
+impl ExtractConverter {
+    /// Export format for entries of a format handler, `None` if the converter doesn't apply.
+    pub fn export_format(&self, handler_id: &str) -> Option<&'static str> {
+        match (self, handler_id) {
+            (ExtractConverter::Texture { format }, "texture") => Some(format.extension()),
+            (ExtractConverter::Audio, "audio") => Some("wav"),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converters_match_by_format_handler() {
+        let converters: Vec<ExtractConverter> =
+            serde_json::from_str(r#"[{"kind": "texture", "format": "dds"}, {"kind": "audio"}]"#)
+                .unwrap();
+
+        assert_eq!(converters[0].export_format("texture"), Some("dds"));
+        assert_eq!(converters[0].export_format("audio"), None);
+        assert_eq!(converters[1].export_format("audio"), Some("wav"));
+        assert_eq!(converters[1].export_format("mesh"), None);
+    }
+}
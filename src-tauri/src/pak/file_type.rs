@@ -0,0 +1,264 @@
+use std::{
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use ree_pak_core::{pak::PakEntry, pakfile::PakFile};
+use serde::Serialize;
+
+/// Number of leading bytes read to detect a file type.
+pub const HEADER_SIZE: usize = 16;
+
+/// File type detected from the magic of an entry.
+///
+/// Used for entries missing from the file list, which have no extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileType {
+    Tex,
+    Mesh,
+    Mdf2,
+    Bnk,
+    Pck,
+    User,
+    Rsz,
+    Scn,
+    Pfb,
+    Msg,
+    Mot,
+    Motlist,
+    Motbank,
+    Efx,
+    Gui,
+    Rcol,
+    Cfil,
+    Dds,
+    Png,
+}
+
+/// (offset, magic, type), checked in order.
+const MAGICS: &[(usize, &[u8], FileType)] = &[
+    (0, b"TEX\0", FileType::Tex),
+    (0, b"MESH", FileType::Mesh),
+    (0, b"MPLY", FileType::Mesh),
+    (0, b"MDF\0", FileType::Mdf2),
+    (0, b"BKHD", FileType::Bnk),
+    (0, b"AKPK", FileType::Pck),
+    (0, b"USR\0", FileType::User),
+    (0, b"RSZ\0", FileType::Rsz),
+    (0, b"SCN\0", FileType::Scn),
+    (0, b"PFB\0", FileType::Pfb),
+    (4, b"GMSG", FileType::Msg),
+    (4, b"mot ", FileType::Mot),
+    (4, b"mlst", FileType::Motlist),
+    (4, b"mbnk", FileType::Motbank),
+    (4, b"EFXR", FileType::Efx),
+    (4, b"GUIR", FileType::Gui),
+    (0, b"RCOL", FileType::Rcol),
+    (0, b"CFIL", FileType::Cfil),
+    (0, b"DDS ", FileType::Dds),
+    (0, b"\x89PNG", FileType::Png),
+];
+
+impl FileType {
+    /// Detect a file type from the first bytes of a file.
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        MAGICS.iter().find_map(|(offset, magic, file_type)| {
+            header
+                .get(*offset..*offset + magic.len())
+                .is_some_and(|bytes| bytes == *magic)
+                .then_some(*file_type)
+        })
+    }
+
+    /// Extension without version suffix, e.g. `tex`.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Tex => "tex",
+            Self::Mesh => "mesh",
+            Self::Mdf2 => "mdf2",
+            Self::Bnk => "bnk",
+            Self::Pck => "pck",
+            Self::User => "user",
+            Self::Rsz => "rsz",
+            Self::Scn => "scn",
+            Self::Pfb => "pfb",
+            Self::Msg => "msg",
+            Self::Mot => "mot",
+            Self::Motlist => "motlist",
+            Self::Motbank => "motbank",
+            Self::Efx => "efx",
+            Self::Gui => "gui",
+            Self::Rcol => "rcol",
+            Self::Cfil => "cfil",
+            Self::Dds => "dds",
+            Self::Png => "png",
+        }
+    }
+
+    /// Append the extension to a path, e.g. `_Unknown/ABCD` -> `_Unknown/ABCD.tex`.
+    pub fn append_extension(self, path: &mut PathBuf) {
+        path.as_mut_os_string()
+            .push(format!(".{}", self.extension()));
+    }
+}
+
+/// Read the leading bytes used for detection, shorter if the reader ends early.
+pub fn read_header(reader: &mut dyn Read) -> io::Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    reader.take(HEADER_SIZE as u64).read_to_end(&mut header)?;
+    Ok(header)
+}
+
+/// Detect the type of a pak entry by reading its first bytes.
+pub fn detect_entry(pakfile: &PakFile, entry: &PakEntry) -> Option<FileType> {
+    let mut reader = pakfile.open_entry(entry).ok()?;
+    let header = read_header(&mut reader).ok()?;
+    FileType::detect(&header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_magics_at_offsets() {
+        assert_eq!(FileType::detect(b"TEX\0\x24\0\0\0"), Some(FileType::Tex));
+        assert_eq!(FileType::detect(b"MPLY\x01\0\0\0"), Some(FileType::Mesh));
+        assert_eq!(
+            FileType::detect(b"\x22\0\0\0GMSG\x10\0\0\0"),
+            Some(FileType::Msg)
+        );
+        assert_eq!(FileType::detect(b"\x55\0\0\0mlst"), Some(FileType::Motlist));
+        assert_eq!(FileType::detect(b"TE"), None);
+        assert_eq!(FileType::detect(b"unknown data"), None);
+    }
+
+    /// Leading bytes of real files: magic, version and the first header fields.
+    fn header(parts: &[&[u8]]) -> Vec<u8> {
+        let mut header = parts.concat();
+        header.resize(HEADER_SIZE, 0);
+        header
+    }
+
+    #[test]
+    fn detect_real_headers() {
+        let cases = [
+            // magic, version, width and height
+            (
+                header(&[
+                    b"TEX\0",
+                    &241106027u32.to_le_bytes(),
+                    &[0x80, 0x07, 0x80, 0x07],
+                ]),
+                FileType::Tex,
+            ),
+            // magic, version, file size
+            (
+                header(&[
+                    b"MESH",
+                    &2109148288u32.to_le_bytes(),
+                    &0x6A50u32.to_le_bytes(),
+                ]),
+                FileType::Mesh,
+            ),
+            (
+                header(&[
+                    b"MPLY",
+                    &240827123u32.to_le_bytes(),
+                    &0x1F40u32.to_le_bytes(),
+                ]),
+                FileType::Mesh,
+            ),
+            // magic, version, material count
+            (
+                header(&[b"MDF\0", &1u16.to_le_bytes(), &3u16.to_le_bytes()]),
+                FileType::Mdf2,
+            ),
+            // section, section size, bank version
+            (
+                header(&[b"BKHD", &0x18u32.to_le_bytes(), &0x8Cu32.to_le_bytes()]),
+                FileType::Bnk,
+            ),
+            // magic, header size, version
+            (
+                header(&[b"AKPK", &0x5Cu32.to_le_bytes(), &1u32.to_le_bytes()]),
+                FileType::Pck,
+            ),
+            // magic, resource, user data and info counts
+            (
+                header(&[
+                    b"USR\0",
+                    &0u32.to_le_bytes(),
+                    &1u32.to_le_bytes(),
+                    &1u32.to_le_bytes(),
+                ]),
+                FileType::User,
+            ),
+            (header(&[b"RSZ\0", &0x10u32.to_le_bytes()]), FileType::Rsz),
+            (
+                header(&[b"SCN\0", &2u32.to_le_bytes(), &4u32.to_le_bytes()]),
+                FileType::Scn,
+            ),
+            (
+                header(&[b"PFB\0", &6u32.to_le_bytes(), &2u32.to_le_bytes()]),
+                FileType::Pfb,
+            ),
+            // version, magic, header size
+            (
+                header(&[&0x22u32.to_le_bytes(), b"GMSG", &0x10u64.to_le_bytes()]),
+                FileType::Msg,
+            ),
+            (
+                header(&[&0x2B0u32.to_le_bytes(), b"mot ", &0x20u64.to_le_bytes()]),
+                FileType::Mot,
+            ),
+            (
+                header(&[&0x63u32.to_le_bytes(), b"mlst", &0x40u64.to_le_bytes()]),
+                FileType::Motlist,
+            ),
+            (
+                header(&[&3u32.to_le_bytes(), b"mbnk", &0x30u64.to_le_bytes()]),
+                FileType::Motbank,
+            ),
+            (
+                header(&[&0x1E0_0000u32.to_le_bytes(), b"EFXR", &2u32.to_le_bytes()]),
+                FileType::Efx,
+            ),
+            (
+                header(&[&0x9_2D14u32.to_le_bytes(), b"GUIR", &0x50u64.to_le_bytes()]),
+                FileType::Gui,
+            ),
+            (
+                header(&[b"RCOL", &2u32.to_le_bytes(), &8u32.to_le_bytes()]),
+                FileType::Rcol,
+            ),
+            (header(&[b"CFIL", &7u32.to_le_bytes()]), FileType::Cfil),
+            // magic, header size, flags
+            (
+                header(&[
+                    b"DDS ",
+                    &124u32.to_le_bytes(),
+                    &0x000A_1007u32.to_le_bytes(),
+                ]),
+                FileType::Dds,
+            ),
+            // signature, IHDR chunk
+            (
+                header(&[b"\x89PNG\r\n\x1a\n", &13u32.to_be_bytes(), b"IHDR"]),
+                FileType::Png,
+            ),
+        ];
+
+        for (header, file_type) in cases {
+            assert_eq!(FileType::detect(&header), Some(file_type), "{header:02X?}");
+        }
+    }
+
+    #[test]
+    fn append_extension_keeps_hash_name() {
+        let mut path = PathBuf::from("_Unknown/10015D55056456A1");
+        FileType::Tex.append_extension(&mut path);
+        assert_eq!(path, PathBuf::from("_Unknown/10015D55056456A1.tex"));
+    }
+}
@@ -0,0 +1,289 @@
+use hashbrown::HashSet;
+use ree_pak_core::{pak::PakEntry, pakfile::PakFile};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::path_components::PathComponents;
+
+use super::{
+    ExtractOptions,
+    file_type::{self, FileType},
+    name_layers::LayeredNames,
+};
+
+/// Filters evaluated against entry paths while extracting.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractFilterOptions {
+    /// Extract entries matching any of these patterns, all entries if empty.
+    #[serde(default)]
+    pub include: Vec<PathPattern>,
+    /// Skip entries matching any of these patterns.
+    #[serde(default)]
+    pub exclude: Vec<PathPattern>,
+    /// Extensions without version suffix, e.g. `["mesh", "tex"]`.
+    ///
+    /// Entries missing from the file list match by the type detected from their magic.
+    pub extensions: Option<Vec<String>>,
+    /// Minimum uncompressed size in bytes, inclusive.
+    pub min_size: Option<u64>,
+    /// Maximum uncompressed size in bytes, inclusive.
+    pub max_size: Option<u64>,
+    /// Only extract entries missing from the file list.
+    #[serde(default)]
+    pub unknown_only: bool,
+}
+
+/// A case-insensitive pattern matched against the whole `/` separated path.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind", content = "pattern")]
+pub enum PathPattern {
+    /// `*` matches within a segment, `**` across segments and `?` one character.
+    Glob(String),
+    Regex(String),
+}
+
+/// Compiled extraction filters.
+#[derive(Debug)]
+pub struct ExtractFilter {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    extensions: Option<HashSet<String>>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    unknown_only: bool,
+    languages: Option<Vec<String>>,
+}
+
+impl ExtractFilter {
+    /// Compile filters of extract options.
+    ///
+    /// Returns `None` if nothing is filtered.
+    pub fn new(options: &ExtractOptions) -> Result<Option<Self>> {
+        if options.filter.is_none() && options.languages.is_none() {
+            return Ok(None);
+        }
+
+        let filter = options.filter.clone().unwrap_or_default();
+        let compile = |patterns: &[PathPattern]| {
+            patterns
+                .iter()
+                .map(PathPattern::compile)
+                .collect::<Result<Vec<_>>>()
+        };
+
+        Ok(Some(Self {
+            include: compile(&filter.include)?,
+            exclude: compile(&filter.exclude)?,
+            extensions: filter.extensions.map(|extensions| {
+                extensions
+                    .iter()
+                    .map(|ext| ext.trim_start_matches('.').to_ascii_lowercase())
+                    .collect()
+            }),
+            min_size: filter.min_size,
+            max_size: filter.max_size,
+            unknown_only: filter.unknown_only,
+            languages: options.languages.clone(),
+        }))
+    }
+
+    /// Check an entry against its path in the file list.
+    pub fn matches_entry(
+        &self,
+        file_name_table: &LayeredNames,
+        pakfile: &PakFile,
+        entry: &PakEntry,
+    ) -> bool {
+        let path = file_name_table.get_file_name(entry.hash());
+        self.matches(path, entry.uncompressed_size(), || {
+            file_type::detect_entry(pakfile, entry)
+        })
+    }
+
+    /// Check an entry, `path` is `None` if the entry is not in the file list.
+    ///
+    /// Unknown entries match extensions by the type `detect` reads from their magic,
+    /// it is only called for them.
+    pub fn matches(
+        &self,
+        path: Option<&str>,
+        uncompressed_size: u64,
+        detect: impl FnOnce() -> Option<FileType>,
+    ) -> bool {
+        if self.min_size.is_some_and(|min| uncompressed_size < min)
+            || self.max_size.is_some_and(|max| uncompressed_size > max)
+        {
+            return false;
+        }
+
+        let Some(path) = path else {
+            // unknown entries have no path for patterns to match against
+            return self.include.is_empty()
+                && self.extensions.as_ref().is_none_or(|extensions| {
+                    detect().is_some_and(|file_type| extensions.contains(file_type.extension()))
+                });
+        };
+        if self.unknown_only {
+            return false;
+        }
+
+        let path = path.replace('\\', "/");
+        if !self.include.is_empty() && !self.include.iter().any(|re| re.is_match(&path)) {
+            return false;
+        }
+        if self.exclude.iter().any(|re| re.is_match(&path)) {
+            return false;
+        }
+
+        let components = PathComponents::parse(&path);
+        if let Some(extensions) = &self.extensions {
+            let extension = components
+                .as_ref()
+                .and_then(PathComponents::extension)
+                .map(str::to_ascii_lowercase);
+            if !extension.is_some_and(|extension| extensions.contains(&extension)) {
+                return false;
+            }
+        }
+        if let Some(languages) = &self.languages
+            && let Some(language) = components.as_ref().and_then(PathComponents::language)
+        {
+            return languages
+                .iter()
+                .any(|lang| lang.eq_ignore_ascii_case(language));
+        }
+
+        true
+    }
+}
+
+impl PathPattern {
+    pub(crate) fn compile(&self) -> Result<Regex> {
+        let pattern = match self {
+            PathPattern::Glob(glob) => glob_to_regex(glob),
+            PathPattern::Regex(regex) => regex.clone(),
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| Error::InvalidExtractFilter(e.to_string()))
+    }
+}
+
+/// Translate a glob into an anchored regex.
+fn glob_to_regex(glob: &str) -> String {
+    let glob = glob.replace('\\', "/");
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches no directory at all
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(json: &str) -> ExtractFilter {
+        let options: ExtractOptions = serde_json::from_str(&format!(
+            r#"{{"outputPath": "", "override": false, "mode": "relativePath",
+                "extractAll": true, "extractFiles": [], "filter": {json}}}"#
+        ))
+        .unwrap();
+        ExtractFilter::new(&options).unwrap().unwrap()
+    }
+
+    #[test]
+    fn glob_matches_segments() {
+        let re = Regex::new(&glob_to_regex("natives/**/character/*.mesh.*")).unwrap();
+        assert!(re.is_match("natives/STM/Art/Model/character/a.mesh.2109148288"));
+        assert!(!re.is_match("natives/STM/Art/Model/character/sub/a.mesh.2109148288"));
+        assert!(
+            Regex::new(&glob_to_regex("**/a.tex"))
+                .unwrap()
+                .is_match("a.tex")
+        );
+    }
+
+    #[test]
+    fn extract_filter_combines_conditions() {
+        let filter = filter(
+            r#"{"include": [{"kind": "glob", "pattern": "natives/stm/art/model/**"}],
+                "exclude": [{"kind": "regex", "pattern": "_lod\\d"}],
+                "extensions": [".mesh"], "maxSize": 100}"#,
+        );
+
+        assert!(filter.matches(
+            Some("natives/STM/Art/Model/character/a.mesh.2109148288"),
+            10,
+            || None
+        ));
+        assert!(!filter.matches(
+            Some("natives/STM/Art/Model/character/a.mesh.2109148288"),
+            1000,
+            || None
+        ));
+        assert!(!filter.matches(
+            Some("natives/STM/Art/Model/character/a_lod1.mesh.1"),
+            10,
+            || None
+        ));
+        assert!(!filter.matches(
+            Some("natives/STM/Art/Model/character/a.tex.143230"),
+            10,
+            || None
+        ));
+        assert!(!filter.matches(None, 10, || None));
+    }
+
+    #[test]
+    fn extract_filter_unknown_only() {
+        let filter = filter(r#"{"unknownOnly": true}"#);
+
+        assert!(filter.matches(None, 10, || None));
+        assert!(!filter.matches(Some("natives/STM/a.tex.143230"), 10, || None));
+    }
+
+    #[test]
+    fn unknown_entries_match_extensions_by_detected_type() {
+        let filter = filter(r#"{"unknownOnly": true, "extensions": ["tex"]}"#);
+
+        assert!(filter.matches(None, 10, || Some(FileType::Tex)));
+        assert!(!filter.matches(None, 10, || Some(FileType::Mesh)));
+        assert!(!filter.matches(None, 10, || None));
+        assert!(!filter.matches(Some("natives/STM/a.tex.143230"), 10, || unreachable!()));
+    }
+
+    #[test]
+    fn invalid_regex_is_an_error() {
+        let options: ExtractOptions = serde_json::from_str(
+            r#"{"outputPath": "", "override": false, "mode": "relativePath",
+                "extractAll": true, "extractFiles": [],
+                "filter": {"include": [{"kind": "regex", "pattern": "("}]}}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ExtractFilter::new(&options),
+            Err(Error::InvalidExtractFilter(_))
+        ));
+    }
+}
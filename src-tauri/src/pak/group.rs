@@ -1,14 +1,38 @@
+use std::path::PathBuf;
+
 use hashbrown::{HashMap, HashSet};
-use ree_pak_core::filename::FileNameTable;
+use rayon::prelude::*;
+use ree_pak_core::{pak::CompressionType, utf16_hash::Utf16HashExt};
+
+use super::{
+    EntryHistory, EntryVersionInfo, ExtractFileInfo, FileTreeStats, Pak, PakId, PakInfo,
+    arena_tree::{ArenaTree, EntryData},
+    entry_path,
+    file_type::FileType,
+    insert_tree_entry,
+    name_layers::{LayeredNames, NameLayerSource, NameLayerStack, NameTable},
+    stats::{PakGroupStats, PakStatsOptions, StatsCollector},
+    streaming::{is_streaming_entry_path, natives_root_entry, streaming_entry_path},
+    tree::{FileTree, RenderTreeOptions},
+    tree_entry_data,
+    tree_index::{ROOT_NODE_ID, TreeChildrenPage, TreeChildrenQuery, TreeIndex},
+    variant::{VariantCollector, VariantSummary},
+};
 
-use super::{FileTreeStats, Pak, PakId, PakInfo, insert_tree_entry, tree::FileTree};
+use crate::common::JsSafeHash;
 
 use crate::error::{Error, Result};
 
 /// Manages a group of paks.
 pub struct PakGroup {
     paks: Vec<Pak>,
-    file_name_table: Option<FileNameTable>,
+    /// Enabled name layers.
+    file_name_table: Option<LayeredNames>,
+    name_layers: NameLayerStack,
+    /// Lazily built index for reading the tree on demand.
+    tree_index: Option<TreeIndex>,
+    /// Bumped whenever paks or the file name table change.
+    tree_generation: u64,
 }
 
 impl PakGroup {
@@ -17,25 +41,20 @@ impl PakGroup {
     }
 
     pub fn paks_mut(&mut self) -> &mut [Pak] {
+        self.invalidate_tree_index();
         &mut self.paks
     }
 
-    pub fn file_name_table(&self) -> Option<&FileNameTable> {
+    pub fn file_name_table(&self) -> Option<&LayeredNames> {
         self.file_name_table.as_ref()
     }
 
-    pub fn file_name_table_mut(&mut self) -> Option<&mut FileNameTable> {
-        self.file_name_table.as_mut()
+    pub fn name_layers(&self) -> &NameLayerStack {
+        &self.name_layers
     }
 
     pub fn pak_infos(&self) -> Vec<PakInfo> {
-        self.paks
-            .iter()
-            .map(|pak| PakInfo {
-                id: pak.id,
-                path: pak.path.clone(),
-            })
-            .collect()
+        self.paks.iter().map(Pak::info).collect()
     }
 
     pub fn total_files(&self) -> u64 {
@@ -52,6 +71,7 @@ impl PakGroup {
             self.remove_pak(&id);
         }
         self.paks.push(pak);
+        self.invalidate_tree_index();
     }
 
     pub fn get_pak(&self, id: &PakId) -> Option<&Pak> {
@@ -63,22 +83,68 @@ impl PakGroup {
     }
 
     pub fn remove_pak(&mut self, id: &PakId) -> Option<Pak> {
-        self.paks
+        let removed = self
+            .paks
             .iter()
             .position(|pak| pak.id == *id)
-            .map(|i| self.paks.remove(i))
+            .map(|i| self.paks.remove(i));
+        if removed.is_some() {
+            self.invalidate_tree_index();
+        }
+        removed
     }
 
     pub fn remove_all_paks(&mut self) {
         self.paks.clear();
+        self.invalidate_tree_index();
+    }
+
+    /// Load a list file as a name layer, replacing the layer of the same name.
+    pub fn load_name_layer(&mut self, name: &str, path: Option<String>, table: NameTable) {
+        self.name_layers.set_file_layer(name, path, table);
+        self.rebuild_file_name_table();
+    }
+
+    /// Add paths to the name layer of a source, ignored if no file list is loaded.
+    pub fn push_file_paths(&mut self, source: NameLayerSource, paths: &[String]) {
+        if self.name_layers.is_empty() {
+            return;
+        }
+        self.push_layer_paths(source, paths);
+    }
+
+    /// Add paths to the name layer of a source, created even if no file list is loaded.
+    pub(super) fn push_layer_paths(&mut self, source: NameLayerSource, paths: &[String]) {
+        // drop the view first, so the layer is not copied on write
+        let names = self.file_name_table.take();
+        if self.name_layers.push_paths(source, paths) {
+            self.rebuild_file_name_table();
+        } else {
+            self.file_name_table = names;
+        }
+    }
+
+    pub fn set_name_layer_enabled(&mut self, name: &str, enabled: bool) -> Result<()> {
+        self.name_layers.set_enabled(name, enabled)?;
+        self.rebuild_file_name_table();
+        Ok(())
+    }
+
+    pub fn remove_name_layer(&mut self, name: &str) -> Result<()> {
+        self.name_layers.remove(name)?;
+        self.rebuild_file_name_table();
+        Ok(())
     }
 
-    pub fn set_file_name_table(&mut self, file_name_table: FileNameTable) {
-        self.file_name_table = Some(file_name_table);
+    fn rebuild_file_name_table(&mut self) {
+        self.file_name_table = self.name_layers.names();
+        self.invalidate_tree_index();
     }
 
     /// 联合解析所有已加载的 Pak 文件树
-    pub fn render_tree_combined(&self) -> Result<FileTree> {
+    ///
+    /// Entries overridden by newer paks are skipped, unless `show_overridden` is set.
+    pub fn render_tree_combined(&self, options: &RenderTreeOptions) -> Result<FileTree> {
         let Some(file_name_table) = self.file_name_table.as_ref() else {
             return Err(Error::MissingFileList);
         };
@@ -93,15 +159,17 @@ impl PakGroup {
 
         for pak in self.paks.iter().rev() {
             for entry in pak.pakfile.metadata().entries() {
-                if !seen_hashes.insert(entry.hash()) {
+                let overridden = !seen_hashes.insert(entry.hash());
+                if overridden && !options.show_overridden() {
                     continue;
                 }
                 insert_tree_entry(
                     &mut root_children,
                     &mut stats,
-                    pak.id,
+                    pak,
                     file_name_table,
                     entry,
+                    overridden,
                 );
             }
         }
@@ -115,11 +183,344 @@ impl PakGroup {
     }
 }
 
+impl PakGroup {
+    /// Build the combined tree of all loaded paks.
+    ///
+    /// Each pak is built in parallel, then merged in load order.
+    /// Entries overridden by newer paks are kept, marked with `is_overridden`.
+    pub fn render_arena_tree(&self) -> Result<ArenaTree> {
+        let Some(file_name_table) = self.file_name_table.as_ref() else {
+            return Err(Error::MissingFileList);
+        };
+
+        let parts = self
+            .paks
+            .par_iter()
+            .map(|pak| {
+                ArenaTree::from_entries(pak.pakfile.metadata().entries().iter().map(|entry| {
+                    (
+                        entry_path(file_name_table, entry.hash()),
+                        tree_entry_data(pak, file_name_table, entry),
+                    )
+                }))
+            })
+            .collect::<Vec<_>>();
+
+        Ok(ArenaTree::merge(parts))
+    }
+
+    /// Build separate trees of selected paks, all paks if `pak_ids` is `None`.
+    ///
+    /// Entries shadowed by newer paks are kept, marked with `is_overridden`.
+    pub fn render_pak_trees(&self, pak_ids: Option<&[PakId]>) -> Result<Vec<(PakInfo, ArenaTree)>> {
+        let Some(file_name_table) = self.file_name_table.as_ref() else {
+            return Err(Error::MissingFileList);
+        };
+        if let Some(id) = pak_ids
+            .unwrap_or_default()
+            .iter()
+            .find(|id| self.get_pak(id).is_none())
+        {
+            return Err(Error::PakIdNotFound(*id));
+        }
+
+        // hash -> load index of the newest pak containing it
+        let mut newest_pak_index = HashMap::new();
+        for (load_index, pak) in self.paks.iter().enumerate() {
+            for entry in pak.pakfile.metadata().entries() {
+                newest_pak_index.insert(entry.hash(), load_index);
+            }
+        }
+
+        let trees = self
+            .paks
+            .par_iter()
+            .enumerate()
+            .filter(|(_, pak)| pak_ids.is_none_or(|ids| ids.contains(&pak.id)))
+            .map(|(load_index, pak)| {
+                let tree =
+                    ArenaTree::from_entries(pak.pakfile.metadata().entries().iter().map(|entry| {
+                        (
+                            entry_path(file_name_table, entry.hash()),
+                            tree_entry_data(pak, file_name_table, entry),
+                        )
+                    }))
+                    .finish_single(|hash| {
+                        newest_pak_index
+                            .get(&hash)
+                            .is_some_and(|newest| *newest > load_index)
+                    });
+                (pak.info(), tree)
+            })
+            .collect();
+
+        Ok(trees)
+    }
+
+    /// Get every version of an entry across the load order.
+    pub fn entry_history(&self, hash: u64) -> EntryHistory {
+        let mut versions = self
+            .paks
+            .iter()
+            .enumerate()
+            .filter_map(|(load_index, pak)| {
+                pak.find_entry(hash).map(|entry| EntryVersionInfo {
+                    belongs_to: pak.id,
+                    pak_path: pak.path.clone(),
+                    load_index,
+                    compressed_size: entry.compressed_size(),
+                    uncompressed_size: entry.uncompressed_size(),
+                    compression_type: entry.compression_type().bits(),
+                    is_compressed: entry.compression_type() != CompressionType::None,
+                    checksum: format!("{:016x}", entry.checksum()),
+                    is_active: false,
+                })
+            })
+            .collect::<Vec<_>>();
+        if let Some(active) = versions.last_mut() {
+            active.is_active = true;
+        }
+
+        EntryHistory {
+            hash: JsSafeHash::from_u64(hash),
+            path: self
+                .file_name_table
+                .as_ref()
+                .and_then(|table| table.get_file_name(hash))
+                .map(str::to_string),
+            versions,
+        }
+    }
+
+    /// Get the path of an entry in the file list.
+    ///
+    /// Entries not in the list get a placeholder path, with the extension of the type
+    /// detected from their magic, e.g. `_Unknown/10015D55056456A1.tex`.
+    /// Uses the version in `belongs_to` if set, or the newest one.
+    pub fn resolve_entry_path(&self, hash: u64, belongs_to: Option<PakId>) -> Result<String> {
+        let Some(file_name_table) = self.file_name_table.as_ref() else {
+            return Err(Error::MissingFileList);
+        };
+        if let Some(path) = file_name_table.get_file_name(hash) {
+            return Ok(path.to_string());
+        }
+
+        let (pak, entry) = self
+            .paks
+            .iter()
+            .rev()
+            .filter(|pak| belongs_to.is_none_or(|id| pak.id == id))
+            .find_map(|pak| pak.find_entry(hash).map(|entry| (pak, entry)))
+            .ok_or_else(|| Error::PakEntryNotFound(format!("{hash:016X}")))?;
+        let mut path = PathBuf::from(entry_path(file_name_table, hash));
+        if let Some(file_type) = pak.detect_file_type(entry) {
+            file_type.append_extension(&mut path);
+        }
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    /// Detect the type of an entry from its magic.
+    ///
+    /// Uses the active version, or the version stored in `belongs_to` if set.
+    pub fn detect_entry_type(&self, hash: u64, belongs_to: Option<PakId>) -> Option<FileType> {
+        self.paks
+            .iter()
+            .rev()
+            .filter(|pak| belongs_to.is_none_or(|id| pak.id == id))
+            .find_map(|pak| pak.find_entry(hash).map(|entry| (pak, entry)))
+            .and_then(|(pak, entry)| pak.detect_file_type(entry))
+    }
+}
+
+impl PakGroup {
+    /// Aggregate entry statistics by extension, directory and pak.
+    ///
+    /// Without `pak_ids`, only active entries are counted, like the combined tree.
+    /// Entries missing from the file list are counted as unknown.
+    pub fn statistics(&self, options: &PakStatsOptions) -> Result<PakGroupStats> {
+        let pak_ids = options.pak_ids.as_deref();
+        if let Some(id) = pak_ids
+            .unwrap_or_default()
+            .iter()
+            .find(|id| self.get_pak(id).is_none())
+        {
+            return Err(Error::PakIdNotFound(*id));
+        }
+
+        let mut collector = StatsCollector::new(options);
+        let paks = self
+            .paks
+            .iter()
+            .filter(|pak| pak_ids.is_none_or(|ids| ids.contains(&pak.id)))
+            .collect::<Vec<_>>();
+        for pak in &paks {
+            collector.add_pak(pak.info());
+        }
+
+        let mut seen_hashes = HashSet::new();
+        for pak in paks.iter().rev() {
+            for entry in pak.pakfile.metadata().entries() {
+                if pak_ids.is_none() && !seen_hashes.insert(entry.hash()) {
+                    continue;
+                }
+                let path = self
+                    .file_name_table
+                    .as_ref()
+                    .and_then(|table| table.get_file_name(entry.hash()))
+                    .map(|path| path.replace('\\', "/"));
+                collector.add_entry(path.as_deref(), &EntryData::from_entry(pak.id, entry));
+            }
+        }
+
+        Ok(collector.finish())
+    }
+
+    /// Find loaded streaming counterparts of natives files.
+    ///
+    /// Counterparts are extracted relative to `natives/<platform>`,
+    /// so they don't collide with the natives files.
+    pub fn streaming_counterparts(&self, files: &[ExtractFileInfo]) -> Vec<ExtractFileInfo> {
+        let Some(file_name_table) = self.file_name_table.as_ref() else {
+            return vec![];
+        };
+
+        let selected = files
+            .iter()
+            .map(|info| info.hash.hash_u64())
+            .collect::<HashSet<_>>();
+        // streaming hash -> source file
+        let candidates = files
+            .iter()
+            .filter_map(|info| {
+                let path = file_name_table
+                    .get_file_name(info.hash.hash_u64())?
+                    .replace('\\', "/");
+                if is_streaming_entry_path(&path) {
+                    return None;
+                }
+                let streaming_path = streaming_entry_path(&path)?;
+                let streaming_hash = streaming_path.hash_mixed();
+                (!selected.contains(&streaming_hash)).then_some((streaming_hash, info))
+            })
+            .collect::<HashMap<_, _>>();
+        if candidates.is_empty() {
+            return vec![];
+        }
+
+        // newest pak wins
+        let mut owners = HashMap::new();
+        for pak in &self.paks {
+            for entry in pak.pakfile.metadata().entries() {
+                if candidates.contains_key(&entry.hash()) {
+                    owners.insert(entry.hash(), pak.id);
+                }
+            }
+        }
+
+        owners
+            .into_iter()
+            .map(|(hash, belongs_to)| {
+                let source = candidates[&hash];
+                ExtractFileInfo {
+                    hash: JsSafeHash::from_u64(hash),
+                    belongs_to,
+                    relative_root: source
+                        .relative_root
+                        .as_deref()
+                        .map(|root| natives_root_entry(root).unwrap_or_else(|| root.to_string())),
+                }
+            })
+            .collect()
+    }
+
+    /// List languages and platforms of active entries.
+    pub fn variant_summary(&self) -> Result<VariantSummary> {
+        let Some(file_name_table) = self.file_name_table.as_ref() else {
+            return Err(Error::MissingFileList);
+        };
+
+        let mut collector = VariantCollector::default();
+        let mut seen_hashes = HashSet::new();
+        for pak in self.paks.iter().rev() {
+            for entry in pak.pakfile.metadata().entries() {
+                if !seen_hashes.insert(entry.hash()) {
+                    continue;
+                }
+                if let Some(path) = file_name_table.get_file_name(entry.hash()) {
+                    collector.add_path(path);
+                }
+            }
+        }
+
+        Ok(collector.finish())
+    }
+}
+
+impl PakGroup {
+    /// Read a page of directory children from the indexed tree.
+    ///
+    /// The index is built on first read and reused until paks or the file name table change.
+    /// Types of unnamed entries are detected for the returned page only.
+    pub fn read_tree_children(&mut self, query: &TreeChildrenQuery) -> Result<TreeChildrenPage> {
+        if query
+            .generation
+            .is_some_and(|generation| generation != self.tree_generation)
+        {
+            return Err(Error::TreeIndexOutdated);
+        }
+
+        let tree_index = match self.tree_index.take() {
+            Some(tree_index) => tree_index,
+            None => TreeIndex::new(self.render_arena_tree()?, self.tree_generation),
+        };
+
+        let pak_order = self.paks.iter().map(|pak| pak.id).collect::<Vec<_>>();
+        let options = query.options.clone().unwrap_or_default();
+        let page = tree_index.read_children(
+            query.node_id.unwrap_or(ROOT_NODE_ID),
+            &options,
+            &pak_order,
+            query.offset.unwrap_or(0),
+            query.limit,
+        );
+        self.tree_index = Some(tree_index);
+
+        let mut page = page?;
+        self.detect_page_types(&mut page);
+        Ok(page)
+    }
+
+    fn detect_page_types(&self, page: &mut TreeChildrenPage) {
+        let Some(file_name_table) = self.file_name_table.as_ref() else {
+            return;
+        };
+        for node in &mut page.children {
+            if node.is_dir || node.file_type.is_some() {
+                continue;
+            }
+            let Some(hash) = node.hash.map(|hash| hash.hash_u64()) else {
+                continue;
+            };
+            if file_name_table.get_file_name(hash).is_none() {
+                node.file_type = self.detect_entry_type(hash, node.belongs_to);
+            }
+        }
+    }
+
+    fn invalidate_tree_index(&mut self) {
+        self.tree_index = None;
+        self.tree_generation += 1;
+    }
+}
+
 impl PakGroup {
     pub fn new() -> Self {
         Self {
             paks: Vec::new(),
             file_name_table: None,
+            name_layers: NameLayerStack::default(),
+            tree_index: None,
+            tree_generation: 0,
         }
     }
 }
@@ -0,0 +1,220 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, LineWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use hashbrown::HashSet;
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+
+const JOURNAL_FILE_NAME: &str = ".extract_journal";
+const JOURNAL_HEADER: &str = "paks";
+const COMPARE_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOutcome {
+    /// Existing file has the same content.
+    Unchanged,
+    /// Number of bytes written.
+    ///
+    /// Counts only the rewritten tail if the start of an existing file was the same.
+    Written(u64),
+}
+
+/// Write an entry, leaving the existing file untouched if its size and content are the same.
+///
+/// Contents are compared while streaming, a changed file is rewritten from the first
+/// differing chunk. An entry that doesn't hold `size` bytes is an error, `UnexpectedEof` if it
+/// ends early.
+pub fn write_if_changed(
+    reader: &mut dyn Read,
+    output_path: &Path,
+    size: u64,
+) -> io::Result<WriteOutcome> {
+    let same_size = std::fs::metadata(output_path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.len() == size);
+    if !same_size {
+        let mut file = File::create(output_path)?;
+        let written = io::copy(reader, &mut file)?;
+        check_entry_size(written, size)?;
+        return Ok(WriteOutcome::Written(written));
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(output_path)?;
+    let mut entry_chunk = vec![0; COMPARE_CHUNK_SIZE];
+    let mut file_chunk = vec![0; COMPARE_CHUNK_SIZE];
+    let mut offset = 0u64;
+    loop {
+        let read = read_chunk(reader, &mut entry_chunk)?;
+        if read == 0 {
+            check_entry_size(offset, size)?;
+            return Ok(WriteOutcome::Unchanged);
+        }
+
+        let file_read = read_chunk(&mut file, &mut file_chunk[..read])?;
+        if file_read == read && entry_chunk[..read] == file_chunk[..read] {
+            offset += read as u64;
+            continue;
+        }
+
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(&entry_chunk[..read])?;
+        let rest = io::copy(reader, &mut file)?;
+        file.set_len(offset + read as u64 + rest)?;
+        check_entry_size(offset + read as u64 + rest, size)?;
+        return Ok(WriteOutcome::Written(read as u64 + rest));
+    }
+}
+
+/// Check that `read` bytes of an entry of `size` were read.
+fn check_entry_size(read: u64, size: u64) -> io::Result<()> {
+    match read.cmp(&size) {
+        std::cmp::Ordering::Equal => Ok(()),
+        std::cmp::Ordering::Less => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("entry ended after {read} of {size} bytes"),
+        )),
+        std::cmp::Ordering::Greater => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("entry is larger than {size} bytes"),
+        )),
+    }
+}
+
+/// Read until the buffer is full or the reader ends.
+fn read_chunk(reader: &mut dyn Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Records extracted entries in the output directory, so an interrupted extraction can resume.
+///
+/// The first line lists the loaded paks, each following line is `<pak index>\t<hash>`.
+/// A journal written with other paks is discarded.
+pub struct ExtractJournal {
+    path: PathBuf,
+    writer: Mutex<LineWriter<File>>,
+}
+
+impl ExtractJournal {
+    /// Open the journal of an output directory.
+    ///
+    /// Returns the journal and `(pak index, hash)` of entries done by a previous run.
+    pub fn open(
+        output_dir: impl AsRef<Path>,
+        pak_paths: &[&str],
+    ) -> Result<(Self, HashSet<(usize, u64)>)> {
+        let output_dir = output_dir.as_ref();
+        std::fs::create_dir_all(output_dir)?;
+        let path = output_dir.join(JOURNAL_FILE_NAME);
+        let header = std::iter::once(JOURNAL_HEADER)
+            .chain(pak_paths.iter().copied())
+            .collect::<Vec<_>>()
+            .join("\t");
+
+        let done = Self::read_done(&path, &header).unwrap_or_default();
+        let file = if done.is_empty() {
+            let mut file = File::create(&path)?;
+            writeln!(file, "{header}")?;
+            file
+        } else {
+            OpenOptions::new().append(true).open(&path)?
+        };
+        let journal = Self {
+            path,
+            writer: Mutex::new(LineWriter::new(file)),
+        };
+        Ok((journal, done))
+    }
+
+    fn read_done(path: &Path, header: &str) -> Option<HashSet<(usize, u64)>> {
+        let mut lines = BufReader::new(File::open(path).ok()?).lines();
+        if lines.next()?.ok()? != header {
+            return None;
+        }
+        // a line cut off by an interruption fails to parse and is ignored
+        let done = lines
+            .map_while(|line| line.ok())
+            .filter_map(|line| {
+                let (pak_index, hash) = line.split_once('\t')?;
+                Some((pak_index.parse().ok()?, u64::from_str_radix(hash, 16).ok()?))
+            })
+            .collect();
+        Some(done)
+    }
+
+    pub fn record(&self, pak_index: usize, hash: u64) -> io::Result<()> {
+        writeln!(self.writer.lock(), "{pak_index}\t{hash:016X}")
+    }
+
+    /// Remove the journal after the extraction finished.
+    pub fn complete(self) -> Result<()> {
+        drop(self.writer);
+        std::fs::remove_file(&self.path).map_err(|e| Error::FileIO {
+            path: self.path.to_string_lossy().to_string(),
+            source: e,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn write_if_changed_keeps_same_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.bin");
+        let data = (0..200_000u32).map(|i| i as u8).collect::<Vec<_>>();
+
+        let outcome = write_if_changed(&mut Cursor::new(&data), &path, data.len() as u64).unwrap();
+        assert_eq!(outcome, WriteOutcome::Written(data.len() as u64));
+        let outcome = write_if_changed(&mut Cursor::new(&data), &path, data.len() as u64).unwrap();
+        assert_eq!(outcome, WriteOutcome::Unchanged);
+
+        let mut changed = data.clone();
+        *changed.last_mut().unwrap() ^= 0xFF;
+        let outcome =
+            write_if_changed(&mut Cursor::new(&changed), &path, changed.len() as u64).unwrap();
+        assert!(matches!(outcome, WriteOutcome::Written(n) if n < changed.len() as u64));
+        assert_eq!(std::fs::read(&path).unwrap(), changed);
+
+        // an entry ending early is an error, not an unchanged or empty write
+        let error = write_if_changed(&mut Cursor::new(&changed[..1000]), &path, data.len() as u64)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn journal_resumes_with_same_paks() {
+        let dir = tempfile::tempdir().unwrap();
+        let (journal, done) = ExtractJournal::open(dir.path(), &["a.pak", "b.pak"]).unwrap();
+        assert!(done.is_empty());
+        journal.record(1, 0xABCD).unwrap();
+        drop(journal);
+
+        let (journal, done) = ExtractJournal::open(dir.path(), &["a.pak", "b.pak"]).unwrap();
+        assert!(done.contains(&(1, 0xABCD)));
+        drop(journal);
+
+        let (journal, done) = ExtractJournal::open(dir.path(), &["a.pak"]).unwrap();
+        assert!(done.is_empty());
+        journal.complete().unwrap();
+        assert!(!dir.path().join(JOURNAL_FILE_NAME).exists());
+    }
+}
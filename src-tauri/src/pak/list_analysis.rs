@@ -0,0 +1,194 @@
+use hashbrown::HashMap;
+use ree_pak_core::utf16_hash::Utf16HashExt;
+use serde::Serialize;
+
+use crate::common::JsSafeHash;
+
+use super::group::PakGroup;
+
+/// A path listed more than once.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicatePath {
+    pub path: String,
+    pub count: usize,
+}
+
+/// Paths sharing a hash, either differing only in case or truly colliding.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashGroup {
+    pub hash: JsSafeHash,
+    /// Distinct paths in list order.
+    pub paths: Vec<String>,
+    /// Paks containing an entry with this hash.
+    pub paks: Vec<String>,
+    /// Name the loaded file name table resolves this hash to.
+    pub resolved_as: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListAnalysisReport {
+    /// Number of non-empty lines.
+    pub total_paths: usize,
+    /// Number of distinct hashes.
+    pub unique_hashes: usize,
+    /// Paths listed more than once with the same case.
+    pub exact_duplicates: Vec<DuplicatePath>,
+    /// Paths differing only in case. They share a hash, so only one of them is shown.
+    pub case_duplicates: Vec<HashGroup>,
+    /// Different paths with the same `hash_mixed`, the wrong name may win silently.
+    pub collisions: Vec<HashGroup>,
+}
+
+impl ListAnalysisReport {
+    /// Find duplicates and collisions in the paths of a list file.
+    pub fn analyze(paths: &[String]) -> Self {
+        Self::analyze_with(paths, |path| path.hash_mixed())
+    }
+
+    fn analyze_with(paths: &[String], hash: impl Fn(&str) -> u64) -> Self {
+        let mut counts = HashMap::<&str, usize>::new();
+        // hash -> distinct paths, in order of first occurrence
+        let mut by_hash = HashMap::<u64, Vec<&str>>::new();
+        let mut hash_order = vec![];
+        let mut path_order = vec![];
+
+        for path in paths {
+            let count = counts.entry(path.as_str()).or_default();
+            *count += 1;
+            if *count > 1 {
+                continue;
+            }
+            path_order.push(path.as_str());
+
+            let hash = hash(path);
+            let group = by_hash.entry(hash).or_insert_with(|| {
+                hash_order.push(hash);
+                vec![]
+            });
+            group.push(path.as_str());
+        }
+
+        let exact_duplicates = path_order
+            .iter()
+            .filter(|path| counts[*path] > 1)
+            .map(|path| DuplicatePath {
+                path: path.to_string(),
+                count: counts[path],
+            })
+            .collect();
+
+        let mut case_duplicates = vec![];
+        let mut collisions = vec![];
+        for hash in &hash_order {
+            let group = &by_hash[hash];
+            if group.len() < 2 {
+                continue;
+            }
+            let hash_group = HashGroup {
+                hash: JsSafeHash::from_u64(*hash),
+                paths: group.iter().map(|path| path.to_string()).collect(),
+                paks: vec![],
+                resolved_as: None,
+            };
+            let first = group[0].to_lowercase();
+            if group.iter().all(|path| path.to_lowercase() == first) {
+                case_duplicates.push(hash_group);
+            } else {
+                collisions.push(hash_group);
+            }
+        }
+
+        Self {
+            total_paths: paths.len(),
+            unique_hashes: hash_order.len(),
+            exact_duplicates,
+            case_duplicates,
+            collisions,
+        }
+    }
+}
+
+impl PakGroup {
+    /// Fill in the paks and loaded names of the hash groups of a report.
+    ///
+    /// Each pak is scanned once for the hashes of all groups.
+    pub fn annotate_list_analysis(&self, report: &mut ListAnalysisReport) {
+        let mut paks_by_hash: HashMap<u64, Vec<String>> = report
+            .case_duplicates
+            .iter()
+            .chain(&report.collisions)
+            .map(|group| (group.hash.hash_u64(), vec![]))
+            .collect();
+        if paks_by_hash.is_empty() {
+            return;
+        }
+        for pak in self.paks() {
+            for entry in pak.pakfile.metadata().entries() {
+                let Some(paks) = paks_by_hash.get_mut(&entry.hash()) else {
+                    continue;
+                };
+                // duplicate entries of a pak list it once
+                if paks.last() != Some(&pak.path) {
+                    paks.push(pak.path.clone());
+                }
+            }
+        }
+
+        let names = self.file_name_table();
+        for group in report
+            .case_duplicates
+            .iter_mut()
+            .chain(report.collisions.iter_mut())
+        {
+            let hash = group.hash.hash_u64();
+            group.paks = paks_by_hash.remove(&hash).unwrap_or_default();
+            group.resolved_as = names
+                .and_then(|table| table.get_file_name(hash))
+                .map(str::to_string);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn analyze_reports_duplicates_and_case_variants() {
+        let report = ListAnalysisReport::analyze(&paths(&[
+            "natives/STM/a.tex.1",
+            "natives/STM/b.tex.1",
+            "natives/STM/a.tex.1",
+            "natives/stm/A.tex.1",
+        ]));
+
+        assert_eq!(report.total_paths, 4);
+        assert_eq!(report.unique_hashes, 2);
+        assert_eq!(report.exact_duplicates.len(), 1);
+        assert_eq!(report.exact_duplicates[0].path, "natives/STM/a.tex.1");
+        assert_eq!(report.exact_duplicates[0].count, 2);
+        assert_eq!(report.case_duplicates.len(), 1);
+        assert_eq!(
+            report.case_duplicates[0].paths,
+            ["natives/STM/a.tex.1", "natives/stm/A.tex.1"]
+        );
+        assert!(report.collisions.is_empty());
+    }
+
+    #[test]
+    fn analyze_separates_collisions_from_case_variants() {
+        // every path hashes alike, so only case folding tells the groups apart
+        let report = ListAnalysisReport::analyze_with(&paths(&["a.tex.1", "b.tex.1"]), |_| 7);
+        assert!(report.case_duplicates.is_empty());
+        assert_eq!(report.collisions.len(), 1);
+        assert_eq!(report.collisions[0].hash.hash_u64(), 7);
+        assert_eq!(report.collisions[0].paths, ["a.tex.1", "b.tex.1"]);
+    }
+}
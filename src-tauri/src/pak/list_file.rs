@@ -0,0 +1,400 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use ree_pak_core::utf16_hash::Utf16HashExt;
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+
+use super::name_layers::NameTable;
+
+const CACHE_MAGIC: &[u8; 4] = b"RPLC";
+const CACHE_VERSION: u32 = 2;
+const CACHE_SUFFIX: &str = ".cache";
+const CACHE_DIGEST_LEN: usize = 32;
+/// Magic, version, source stamp and record count.
+const CACHE_HEADER_LEN: u64 = 32;
+/// Hash and name length of a record, followed by the name.
+const CACHE_RECORD_LEN: usize = 12;
+
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Container format of a list file, detected from its magic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListFormat {
+    Text,
+    Gzip,
+    Zstd,
+    /// Every file in the archive is read as a list.
+    Zip,
+    Cache,
+}
+
+impl ListFormat {
+    fn detect(header: &[u8]) -> Self {
+        if header.starts_with(CACHE_MAGIC) {
+            Self::Cache
+        } else if header.starts_with(GZIP_MAGIC) {
+            Self::Gzip
+        } else if header.starts_with(ZSTD_MAGIC) {
+            Self::Zstd
+        } else if header.starts_with(ZIP_MAGIC) {
+            Self::Zip
+        } else {
+            Self::Text
+        }
+    }
+}
+
+/// Size and modification time of the source list, stored in the cache header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SourceStamp {
+    len: u64,
+    modified_ns: u64,
+}
+
+impl SourceStamp {
+    fn of(path: &Path) -> io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        let modified_ns = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        Ok(Self {
+            len: metadata.len(),
+            modified_ns,
+        })
+    }
+}
+
+/// Load a list file, plain or gzip/zstd compressed, a zip archive of lists, or a list cache.
+///
+/// A binary cache of `hash -> path` records is written next to the source list on first load,
+/// e.g. `MHWs.list.gz.cache`, and is used until the source list changes.
+pub fn load_list_file(path: impl AsRef<Path>) -> Result<NameTable> {
+    let path = path.as_ref();
+    let io_error = |e| Error::FileIO {
+        path: path.to_string_lossy().to_string(),
+        source: e,
+    };
+
+    let format = ListFormat::detect(&read_magic(path).map_err(io_error)?);
+    if format == ListFormat::Cache {
+        return read_cache(path, None)?.ok_or_else(|| invalid_cache(path));
+    }
+
+    let stamp = SourceStamp::of(path).map_err(io_error)?;
+    let cache_path = cache_path_for(path);
+    match read_cache(&cache_path, Some(stamp)) {
+        Ok(Some(table)) => return Ok(table),
+        Ok(None) => {}
+        Err(error) => log::warn!("Ignoring list cache {}: {}", cache_path.display(), error),
+    }
+
+    let table = read_source(path, format)?;
+    if let Err(error) = write_cache(&cache_path, stamp, &table) {
+        log::warn!(
+            "Failed to write list cache {}: {}",
+            cache_path.display(),
+            error
+        );
+    }
+    Ok(table)
+}
+
+/// Cache path next to a source list.
+pub fn cache_path_for(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(CACHE_SUFFIX);
+    path.with_file_name(file_name)
+}
+
+fn read_magic(path: &Path) -> io::Result<Vec<u8>> {
+    let mut magic = Vec::with_capacity(4);
+    File::open(path)?.take(4).read_to_end(&mut magic)?;
+    Ok(magic)
+}
+
+/// Read the paths of a list file in file order, duplicates included.
+///
+/// A list cache only holds one path per hash, so its paths are already deduplicated.
+pub fn read_list_paths(path: impl AsRef<Path>) -> Result<Vec<String>> {
+    let path = path.as_ref();
+    let format = ListFormat::detect(&read_magic(path).map_err(|e| Error::FileIO {
+        path: path.to_string_lossy().to_string(),
+        source: e,
+    })?);
+    if format == ListFormat::Cache {
+        let table = read_cache(path, None)?.ok_or_else(|| invalid_cache(path))?;
+        return Ok(table
+            .file_names()
+            .map(|(_, name)| name.to_string())
+            .collect());
+    }
+
+    let mut paths = vec![];
+    read_source_lines(path, format, &mut |line| paths.push(line.to_string()))?;
+    Ok(paths)
+}
+
+fn read_source(path: &Path, format: ListFormat) -> Result<NameTable> {
+    let mut table = NameTable::default();
+    read_source_lines(path, format, &mut |line| table.push_str(line))?;
+    Ok(table)
+}
+
+/// Call `f` on each non-empty trimmed line of a source list.
+fn read_source_lines(path: &Path, format: ListFormat, f: &mut impl FnMut(&str)) -> Result<()> {
+    let io_error = |e| Error::FileIO {
+        path: path.to_string_lossy().to_string(),
+        source: e,
+    };
+    let file = BufReader::new(File::open(path).map_err(io_error)?);
+
+    match format {
+        ListFormat::Text => visit_lines(file, f),
+        ListFormat::Gzip => visit_lines(flate2::read::MultiGzDecoder::new(file), f),
+        ListFormat::Zstd => visit_lines(zstd::stream::read::Decoder::new(file)?, f),
+        ListFormat::Zip => {
+            let mut archive = zip::ZipArchive::new(file)?;
+            for index in 0..archive.len() {
+                let entry = archive.by_index(index)?;
+                if entry.is_file() {
+                    visit_lines(entry, f)?;
+                }
+            }
+            Ok(())
+        }
+        ListFormat::Cache => unreachable!("cache is read before sources"),
+    }
+    .map_err(io_error)
+}
+
+fn visit_lines(reader: impl Read, f: &mut impl FnMut(&str)) -> io::Result<()> {
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() {
+            f(line);
+        }
+    }
+    Ok(())
+}
+
+/// Write `hash -> path` records after a header with the source stamp, then their digest.
+///
+/// The cache is written under a temp name and renamed, so a partial write is never loaded.
+fn write_cache(cache_path: &Path, stamp: SourceStamp, table: &NameTable) -> io::Result<()> {
+    let mut file_name = cache_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".part");
+    let temp_path = cache_path.with_file_name(file_name);
+
+    let result = write_cache_records(&temp_path, stamp, table)
+        .and_then(|_| std::fs::rename(&temp_path, cache_path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn write_cache_records(path: &Path, stamp: SourceStamp, table: &NameTable) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(CACHE_MAGIC)?;
+    writer.write_all(&CACHE_VERSION.to_le_bytes())?;
+    writer.write_all(&stamp.len.to_le_bytes())?;
+    writer.write_all(&stamp.modified_ns.to_le_bytes())?;
+    writer.write_all(&(table.len() as u64).to_le_bytes())?;
+
+    let mut digest = Sha256::new();
+    for (hash, name) in table.file_names() {
+        for bytes in [
+            &hash.to_le_bytes()[..],
+            &(name.len() as u32).to_le_bytes(),
+            name.as_bytes(),
+        ] {
+            digest.update(bytes);
+            writer.write_all(bytes)?;
+        }
+    }
+    writer.write_all(&digest.finalize())?;
+    writer.flush()
+}
+
+/// Read a cache, `None` if it is missing, of another version or for another source.
+///
+/// The source stamp is not checked if `expected` is `None`. Records are inserted with their
+/// stored hashes and checked against the digest at the end of the cache.
+fn read_cache(cache_path: &Path, expected: Option<SourceStamp>) -> Result<Option<NameTable>> {
+    let file = match File::open(cache_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(Error::FileIO {
+                path: cache_path.to_string_lossy().to_string(),
+                source: e,
+            });
+        }
+    };
+    let io_error = |e| Error::FileIO {
+        path: cache_path.to_string_lossy().to_string(),
+        source: e,
+    };
+    let file_len = file.metadata().map_err(io_error)?.len();
+    let mut reader = BufReader::new(file);
+
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic).map_err(io_error)?;
+    if &magic != CACHE_MAGIC || read_u32(&mut reader).map_err(io_error)? != CACHE_VERSION {
+        return Ok(None);
+    }
+    let stamp = SourceStamp {
+        len: read_u64(&mut reader).map_err(io_error)?,
+        modified_ns: read_u64(&mut reader).map_err(io_error)?,
+    };
+    if expected.is_some_and(|expected| expected != stamp) {
+        return Ok(None);
+    }
+
+    let count = read_u64(&mut reader).map_err(io_error)?;
+    // bytes left for the records, sizes read from a damaged cache must not exceed them
+    let mut remaining = file_len
+        .checked_sub(CACHE_HEADER_LEN + CACHE_DIGEST_LEN as u64)
+        .ok_or_else(|| invalid_cache(cache_path))?;
+    if count > remaining / CACHE_RECORD_LEN as u64 {
+        return Err(invalid_cache(cache_path));
+    }
+    let mut table = NameTable::with_capacity(count as usize);
+    let mut digest = Sha256::new();
+    let mut buf = Vec::new();
+    for index in 0..count {
+        let mut record = [0; CACHE_RECORD_LEN];
+        reader.read_exact(&mut record).map_err(io_error)?;
+        digest.update(record);
+        let hash = u64::from_le_bytes(record[..8].try_into().unwrap());
+        let len = u32::from_le_bytes(record[8..].try_into().unwrap()) as u64;
+        remaining -= CACHE_RECORD_LEN as u64;
+        if len > remaining {
+            return Err(invalid_cache(cache_path));
+        }
+        remaining -= len;
+        buf.resize(len as usize, 0);
+        reader.read_exact(&mut buf).map_err(io_error)?;
+        digest.update(&buf);
+        let name = std::str::from_utf8(&buf).map_err(|_| invalid_cache(cache_path))?;
+        // a cache written with another hash function is unusable
+        if index == 0 && name.hash_mixed() != hash {
+            return Err(invalid_cache(cache_path));
+        }
+        table.insert(hash, name);
+    }
+
+    let mut stored = [0; CACHE_DIGEST_LEN];
+    reader.read_exact(&mut stored).map_err(io_error)?;
+    if digest.finalize()[..] != stored {
+        return Err(invalid_cache(cache_path));
+    }
+    Ok(Some(table))
+}
+
+fn invalid_cache(cache_path: &Path) -> Error {
+    Error::InvalidListCache(cache_path.to_string_lossy().to_string())
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    const LIST: &str = "natives/STM/a.tex.1\n\n  natives/STM/b.mesh.2  \n";
+
+    fn assert_names(table: &NameTable) {
+        assert!(
+            table
+                .get_file_name("natives/STM/a.tex.1".hash_mixed())
+                .is_some()
+        );
+        assert!(
+            table
+                .get_file_name("natives/STM/b.mesh.2".hash_mixed())
+                .is_some()
+        );
+        assert_eq!(table.file_names().count(), 2);
+    }
+
+    #[test]
+    fn load_compressed_lists() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let gz_path = dir.path().join("a.list.gz");
+        let mut encoder =
+            flate2::write::GzEncoder::new(File::create(&gz_path).unwrap(), Default::default());
+        encoder.write_all(LIST.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+        assert_names(&load_list_file(&gz_path).unwrap());
+
+        let zst_path = dir.path().join("a.list.zst");
+        std::fs::write(&zst_path, zstd::encode_all(LIST.as_bytes(), 3).unwrap()).unwrap();
+        assert_names(&load_list_file(&zst_path).unwrap());
+    }
+
+    #[test]
+    fn cache_is_written_and_invalidated() {
+        let dir = tempfile::tempdir().unwrap();
+        let list_path = dir.path().join("a.list");
+        std::fs::write(&list_path, LIST).unwrap();
+
+        assert_names(&load_list_file(&list_path).unwrap());
+        let cache_path = cache_path_for(&list_path);
+        assert!(cache_path.exists());
+        assert_names(&load_list_file(&cache_path).unwrap());
+
+        // a damaged record fails the digest and the cache is rebuilt from the list
+        let mut bytes = std::fs::read(&cache_path).unwrap();
+        let last_name_byte = bytes.len() - CACHE_DIGEST_LEN - 1;
+        bytes[last_name_byte] ^= 1;
+        std::fs::write(&cache_path, bytes).unwrap();
+        assert!(load_list_file(&cache_path).is_err());
+        assert_names(&load_list_file(&list_path).unwrap());
+        assert_names(&load_list_file(&cache_path).unwrap());
+
+        // a damaged record count is rejected before allocating for it
+        let mut bytes = std::fs::read(&cache_path).unwrap();
+        bytes[24..32].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&cache_path, bytes).unwrap();
+        assert!(matches!(
+            load_list_file(&cache_path),
+            Err(Error::InvalidListCache(_))
+        ));
+        assert_names(&load_list_file(&list_path).unwrap());
+
+        // a changed list must not be served from the stale cache
+        std::fs::write(&list_path, "natives/STM/c.tex.1\n").unwrap();
+        let table = load_list_file(&list_path).unwrap();
+        assert!(
+            table
+                .get_file_name("natives/STM/c.tex.1".hash_mixed())
+                .is_some()
+        );
+        assert_eq!(table.file_names().count(), 1);
+    }
+}
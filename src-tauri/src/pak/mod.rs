@@ -1,19 +1,42 @@
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
+use archive_output::ExtractArchiveFormat;
+use arena_tree::EntryData;
+use convert::ExtractConverter;
+use file_type::FileType;
+use filter::ExtractFilterOptions;
 use hashbrown::HashMap;
-use ree_pak_core::{
-    filename::FileNameTable,
-    pak::{CompressionType, PakEntry},
-    pakfile::PakFile,
-};
+use name_layers::LayeredNames;
+use parking_lot::Mutex;
+use ree_pak_core::{pak::PakEntry, pakfile::PakFile, utf16_hash::Utf16HashExt};
 use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
 use tree::{FileTreeNode, NodeInfo};
 
 use crate::common::{JsSafeHash, UniqueId};
 
+pub mod archive;
+pub mod archive_output;
+pub mod arena_tree;
+pub mod convert;
+pub mod file_type;
+pub mod filter;
 pub mod group;
+pub mod incremental;
+pub mod list_analysis;
+pub mod list_file;
+pub mod name_layers;
+pub mod path_list;
+pub mod report;
+pub mod search;
+pub mod stats;
+pub mod streaming;
+pub mod suffix_resolver;
+#[cfg(test)]
+pub(crate) mod test_util;
 pub mod tree;
+pub mod tree_index;
+pub mod variant;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct PakId(UniqueId);
@@ -24,11 +47,19 @@ impl From<UniqueId> for PakId {
     }
 }
 
+impl std::fmt::Display for PakId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PakInfo {
     pub id: PakId,
     pub path: String,
+    /// Source zip archive, if the pak was loaded from one.
+    pub archive_path: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -39,6 +70,63 @@ pub struct ExtractOptions {
     pub mode: ExtractMode,
     pub extract_all: bool,
     pub extract_files: Vec<ExtractFileInfo>,
+    /// Only extract localized files of these languages, e.g. `["En", "Ja"]`.
+    ///
+    /// Files without a language tag are always extracted.
+    pub languages: Option<Vec<String>>,
+    /// Also extract streaming counterparts of selected natives files.
+    pub with_streaming: Option<bool>,
+    /// Path, extension and size filters, applied to all or selected files.
+    pub filter: Option<ExtractFilterOptions>,
+    /// Write entries into an archive at `output_path` instead of a directory.
+    pub archive: Option<ExtractArchiveFormat>,
+    /// Save the job report as JSON next to the output.
+    pub save_report: Option<bool>,
+    /// Leave existing files with the same size and content untouched, requires `override`.
+    pub skip_unchanged: Option<bool>,
+    /// Keep a journal in the output directory and skip entries done by an interrupted run.
+    ///
+    /// Ignored when writing an archive.
+    pub resume: Option<bool>,
+    /// Number of extraction workers, defaults to available parallelism.
+    pub workers: Option<usize>,
+    /// Convert entries as they are extracted, ignored when writing an archive.
+    #[serde(default)]
+    pub converters: Vec<ExtractConverter>,
+    /// Keep the extracted file after it was converted.
+    pub keep_original: Option<bool>,
+}
+
+impl ExtractOptions {
+    pub fn with_streaming(&self) -> bool {
+        self.with_streaming.unwrap_or(false)
+    }
+
+    pub fn save_report(&self) -> bool {
+        self.save_report.unwrap_or(false)
+    }
+
+    pub fn skip_unchanged(&self) -> bool {
+        self.skip_unchanged.unwrap_or(false)
+    }
+
+    pub fn resume(&self) -> bool {
+        self.resume.unwrap_or(false) && self.archive.is_none()
+    }
+
+    pub fn keep_original(&self) -> bool {
+        self.keep_original.unwrap_or(true)
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+            .filter(|workers| *workers > 0)
+            .unwrap_or_else(|| {
+                std::thread::available_parallelism()
+                    .map(|count| count.get())
+                    .unwrap_or(1)
+            })
+    }
 }
 
 #[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
@@ -56,10 +144,60 @@ pub struct ExtractFileInfo {
     pub relative_root: Option<String>,
 }
 
+/// Query an entry by hash or by path.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryQuery {
+    pub hash: Option<JsSafeHash>,
+    pub path: Option<String>,
+}
+
+impl EntryQuery {
+    pub fn resolve_hash(&self) -> Option<u64> {
+        self.hash.map(|hash| hash.hash_u64()).or_else(|| {
+            self.path
+                .as_deref()
+                .map(|path| path.trim().replace('\\', "/").hash_mixed())
+        })
+    }
+}
+
+/// All versions of an entry across the load order.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryHistory {
+    pub hash: JsSafeHash,
+    pub path: Option<String>,
+    /// Versions in load order, the last one is active.
+    pub versions: Vec<EntryVersionInfo>,
+}
+
+/// One version of an entry, stored in a specific pak.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryVersionInfo {
+    pub belongs_to: PakId,
+    pub pak_path: String,
+    /// Position of the pak in load order.
+    pub load_index: usize,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub compression_type: u8,
+    pub is_compressed: bool,
+    pub checksum: String,
+    /// Whether this version is used, i.e. not overridden by a newer pak.
+    pub is_active: bool,
+}
+
 pub struct Pak {
     pub(crate) id: PakId,
     pub(crate) path: String,
+    pub(crate) archive_path: Option<String>,
     pub(crate) pakfile: Arc<PakFile>,
+    /// Hash -> index of the entry, built on first lookup.
+    entry_index: OnceLock<HashMap<u64, usize>>,
+    /// Detected types of entries, by hash.
+    file_types: Mutex<HashMap<u64, Option<FileType>>>,
 }
 
 impl Pak {
@@ -67,7 +205,56 @@ impl Pak {
         Pak {
             id: UniqueId::create().into(),
             path: path.to_string(),
+            archive_path: None,
             pakfile: Arc::new(pakfile),
+            entry_index: OnceLock::new(),
+            file_types: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Create a pak loaded from a zip archive.
+    ///
+    /// `path` is the virtual path of the pak inside the archive.
+    pub fn new_in_archive(path: &str, archive_path: &str, pakfile: PakFile) -> Pak {
+        Pak {
+            archive_path: Some(archive_path.to_string()),
+            ..Pak::new(path, pakfile)
+        }
+    }
+
+    pub fn find_entry(&self, hash: u64) -> Option<&PakEntry> {
+        let entries = self.pakfile.metadata().entries();
+        let index = self.entry_index.get_or_init(|| {
+            let mut index = HashMap::with_capacity(entries.len());
+            for (i, entry) in entries.iter().enumerate() {
+                // keep the first entry of a duplicated hash, as a linear scan would
+                index.entry(entry.hash()).or_insert(i);
+            }
+            index
+        });
+        index.get(&hash).map(|i| &entries[*i])
+    }
+
+    /// Type of an entry if it was already detected, never reads the entry.
+    pub fn cached_file_type(&self, hash: u64) -> Option<FileType> {
+        self.file_types.lock().get(&hash).copied().flatten()
+    }
+
+    /// Detect the type of an entry from its first bytes, results are cached.
+    pub fn detect_file_type(&self, entry: &PakEntry) -> Option<FileType> {
+        if let Some(file_type) = self.file_types.lock().get(&entry.hash()) {
+            return *file_type;
+        }
+        let file_type = file_type::detect_entry(&self.pakfile, entry);
+        self.file_types.lock().insert(entry.hash(), file_type);
+        file_type
+    }
+
+    pub fn info(&self) -> PakInfo {
+        PakInfo {
+            id: self.id,
+            path: self.path.clone(),
+            archive_path: self.archive_path.clone(),
         }
     }
 }
@@ -79,18 +266,52 @@ pub(super) struct FileTreeStats {
     pub file_count: u64,
 }
 
+/// Get the path of an entry, or a placeholder under `_Unknown/` if not in the file list.
+pub(super) fn entry_path(name_table: &LayeredNames, hash: u64) -> String {
+    name_table
+        .get_file_name(hash)
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("_Unknown/{hash:08X}"))
+        .replace('\\', "/")
+}
+
+/// Get the tree data of an entry from the metadata only.
+///
+/// Entries not in the file list get the type detected so far, if any. Tree pages detect
+/// the rest when they are read, see [`group::PakGroup::read_tree_children`].
+pub(super) fn tree_entry_data(pak: &Pak, name_table: &LayeredNames, entry: &PakEntry) -> EntryData {
+    let mut data = EntryData::from_entry(pak.id, entry);
+    if name_table.get_file_name(entry.hash()).is_none() {
+        data.file_type = pak.cached_file_type(entry.hash());
+    }
+    data
+}
+
 pub(super) fn insert_tree_entry(
     root_children: &mut HashMap<SmolStr, FileTreeNode>,
     stats: &mut FileTreeStats,
-    pak_id: PakId,
-    name_table: &FileNameTable,
+    pak: &Pak,
+    name_table: &LayeredNames,
     entry: &PakEntry,
+    overridden: bool,
+) {
+    insert_tree_path(
+        root_children,
+        stats,
+        &entry_path(name_table, entry.hash()),
+        &tree_entry_data(pak, name_table, entry),
+        overridden,
+    );
+}
+
+pub(super) fn insert_tree_path(
+    root_children: &mut HashMap<SmolStr, FileTreeNode>,
+    stats: &mut FileTreeStats,
+    file_relative_path: &str,
+    entry: &EntryData,
+    overridden: bool,
 ) {
-    let file_relative_path = name_table
-        .get_file_name(entry.hash())
-        .map(|fname| fname.to_string().unwrap())
-        .unwrap_or_else(|| format!("_Unknown/{:08X}", entry.hash()))
-        .replace('\\', "/");
+    let pak_id = entry.belongs_to;
     let mut current_node = root_children;
     let mut components = file_relative_path
         .split('/')
@@ -100,28 +321,37 @@ pub(super) fn insert_tree_entry(
     while let Some(component) = components.next() {
         let is_dir = components.peek().is_some();
         let component_name = SmolStr::new(component);
-        let child_node = current_node
-            .entry(component_name.clone())
-            .or_insert_with(|| FileTreeNode {
-                info: NodeInfo {
-                    is_dir,
-                    relative_path: component_name,
-                    hash: None,
-                    uncompressed_size: 0,
-                    compressed_size: 0,
-                    is_compressed: false,
-                    belongs_to: if is_dir { None } else { Some(pak_id) },
-                },
-                children: HashMap::new(),
-            });
+        // overridden versions share the file name, keep them apart by pak
+        let key = if !is_dir && overridden {
+            SmolStr::new(format!("{component}#{pak_id}"))
+        } else {
+            component_name.clone()
+        };
+        let child_node = current_node.entry(key).or_insert_with(|| FileTreeNode {
+            info: NodeInfo {
+                is_dir,
+                relative_path: component_name,
+                hash: None,
+                uncompressed_size: 0,
+                compressed_size: 0,
+                is_compressed: false,
+                belongs_to: if is_dir { None } else { Some(pak_id) },
+                is_overridden: !is_dir && overridden,
+                file_type: None,
+            },
+            children: HashMap::new(),
+        });
         if !is_dir {
-            child_node.info.uncompressed_size = entry.uncompressed_size();
-            child_node.info.compressed_size = entry.compressed_size();
-            child_node.info.is_compressed = entry.compression_type() != CompressionType::None;
-            child_node.info.hash = Some(JsSafeHash::from_u64(entry.hash()));
-            stats.uncompressed_size += entry.uncompressed_size();
-            stats.compressed_size += entry.compressed_size();
-            stats.file_count += 1;
+            child_node.info.uncompressed_size = entry.uncompressed_size;
+            child_node.info.compressed_size = entry.compressed_size;
+            child_node.info.is_compressed = entry.is_compressed;
+            child_node.info.hash = Some(JsSafeHash::from_u64(entry.hash));
+            child_node.info.file_type = entry.file_type;
+            if !overridden {
+                stats.uncompressed_size += entry.uncompressed_size;
+                stats.compressed_size += entry.compressed_size;
+                stats.file_count += 1;
+            }
         }
         current_node = &mut child_node.children;
     }
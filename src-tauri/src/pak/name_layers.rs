@@ -0,0 +1,366 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use hashbrown::HashMap;
+use ree_pak_core::utf16_hash::Utf16HashExt;
+use rustc_hash::FxBuildHasher;
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+
+/// Layer of file lists loaded without a name.
+pub const BASE_LAYER_NAME: &str = "base";
+
+/// Where the names of a layer come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NameLayerSource {
+    /// A list file.
+    File,
+    /// Names found by resolvers or in opened archives.
+    Discovered,
+    /// Names pushed by the user.
+    Manual,
+}
+
+impl NameLayerSource {
+    /// Name of the single layer of a pushed source.
+    fn layer_name(self) -> &'static str {
+        match self {
+            Self::File => BASE_LAYER_NAME,
+            Self::Discovered => "discovered",
+            Self::Manual => "manual",
+        }
+    }
+}
+
+/// Names of one layer, by hash.
+///
+/// Unlike `FileNameTable`, names read with their hash, e.g. from a list cache, are inserted
+/// without hashing them again.
+#[derive(Debug, Clone, Default)]
+pub struct NameTable {
+    names: HashMap<u64, Box<str>, FxBuildHasher>,
+}
+
+impl NameTable {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            names: HashMap::with_capacity_and_hasher(capacity, FxBuildHasher),
+        }
+    }
+
+    pub fn push_str(&mut self, path: &str) {
+        self.insert(path.hash_mixed(), path);
+    }
+
+    /// Insert a name under its known hash.
+    pub fn insert(&mut self, hash: u64, path: &str) {
+        self.names.insert(hash, path.into());
+    }
+
+    pub fn get_file_name(&self, hash: u64) -> Option<&str> {
+        self.names.get(&hash).map(AsRef::as_ref)
+    }
+
+    pub fn file_names(&self) -> impl Iterator<Item = (u64, &str)> {
+        self.names.iter().map(|(hash, path)| (*hash, path.as_ref()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+struct NameLayer {
+    name: String,
+    source: NameLayerSource,
+    /// Path of the list file.
+    path: Option<String>,
+    enabled: bool,
+    /// Shared with [`LayeredNames`] views, copied on write while a view is alive.
+    table: Arc<NameTable>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NameLayerInfo {
+    pub name: String,
+    pub source: NameLayerSource,
+    pub path: Option<String>,
+    pub enabled: bool,
+    pub name_count: usize,
+}
+
+/// A name and the layer it was resolved from.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NameLookup {
+    pub path: String,
+    pub layer: String,
+}
+
+/// Enabled name layers, looked up from the top down without merging them.
+///
+/// Cloning only shares the layer tables.
+#[derive(Clone, Default)]
+pub struct LayeredNames {
+    /// Bottom to top.
+    layers: Vec<Arc<NameTable>>,
+}
+
+impl LayeredNames {
+    /// Find the name of a hash in the topmost layer containing it.
+    pub fn get_file_name(&self, hash: u64) -> Option<&str> {
+        self.layers
+            .iter()
+            .rev()
+            .find_map(|table| table.get_file_name(hash))
+    }
+
+    /// All names, each hash once with the name of its topmost layer.
+    pub fn file_names(&self) -> impl Iterator<Item = (u64, &str)> {
+        self.layers
+            .iter()
+            .enumerate()
+            .rev()
+            .flat_map(move |(index, table)| {
+                let upper = &self.layers[index + 1..];
+                table.file_names().filter(move |(hash, _)| {
+                    upper
+                        .iter()
+                        .all(|table| table.get_file_name(*hash).is_none())
+                })
+            })
+    }
+}
+
+/// Stack of file name tables, upper layers take precedence.
+///
+/// List files stay below discovered and manual names. Lookups across the stack go through
+/// the view returned by [`NameLayerStack::names`].
+#[derive(Default)]
+pub struct NameLayerStack {
+    layers: Vec<NameLayer>,
+}
+
+impl NameLayerStack {
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Add a layer loaded from a list file, replacing the layer of the same name.
+    pub fn set_file_layer(&mut self, name: &str, path: Option<String>, table: NameTable) {
+        let layer = NameLayer {
+            name: name.to_string(),
+            source: NameLayerSource::File,
+            path,
+            enabled: true,
+            table: Arc::new(table),
+        };
+        if let Some(existing) = self.layers.iter_mut().find(|layer| layer.name == name) {
+            *existing = layer;
+            return;
+        }
+        let position = self
+            .layers
+            .iter()
+            .position(|layer| layer.source != NameLayerSource::File)
+            .unwrap_or(self.layers.len());
+        self.layers.insert(position, layer);
+    }
+
+    /// Push names into the layer of a source, created on first push.
+    ///
+    /// Returns whether the layer is enabled.
+    pub fn push_paths(&mut self, source: NameLayerSource, paths: &[String]) -> bool {
+        let name = source.layer_name();
+        let index = match self.layers.iter().position(|layer| layer.name == name) {
+            Some(index) => index,
+            None => {
+                self.layers.push(NameLayer {
+                    name: name.to_string(),
+                    source,
+                    path: None,
+                    enabled: true,
+                    table: Arc::default(),
+                });
+                self.layers.len() - 1
+            }
+        };
+        let layer = &mut self.layers[index];
+        let table = Arc::make_mut(&mut layer.table);
+        for path in paths {
+            table.push_str(path);
+        }
+        layer.enabled
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<()> {
+        self.layer_mut(name)?.enabled = enabled;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<()> {
+        let index = self
+            .layers
+            .iter()
+            .position(|layer| layer.name == name)
+            .ok_or_else(|| Error::NameLayerNotFound(name.to_string()))?;
+        self.layers.remove(index);
+        Ok(())
+    }
+
+    /// Layers from bottom to top.
+    pub fn infos(&self) -> Vec<NameLayerInfo> {
+        self.layers
+            .iter()
+            .map(|layer| NameLayerInfo {
+                name: layer.name.clone(),
+                source: layer.source,
+                path: layer.path.clone(),
+                enabled: layer.enabled,
+                name_count: layer.table.len(),
+            })
+            .collect()
+    }
+
+    /// Find the name of a hash in the topmost enabled layer containing it.
+    pub fn lookup(&self, hash: u64) -> Option<NameLookup> {
+        self.enabled_layers().rev().find_map(|layer| {
+            let path = layer.table.get_file_name(hash)?.to_string();
+            Some(NameLookup {
+                path,
+                layer: layer.name.clone(),
+            })
+        })
+    }
+
+    /// View of the enabled layers, `None` if no layer is enabled.
+    pub fn names(&self) -> Option<LayeredNames> {
+        let layers = self
+            .enabled_layers()
+            .map(|layer| Arc::clone(&layer.table))
+            .collect::<Vec<_>>();
+        (!layers.is_empty()).then_some(LayeredNames { layers })
+    }
+
+    /// Write the names of enabled layers as one list file, sorted and without duplicates.
+    ///
+    /// Returns the number of names written.
+    pub fn export(&self, output_path: impl AsRef<Path>) -> Result<usize> {
+        let output_path = output_path.as_ref();
+        let mut names = self
+            .names()
+            .map(|names| {
+                names
+                    .file_names()
+                    .map(|(_, name)| name.to_string())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        names.sort_unstable();
+
+        let io_error = |e| Error::FileIO {
+            path: output_path.to_string_lossy().to_string(),
+            source: e,
+        };
+        let mut writer = BufWriter::new(File::create(output_path).map_err(io_error)?);
+        for name in &names {
+            writeln!(writer, "{name}").map_err(io_error)?;
+        }
+        writer.flush().map_err(io_error)?;
+        Ok(names.len())
+    }
+
+    fn enabled_layers(&self) -> impl DoubleEndedIterator<Item = &NameLayer> {
+        self.layers.iter().filter(|layer| layer.enabled)
+    }
+
+    fn layer_mut(&mut self, name: &str) -> Result<&mut NameLayer> {
+        self.layers
+            .iter_mut()
+            .find(|layer| layer.name == name)
+            .ok_or_else(|| Error::NameLayerNotFound(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(paths: &[&str]) -> NameTable {
+        let mut table = NameTable::default();
+        for path in paths {
+            table.push_str(path);
+        }
+        table
+    }
+
+    #[test]
+    fn lookup_reports_topmost_enabled_layer() {
+        let mut stack = NameLayerStack::default();
+        stack.set_file_layer(BASE_LAYER_NAME, None, table(&["natives/STM/a.tex.1"]));
+        stack.push_paths(
+            NameLayerSource::Manual,
+            &["natives/STM/b.tex.1".to_string()],
+        );
+        // file layers are kept below manual names
+        stack.set_file_layer("team", None, table(&["natives/STM/a.tex.1"]));
+
+        let names = stack
+            .infos()
+            .into_iter()
+            .map(|info| info.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["base", "team", "manual"]);
+
+        let hash = "natives/STM/a.tex.1".hash_mixed();
+        assert_eq!(stack.lookup(hash).unwrap().layer, "team");
+        stack.set_enabled("team", false).unwrap();
+        assert_eq!(stack.lookup(hash).unwrap().layer, "base");
+        assert!(stack.set_enabled("missing", false).is_err());
+    }
+
+    #[test]
+    fn names_share_layers_and_list_each_hash_once() {
+        let mut stack = NameLayerStack::default();
+        stack.set_file_layer(BASE_LAYER_NAME, None, table(&["a.tex.1", "b.tex.1"]));
+        stack.push_paths(NameLayerSource::Manual, &["A.tex.1".to_string()]);
+
+        let names = stack.names().unwrap();
+        // the view keeps the manual layer alive, pushing copies it
+        stack.push_paths(NameLayerSource::Manual, &["c.tex.1".to_string()]);
+
+        let hash = "a.tex.1".hash_mixed();
+        assert_eq!(names.get_file_name(hash), Some("A.tex.1"));
+        assert_eq!(names.file_names().count(), 2);
+        assert!(names.get_file_name("c.tex.1".hash_mixed()).is_none());
+        assert_eq!(stack.names().unwrap().file_names().count(), 3);
+    }
+
+    #[test]
+    fn export_writes_merged_names() {
+        let mut stack = NameLayerStack::default();
+        stack.set_file_layer(BASE_LAYER_NAME, None, table(&["b.tex.1", "a.tex.1"]));
+        stack.push_paths(NameLayerSource::Discovered, &["a.tex.1".to_string()]);
+        stack.set_file_layer("disabled", None, table(&["c.tex.1"]));
+        stack.set_enabled("disabled", false).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("merged.list");
+        assert_eq!(stack.export(&path).unwrap(), 2);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "a.tex.1\nb.tex.1\n"
+        );
+    }
+}
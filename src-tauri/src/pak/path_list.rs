@@ -0,0 +1,147 @@
+use hashbrown::HashMap;
+use ree_pak_core::utf16_hash::Utf16HashExt;
+use serde::{Deserialize, Serialize};
+
+use crate::common::JsSafeHash;
+use crate::path_components::PathComponents;
+
+use super::{
+    ExtractFileInfo, ExtractMode, PakId, archive_output::ExtractArchiveFormat, group::PakGroup,
+    report::JobReport,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractListOptions {
+    /// List with one path per line, plain or compressed like file lists.
+    ///
+    /// See [`super::list_file::read_list_paths`] for supported formats.
+    pub list_path: String,
+    pub output_path: String,
+    pub r#override: bool,
+    pub mode: ExtractMode,
+    /// Match paths with any version suffix, e.g. `a.tex` matches `a.tex.241106027`.
+    pub ignore_version: Option<bool>,
+    pub archive: Option<ExtractArchiveFormat>,
+    pub save_report: Option<bool>,
+}
+
+impl ExtractListOptions {
+    pub fn ignore_version(&self) -> bool {
+        self.ignore_version.unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractListReport {
+    /// Number of paths in the list.
+    pub requested: usize,
+    /// Number of distinct entries the paths resolved to, see `job` for how many were extracted.
+    pub resolved: usize,
+    /// Paths without a loaded entry, as written in the list.
+    pub not_found: Vec<String>,
+    /// Outcome of extracting the resolved entries.
+    pub job: JobReport,
+}
+
+/// Entries resolved from a path list.
+#[derive(Debug, Default)]
+pub struct PathListResolution {
+    pub files: Vec<ExtractFileInfo>,
+    pub report: ExtractListReport,
+}
+
+impl PakGroup {
+    /// Resolve each path of a list to its newest entry.
+    ///
+    /// Lines are normalized with [`PathComponents::parse`], empty lines and comments are skipped.
+    pub fn resolve_path_list(&self, list: &[String], ignore_version: bool) -> PathListResolution {
+        // hash -> newest pak containing it
+        let mut owners = HashMap::new();
+        for pak in self.paks() {
+            for entry in pak.pakfile.metadata().entries() {
+                owners.insert(entry.hash(), pak.id);
+            }
+        }
+        let versionless = if ignore_version {
+            self.versionless_index()
+        } else {
+            HashMap::new()
+        };
+
+        let mut resolution = PathListResolution::default();
+        let mut resolved_hashes = HashMap::new();
+        for line in list {
+            let Some(components) = PathComponents::parse(line) else {
+                continue;
+            };
+            resolution.report.requested += 1;
+
+            let hash = components.full_path().hash_mixed();
+            let found = owners.get(&hash).map(|pak_id| (hash, *pak_id)).or_else(|| {
+                versionless
+                    .get(&components.without_version().to_ascii_lowercase())
+                    .copied()
+            });
+            match found {
+                Some((hash, belongs_to)) => {
+                    resolved_hashes.insert(hash, belongs_to);
+                }
+                None => resolution.report.not_found.push(line.trim().to_string()),
+            }
+        }
+
+        resolution.report.resolved = resolved_hashes.len();
+        resolution.files = resolved_hashes
+            .into_iter()
+            .map(|(hash, belongs_to)| ExtractFileInfo {
+                hash: JsSafeHash::from_u64(hash),
+                belongs_to,
+                relative_root: None,
+            })
+            .collect();
+        resolution
+    }
+
+    /// Map lower case paths without version to loaded entries, newer paks win.
+    fn versionless_index(&self) -> HashMap<String, (u64, PakId)> {
+        let Some(file_name_table) = self.file_name_table() else {
+            return HashMap::new();
+        };
+
+        let mut index = HashMap::new();
+        for pak in self.paks() {
+            for entry in pak.pakfile.metadata().entries() {
+                let Some(components) = file_name_table
+                    .get_file_name(entry.hash())
+                    .and_then(PathComponents::parse)
+                else {
+                    continue;
+                };
+                index.insert(
+                    components.without_version().to_ascii_lowercase(),
+                    (entry.hash(), pak.id),
+                );
+            }
+        }
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_lines_are_normalized_before_hashing() {
+        let components = PathComponents::parse("  natives\\STM\\a.tex.241106027  ").unwrap();
+
+        assert_eq!(components.full_path(), "natives/STM/a.tex.241106027");
+        assert_eq!(
+            components.without_version().to_ascii_lowercase(),
+            "natives/stm/a.tex"
+        );
+        assert!(PathComponents::parse("# comment").is_none());
+    }
+}
@@ -0,0 +1,140 @@
+use std::path::{Path, PathBuf};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::common::JsSafeHash;
+use crate::error::{Error, Result};
+
+const REPORT_FILE_NAME: &str = "extract_report.json";
+
+/// Outcome of an extraction or export job.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobReport {
+    /// Number of entries written.
+    pub succeeded: usize,
+    /// Entries not written because the output already exists.
+    pub skipped: Vec<JobReportEntry>,
+    /// Number of existing files left untouched because their content is the same.
+    pub unchanged: usize,
+    /// Number of entries already extracted by an interrupted run.
+    pub resumed: usize,
+    /// Number of files produced by converters.
+    pub converted: usize,
+    pub failed: Vec<JobReportEntry>,
+    /// Bytes written to disk, files rewritten from their first change count the rewritten part.
+    pub bytes_written: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobReportEntry {
+    pub hash: JsSafeHash,
+    pub path: String,
+    pub reason: String,
+}
+
+impl JobReport {
+    /// Save the report as pretty printed JSON.
+    pub fn save_json(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let json =
+            serde_json::to_string_pretty(self).map_err(|e| Error::Internal(e.to_string()))?;
+        std::fs::write(path, json).map_err(|e| Error::FileIO {
+            path: path.to_string_lossy().to_string(),
+            source: e,
+        })
+    }
+
+    /// Report path next to the output.
+    ///
+    /// Inside an output directory, or beside an output archive, e.g. `out.zip.report.json`.
+    pub fn path_for_output(output_path: impl AsRef<Path>, is_archive: bool) -> PathBuf {
+        let output_path = output_path.as_ref();
+        if !is_archive {
+            return output_path.join(REPORT_FILE_NAME);
+        }
+        let mut file_name = output_path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".report.json");
+        output_path.with_file_name(file_name)
+    }
+}
+
+/// Collects job outcomes from parallel workers.
+#[derive(Debug, Default)]
+pub struct JobReportCollector {
+    report: Mutex<JobReport>,
+}
+
+impl JobReportCollector {
+    pub fn succeeded(&self, bytes_written: u64) {
+        let mut report = self.report.lock();
+        report.succeeded += 1;
+        report.bytes_written += bytes_written;
+    }
+
+    pub fn unchanged(&self) {
+        self.report.lock().unchanged += 1;
+    }
+
+    pub fn converted(&self, count: usize) {
+        self.report.lock().converted += count;
+    }
+
+    pub fn resumed(&self, count: usize) {
+        self.report.lock().resumed += count;
+    }
+
+    pub fn skipped(&self, hash: u64, path: impl Into<String>, reason: impl Into<String>) {
+        self.report.lock().skipped.push(JobReportEntry {
+            hash: JsSafeHash::from_u64(hash),
+            path: path.into(),
+            reason: reason.into(),
+        });
+    }
+
+    pub fn failed(&self, hash: u64, path: impl Into<String>, reason: impl Into<String>) {
+        self.report.lock().failed.push(JobReportEntry {
+            hash: JsSafeHash::from_u64(hash),
+            path: path.into(),
+            reason: reason.into(),
+        });
+    }
+
+    pub fn into_report(self) -> JobReport {
+        self.report.into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collector_sums_outcomes() {
+        let collector = JobReportCollector::default();
+        collector.succeeded(10);
+        collector.succeeded(5);
+        collector.skipped(1, "natives/STM/a.tex", "already exists");
+        collector.failed(2, "natives/STM/b.tex", "invalid data");
+
+        let report = collector.into_report();
+        assert_eq!(report.succeeded, 2);
+        assert_eq!(report.bytes_written, 15);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.failed[0].reason, "invalid data");
+    }
+
+    #[test]
+    fn report_path_is_next_to_output() {
+        assert_eq!(
+            JobReport::path_for_output("out/dir", false),
+            Path::new("out/dir").join(REPORT_FILE_NAME)
+        );
+        assert_eq!(
+            JobReport::path_for_output("out/mod.zip", true),
+            Path::new("out/mod.zip.report.json")
+        );
+    }
+}
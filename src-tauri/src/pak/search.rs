@@ -0,0 +1,221 @@
+use hashbrown::HashSet;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+use crate::common::JsSafeHash;
+use crate::error::{Error, Result};
+use crate::path_components::PathComponents;
+
+use super::{
+    PakId, arena_tree::EntryData, filter::PathPattern, group::PakGroup, variant::AssetVariant,
+};
+
+const DEFAULT_SEARCH_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntrySearchQuery {
+    pub pattern: PathPattern,
+    /// Maximum number of hits, 1000 if not set.
+    pub limit: Option<usize>,
+    /// Collapse language and platform variants of an asset into one hit, off if not set.
+    pub group_variants: Option<bool>,
+}
+
+impl EntrySearchQuery {
+    pub fn group_variants(&self) -> bool {
+        self.group_variants.unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntrySearchHit {
+    pub path: String,
+    pub hash: JsSafeHash,
+    /// Pak of the active version.
+    pub belongs_to: PakId,
+    pub pak_path: String,
+    pub uncompressed_size: u64,
+    /// All variants sorted by name when grouped, the hit itself is the first one.
+    pub variants: Option<Vec<AssetVariant>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntrySearchResult {
+    /// Hits sorted by path.
+    pub hits: Vec<EntrySearchHit>,
+    /// More entries matched than the limit.
+    pub truncated: bool,
+}
+
+impl PakGroup {
+    /// Find the active versions of entries whose path matches a pattern.
+    ///
+    /// Entries missing from the file list have no path and are never matched.
+    pub fn search_entries(&self, query: &EntrySearchQuery) -> Result<EntrySearchResult> {
+        if self.paks().is_empty() {
+            return Err(Error::NoPaksLoaded);
+        }
+        let Some(file_name_table) = self.file_name_table() else {
+            return Err(Error::MissingFileList);
+        };
+        let regex = query.pattern.compile()?;
+
+        let mut seen = HashSet::new();
+        let mut hits = vec![];
+        // newest paks first, so each hash is reported once with its active version
+        for pak in self.paks().iter().rev() {
+            for entry in pak.pakfile.metadata().entries() {
+                if !seen.insert(entry.hash()) {
+                    continue;
+                }
+                let Some(path) = file_name_table
+                    .get_file_name(entry.hash())
+                    .map(str::to_string)
+                else {
+                    continue;
+                };
+                if regex.is_match(&path.replace('\\', "/")) {
+                    let hit = EntrySearchHit {
+                        path,
+                        hash: JsSafeHash::from_u64(entry.hash()),
+                        belongs_to: pak.id,
+                        pak_path: pak.path.clone(),
+                        uncompressed_size: entry.uncompressed_size(),
+                        variants: None,
+                    };
+                    hits.push((hit, EntryData::from_entry(pak.id, entry)));
+                }
+            }
+        }
+
+        hits.sort_unstable_by(|(a, _), (b, _)| a.path.cmp(&b.path));
+        let mut hits = if query.group_variants() {
+            group_variant_hits(hits)
+        } else {
+            hits.into_iter().map(|(hit, _)| hit).collect()
+        };
+        let limit = query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+        let truncated = hits.len() > limit;
+        hits.truncate(limit);
+        Ok(EntrySearchResult { hits, truncated })
+    }
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum HitKey {
+    Path(String),
+    VariantBase(String),
+}
+
+/// Collapse hits of tagged paths sharing a variant base into their first hit.
+///
+/// Untagged paths and assets with a single variant are kept as they are, in path order.
+fn group_variant_hits(hits: Vec<(EntrySearchHit, EntryData)>) -> Vec<EntrySearchHit> {
+    let mut groups: IndexMap<HitKey, Vec<(EntrySearchHit, EntryData)>> = IndexMap::new();
+    for (hit, data) in hits {
+        let key = PathComponents::parse(&hit.path)
+            .filter(|components| components.variant_base() != hit.path.replace('\\', "/"))
+            .map_or_else(
+                || HitKey::Path(hit.path.clone()),
+                |components| HitKey::VariantBase(components.variant_base().to_string()),
+            );
+        groups.entry(key).or_default().push((hit, data));
+    }
+
+    groups
+        .into_values()
+        .map(|members| {
+            if members.len() == 1 {
+                return members.into_iter().next().unwrap().0;
+            }
+
+            let variants = members
+                .iter()
+                .map(|(hit, data)| {
+                    let name = hit.path.rsplit(['/', '\\']).next().unwrap_or_default();
+                    let components = PathComponents::parse(&hit.path);
+                    let tag = |tag: fn(&PathComponents) -> Option<&str>| {
+                        components.as_ref().and_then(tag).map(SmolStr::new)
+                    };
+                    AssetVariant {
+                        name: SmolStr::new(name),
+                        language: tag(PathComponents::language),
+                        platform: tag(PathComponents::platform),
+                        hash: Some(hit.hash),
+                        compressed_size: data.compressed_size,
+                        uncompressed_size: data.uncompressed_size,
+                        is_compressed: data.is_compressed,
+                        belongs_to: Some(data.belongs_to),
+                        is_overridden: false,
+                    }
+                })
+                .collect();
+            let mut primary = members.into_iter().next().unwrap().0;
+            primary.variants = Some(variants);
+            primary
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pak::{
+        Pak,
+        test_util::{name_table, write_pak},
+    };
+
+    #[test]
+    fn search_groups_variants_into_the_first_hit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("re_chunk_000.pak");
+        let paths = [
+            "natives/STM/gui/a.msg.23.Ja",
+            "natives/STM/gui/a.msg.23.En",
+            "natives/STM/gui/b.msg.23.En",
+            "natives/STM/gui/c.tex.241106027",
+        ];
+        let files = paths.map(|path| (path, b"data".as_slice()));
+        let pakfile = write_pak(&path, &files);
+        let mut pak_group = PakGroup::new();
+        pak_group.add_pak(Pak::new(&path.to_string_lossy(), pakfile));
+        pak_group.load_name_layer("list", None, name_table(&paths));
+
+        let mut query = EntrySearchQuery {
+            pattern: PathPattern::Glob("natives/STM/gui/*".to_string()),
+            limit: Some(2),
+            group_variants: None,
+        };
+        let result = pak_group.search_entries(&query).unwrap();
+        assert_eq!(result.hits.len(), 2);
+        assert!(result.truncated);
+        assert!(result.hits.iter().all(|hit| hit.variants.is_none()));
+
+        query.group_variants = Some(true);
+        let result = pak_group.search_entries(&query).unwrap();
+        let hits = result
+            .hits
+            .iter()
+            .map(|hit| hit.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hits,
+            ["natives/STM/gui/a.msg.23.En", "natives/STM/gui/b.msg.23.En"]
+        );
+        assert!(result.truncated);
+        let variants = result.hits[0].variants.as_ref().unwrap();
+        let names = variants
+            .iter()
+            .map(|variant| (variant.name.as_str(), variant.language.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [("a.msg.23.En", Some("En")), ("a.msg.23.Ja", Some("Ja"))]
+        );
+        assert!(result.hits[1].variants.is_none());
+    }
+}
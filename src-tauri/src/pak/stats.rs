@@ -0,0 +1,275 @@
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::path_components::PathComponents;
+
+use super::{PakId, PakInfo, arena_tree::EntryData};
+
+const DEFAULT_TOP_DIRECTORIES: usize = 20;
+const DEFAULT_DIRECTORY_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PakStatsOptions {
+    /// Only count entries of these paks, including entries shadowed by newer paks.
+    ///
+    /// If `None`, count the combined view of all loaded paks.
+    pub pak_ids: Option<Vec<PakId>>,
+    /// Number of largest directories to report.
+    pub top_directories: Option<usize>,
+    /// Directories are grouped by this many leading path components.
+    pub directory_depth: Option<usize>,
+}
+
+impl PakStatsOptions {
+    pub fn top_directories(&self) -> usize {
+        self.top_directories.unwrap_or(DEFAULT_TOP_DIRECTORIES)
+    }
+
+    pub fn directory_depth(&self) -> usize {
+        self.directory_depth
+            .unwrap_or(DEFAULT_DIRECTORY_DEPTH)
+            .max(1)
+    }
+}
+
+/// Count and sizes of a group of entries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsBucket {
+    pub file_count: u64,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    /// Compressed size / uncompressed size.
+    pub compression_ratio: f64,
+}
+
+impl StatsBucket {
+    fn add(&mut self, entry: &EntryData) {
+        self.file_count += 1;
+        self.compressed_size += entry.compressed_size;
+        self.uncompressed_size += entry.uncompressed_size;
+    }
+
+    fn finish(&mut self) {
+        self.compression_ratio = if self.uncompressed_size == 0 {
+            1.0
+        } else {
+            self.compressed_size as f64 / self.uncompressed_size as f64
+        };
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionStats {
+    /// Lower case extension without version suffix, `None` for unknown entries.
+    pub extension: Option<String>,
+    #[serde(flatten)]
+    pub stats: StatsBucket,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryStats {
+    pub path: String,
+    #[serde(flatten)]
+    pub stats: StatsBucket,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PakStats {
+    pub pak_id: PakId,
+    pub pak_path: String,
+    #[serde(flatten)]
+    pub stats: StatsBucket,
+}
+
+/// Statistics of loaded paks, sorted by uncompressed size, largest first.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PakGroupStats {
+    pub total: StatsBucket,
+    pub by_extension: Vec<ExtensionStats>,
+    pub top_directories: Vec<DirectoryStats>,
+    /// Paks in load order.
+    pub by_pak: Vec<PakStats>,
+}
+
+/// Aggregates entries into [`PakGroupStats`].
+pub(super) struct StatsCollector {
+    directory_depth: usize,
+    top_directories: usize,
+    total: StatsBucket,
+    by_extension: HashMap<Option<String>, StatsBucket>,
+    by_directory: HashMap<String, StatsBucket>,
+    by_pak: Vec<(PakInfo, StatsBucket)>,
+}
+
+impl StatsCollector {
+    pub(super) fn new(options: &PakStatsOptions) -> Self {
+        Self {
+            directory_depth: options.directory_depth(),
+            top_directories: options.top_directories(),
+            total: StatsBucket::default(),
+            by_extension: HashMap::new(),
+            by_directory: HashMap::new(),
+            by_pak: vec![],
+        }
+    }
+
+    /// Register a pak, paks should be added in load order.
+    pub(super) fn add_pak(&mut self, pak_info: PakInfo) {
+        self.by_pak.push((pak_info, StatsBucket::default()));
+    }
+
+    /// Add an entry, `path` is `None` if the entry is not in the file list.
+    pub(super) fn add_entry(&mut self, path: Option<&str>, entry: &EntryData) {
+        self.total.add(entry);
+
+        let extension = path
+            .and_then(PathComponents::parse)
+            .and_then(|components| components.extension().map(str::to_ascii_lowercase));
+        self.by_extension.entry(extension).or_default().add(entry);
+
+        let directory = path.map_or_else(
+            || "_Unknown".to_string(),
+            |path| directory_prefix(path, self.directory_depth),
+        );
+        self.by_directory.entry(directory).or_default().add(entry);
+
+        if let Some((_, stats)) = self
+            .by_pak
+            .iter_mut()
+            .find(|(pak_info, _)| pak_info.id == entry.belongs_to)
+        {
+            stats.add(entry);
+        }
+    }
+
+    pub(super) fn finish(mut self) -> PakGroupStats {
+        self.total.finish();
+
+        let mut by_extension = self
+            .by_extension
+            .into_iter()
+            .map(|(extension, mut stats)| {
+                stats.finish();
+                ExtensionStats { extension, stats }
+            })
+            .collect::<Vec<_>>();
+        by_extension.sort_by(|a, b| {
+            b.stats
+                .uncompressed_size
+                .cmp(&a.stats.uncompressed_size)
+                .then_with(|| a.extension.cmp(&b.extension))
+        });
+
+        let mut top_directories = self
+            .by_directory
+            .into_iter()
+            .map(|(path, mut stats)| {
+                stats.finish();
+                DirectoryStats { path, stats }
+            })
+            .collect::<Vec<_>>();
+        top_directories.sort_by(|a, b| {
+            b.stats
+                .uncompressed_size
+                .cmp(&a.stats.uncompressed_size)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        top_directories.truncate(self.top_directories);
+
+        let by_pak = self
+            .by_pak
+            .into_iter()
+            .map(|(pak_info, mut stats)| {
+                stats.finish();
+                PakStats {
+                    pak_id: pak_info.id,
+                    pak_path: pak_info.path,
+                    stats,
+                }
+            })
+            .collect();
+
+        PakGroupStats {
+            total: self.total,
+            by_extension,
+            top_directories,
+            by_pak,
+        }
+    }
+}
+
+/// Get the first `depth` components of the directory of a path.
+///
+/// Example: `natives/STM/Art/a.tex`, depth 2 -> `natives/STM`
+fn directory_prefix(path: &str, depth: usize) -> String {
+    let components = path
+        .split('/')
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<_>>();
+    let directory_len = components.len().saturating_sub(1);
+    components[..directory_len.min(depth)].join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pak::test_util::{compressed_entry, entry, new_pak_id};
+
+    #[test]
+    fn test_directory_prefix() {
+        assert_eq!(directory_prefix("natives/STM/Art/a.tex", 2), "natives/STM");
+        assert_eq!(
+            directory_prefix("natives/STM/Art/a.tex", 8),
+            "natives/STM/Art"
+        );
+        assert_eq!(directory_prefix("a.tex", 2), "");
+    }
+
+    #[test]
+    fn stats_group_extensions_ignoring_version_suffix() {
+        let pak = PakInfo {
+            id: new_pak_id(),
+            path: "re_chunk_000.pak".to_string(),
+            archive_path: None,
+        };
+        let options = PakStatsOptions {
+            directory_depth: Some(3),
+            top_directories: Some(1),
+            ..PakStatsOptions::default()
+        };
+        let mut collector = StatsCollector::new(&options);
+        collector.add_pak(pak.clone());
+        collector.add_entry(
+            Some("natives/STM/Art/a.tex.241106027"),
+            &compressed_entry(0, 50, 100, pak.id),
+        );
+        collector.add_entry(
+            Some("natives/STM/Art/b.TEX.143230"),
+            &compressed_entry(0, 50, 100, pak.id),
+        );
+        collector.add_entry(
+            Some("natives/STM/Sound/c.bnk.2.X64.En"),
+            &entry(0, 10, pak.id),
+        );
+        collector.add_entry(None, &entry(0, 1, pak.id));
+
+        let stats = collector.finish();
+
+        assert_eq!(stats.total.file_count, 4);
+        assert_eq!(stats.total.uncompressed_size, 211);
+        assert_eq!(stats.by_extension[0].extension.as_deref(), Some("tex"));
+        assert_eq!(stats.by_extension[0].stats.file_count, 2);
+        assert_eq!(stats.by_extension[0].stats.compression_ratio, 0.5);
+        assert_eq!(stats.by_extension[1].extension.as_deref(), Some("bnk"));
+        assert_eq!(stats.by_extension[2].extension, None);
+        assert_eq!(stats.top_directories.len(), 1);
+        assert_eq!(stats.top_directories[0].path, "natives/STM/Art");
+        assert_eq!(stats.by_pak[0].stats.file_count, 4);
+    }
+}
@@ -0,0 +1,105 @@
+use serde::Serialize;
+
+use crate::common::JsSafeHash;
+
+use super::PakId;
+
+/// Streaming counterpart of a natives entry, e.g. the high resolution mips of a texture.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamingPair {
+    pub hash: JsSafeHash,
+    pub belongs_to: PakId,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    /// Natives entry and streaming entry together.
+    pub combined_compressed_size: u64,
+    pub combined_uncompressed_size: u64,
+}
+
+/// Get `natives/<platform>` of a path.
+pub fn natives_root_entry(path: &str) -> Option<String> {
+    let components = path
+        .split('/')
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<_>>();
+    components
+        .iter()
+        .position(|component| component.eq_ignore_ascii_case("natives"))
+        .and_then(|index| {
+            (index + 1 < components.len()).then(|| components[..=index + 1].join("/"))
+        })
+}
+
+/// Get the streaming mirror of a natives path.
+///
+/// Example: `natives/STM/Art/a.tex` -> `natives/STM/streaming/Art/a.tex`,
+/// streaming paths are returned as they are.
+pub fn streaming_entry_path(path: &str) -> Option<String> {
+    let mut components = path
+        .split('/')
+        .filter(|component| !component.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let natives_index = components
+        .iter()
+        .position(|component| component.eq_ignore_ascii_case("natives"))?;
+    let streaming_index = natives_index + 2;
+    if streaming_index > components.len() {
+        return None;
+    }
+    if components
+        .get(streaming_index)
+        .is_some_and(|component| component.eq_ignore_ascii_case("streaming"))
+    {
+        return Some(components.join("/"));
+    }
+    components.insert(streaming_index, "streaming".to_string());
+    Some(components.join("/"))
+}
+
+/// Get the natives path of a streaming path, the inverse of [`streaming_entry_path`].
+///
+/// Returns `None` if the path is not under `natives/<platform>/streaming/`.
+pub fn natives_entry_path(path: &str) -> Option<String> {
+    let mut components = path
+        .split('/')
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<_>>();
+    let natives_index = components
+        .iter()
+        .position(|component| component.eq_ignore_ascii_case("natives"))?;
+    let streaming_index = natives_index + 2;
+    if !components
+        .get(streaming_index)
+        .is_some_and(|component| component.eq_ignore_ascii_case("streaming"))
+    {
+        return None;
+    }
+    components.remove(streaming_index);
+    Some(components.join("/"))
+}
+
+pub fn is_streaming_entry_path(path: &str) -> bool {
+    path.split('/')
+        .filter(|component| !component.is_empty())
+        .any(|component| component.eq_ignore_ascii_case("streaming"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natives_entry_path_removes_streaming() {
+        assert_eq!(
+            natives_entry_path("natives/STM/streaming/Art/a.tex.241106027"),
+            Some("natives/STM/Art/a.tex.241106027".to_string())
+        );
+        assert_eq!(natives_entry_path("natives/STM/Art/a.tex.241106027"), None);
+        assert_eq!(
+            streaming_entry_path(&natives_entry_path("natives/STM/streaming/Art/a").unwrap()),
+            Some("natives/STM/streaming/Art/a".to_string())
+        );
+    }
+}
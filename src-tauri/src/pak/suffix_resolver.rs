@@ -0,0 +1,268 @@
+use std::collections::BTreeSet;
+
+use hashbrown::{HashMap, HashSet};
+use rayon::prelude::*;
+use ree_pak_core::utf16_hash::Utf16HashExt;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::path_components::{LANGUAGES, PathComponents};
+
+use super::{group::PakGroup, name_layers::NameLayerSource};
+
+/// Platform and arch tags tried after the version.
+const PLATFORM_TAGS: &[&str] = &["X64", "STM", "NSW", "MSG"];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuffixResolveOptions {
+    /// Base paths without version, e.g. `natives/STM/Art/foo.tex`.
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Text file with one base path per line, in file list format.
+    pub list_path: Option<String>,
+    /// Versions to try per extension, e.g. `{ "tex": [241106027] }`.
+    ///
+    /// Versions of the loaded file list are always tried.
+    #[serde(default)]
+    pub versions: HashMap<String, Vec<u32>>,
+    /// Also try language suffixes, e.g. `.En`.
+    pub with_languages: Option<bool>,
+    /// Also try platform and arch suffixes, e.g. `.X64`.
+    pub with_platforms: Option<bool>,
+}
+
+impl SuffixResolveOptions {
+    pub fn with_languages(&self) -> bool {
+        self.with_languages.unwrap_or(true)
+    }
+
+    pub fn with_platforms(&self) -> bool {
+        self.with_platforms.unwrap_or(true)
+    }
+
+    /// Base paths from `paths` and the list file.
+    pub fn read_base_paths(&self) -> Result<Vec<String>> {
+        let mut paths = self.paths.clone();
+        if let Some(list_path) = &self.list_path {
+            let list = std::fs::read_to_string(list_path).map_err(|e| Error::FileIO {
+                path: list_path.clone(),
+                source: e,
+            })?;
+            paths.extend(list.lines().map(str::to_string));
+        }
+        Ok(paths)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuffixResolveReport {
+    /// Number of base paths tried.
+    pub base_paths: usize,
+    /// Number of candidate paths hashed.
+    pub candidates: usize,
+    /// Resolved paths, pushed into the discovered name layer.
+    pub resolved: Vec<String>,
+    /// Number of entries still missing from the file name table.
+    pub unknown_left: usize,
+}
+
+impl PakGroup {
+    /// Resolve unknown entries by trying version and variant suffixes on base paths.
+    ///
+    /// Each base path is combined with the known versions of its extension, then with
+    /// platform and language tags. Candidates are hashed with `hash_mixed`, matches of
+    /// unknown entries are pushed into the discovered name layer.
+    ///
+    /// Without a loaded file list every entry is unknown, only the versions of `options`
+    /// are tried then.
+    pub fn resolve_suffixes(
+        &mut self,
+        base_paths: &[String],
+        options: &SuffixResolveOptions,
+    ) -> Result<SuffixResolveReport> {
+        let file_name_table = self.file_name_table();
+        let unknown = self
+            .paks()
+            .iter()
+            .flat_map(|pak| pak.pakfile.metadata().entries())
+            .map(|entry| entry.hash())
+            .filter(|hash| file_name_table.is_none_or(|names| names.get_file_name(*hash).is_none()))
+            .collect::<HashSet<_>>();
+        let mut versions = self.known_versions();
+        for (extension, extra) in &options.versions {
+            versions
+                .entry(extension.to_ascii_lowercase())
+                .or_default()
+                .extend(extra.iter().map(u32::to_string));
+        }
+        let tails = suffix_tails(options.with_platforms(), options.with_languages());
+
+        let bases = base_paths
+            .iter()
+            .filter_map(|line| PathComponents::parse(line))
+            .collect::<Vec<_>>();
+        let (candidates, resolved) = bases
+            .par_iter()
+            .map(|base| {
+                let Some(base_versions) = base
+                    .extension()
+                    .and_then(|extension| versions.get(&extension.to_ascii_lowercase()))
+                else {
+                    return (0, vec![]);
+                };
+                let mut candidates = 0;
+                let mut resolved = vec![];
+                for version in base_versions {
+                    for tail in &tails {
+                        let candidate = format!("{}.{version}{tail}", base.base_path());
+                        candidates += 1;
+                        if unknown.contains(&candidate.hash_mixed()) {
+                            resolved.push(candidate);
+                        }
+                    }
+                }
+                (candidates, resolved)
+            })
+            .reduce(
+                || (0, vec![]),
+                |(count_a, mut resolved_a), (count_b, resolved_b)| {
+                    resolved_a.extend(resolved_b);
+                    (count_a + count_b, resolved_a)
+                },
+            );
+
+        let mut resolved_hashes = HashSet::new();
+        let resolved = resolved
+            .into_iter()
+            .filter(|path| resolved_hashes.insert(path.hash_mixed()))
+            .collect::<Vec<_>>();
+        if !resolved.is_empty() {
+            self.push_layer_paths(NameLayerSource::Discovered, &resolved);
+        }
+
+        Ok(SuffixResolveReport {
+            base_paths: bases.len(),
+            candidates,
+            unknown_left: unknown.len() - resolved.len(),
+            resolved,
+        })
+    }
+
+    /// Versions of known entries by lower case extension.
+    fn known_versions(&self) -> HashMap<String, BTreeSet<String>> {
+        let Some(file_name_table) = self.file_name_table() else {
+            return HashMap::new();
+        };
+
+        let mut versions = HashMap::<String, BTreeSet<String>>::new();
+        for pak in self.paks() {
+            for entry in pak.pakfile.metadata().entries() {
+                let Some(components) = file_name_table
+                    .get_file_name(entry.hash())
+                    .and_then(PathComponents::parse)
+                else {
+                    continue;
+                };
+                if let (Some(extension), Some(version)) =
+                    (components.extension(), components.version_str())
+                {
+                    versions
+                        .entry(extension.to_ascii_lowercase())
+                        .or_default()
+                        .insert(version.to_string());
+                }
+            }
+        }
+        versions
+    }
+}
+
+/// Suffixes tried after the version: none, platform, language, then platform and language.
+fn suffix_tails(with_platforms: bool, with_languages: bool) -> Vec<String> {
+    let platforms: &[&str] = if with_platforms { PLATFORM_TAGS } else { &[] };
+    let languages: &[&str] = if with_languages { LANGUAGES } else { &[] };
+    let platforms = std::iter::once("")
+        .chain(platforms.iter().copied())
+        .collect::<Vec<_>>();
+    let languages = std::iter::once("")
+        .chain(languages.iter().copied())
+        .collect::<Vec<_>>();
+
+    platforms
+        .iter()
+        .flat_map(|platform| {
+            languages.iter().map(move |language| {
+                [*platform, *language]
+                    .iter()
+                    .filter(|tag| !tag.is_empty())
+                    .map(|tag| format!(".{tag}"))
+                    .collect::<String>()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pak::{Pak, test_util::write_pak};
+
+    #[test]
+    fn resolved_paths_go_into_the_discovered_layer_without_a_file_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("re_chunk_000.pak");
+        let pakfile = write_pak(
+            &path,
+            &[
+                ("natives/STM/a.tex.241106027", b"a"),
+                ("natives/STM/b.tex.241106027.X64", b"b"),
+                ("natives/STM/c.mesh.2109148288", b"c"),
+            ],
+        );
+        let mut pak_group = PakGroup::new();
+        pak_group.add_pak(Pak::new(&path.to_string_lossy(), pakfile));
+
+        let options = SuffixResolveOptions {
+            versions: HashMap::from([("tex".to_string(), vec![241106027])]),
+            ..Default::default()
+        };
+        let base_paths = [
+            "natives/STM/a.tex".to_string(),
+            "natives/STM/b.tex".to_string(),
+        ];
+        let report = pak_group.resolve_suffixes(&base_paths, &options).unwrap();
+
+        assert_eq!(report.base_paths, 2);
+        assert_eq!(report.resolved.len(), 2);
+        assert_eq!(report.unknown_left, 1);
+        let found = pak_group
+            .name_layers()
+            .lookup("natives/STM/b.tex.241106027.X64".hash_mixed())
+            .unwrap();
+        assert_eq!(found.layer, "discovered");
+        assert!(
+            pak_group
+                .file_name_table()
+                .unwrap()
+                .get_file_name("natives/STM/c.mesh.2109148288".hash_mixed())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn suffix_tails_combine_platform_and_language() {
+        assert_eq!(suffix_tails(false, false), vec![String::new()]);
+
+        let tails = suffix_tails(true, true);
+        assert_eq!(
+            tails.len(),
+            (PLATFORM_TAGS.len() + 1) * (LANGUAGES.len() + 1)
+        );
+        assert!(tails.contains(&String::new()));
+        assert!(tails.contains(&".X64".to_string()));
+        assert!(tails.contains(&".En".to_string()));
+        assert!(tails.contains(&".X64.Ja".to_string()));
+    }
+}
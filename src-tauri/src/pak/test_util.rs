@@ -0,0 +1,60 @@
+//! Fixtures shared by the pak tests.
+
+use std::{fs::File, io::Write, path::Path};
+
+use ree_pak_core::{
+    pakfile::PakFile,
+    utf16_hash::Utf16HashExt,
+    write::{FileOptions, PakWriter},
+};
+
+use crate::common::UniqueId;
+
+use super::{PakId, arena_tree::EntryData, name_layers::NameTable};
+
+pub(crate) fn new_pak_id() -> PakId {
+    PakId::from(UniqueId::create())
+}
+
+/// Uncompressed entry of `size` bytes.
+pub(crate) fn entry(hash: u64, size: u64, belongs_to: PakId) -> EntryData {
+    compressed_entry(hash, size, size, belongs_to)
+}
+
+/// Entry marked compressed if the sizes differ.
+pub(crate) fn compressed_entry(
+    hash: u64,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    belongs_to: PakId,
+) -> EntryData {
+    EntryData {
+        hash,
+        compressed_size,
+        uncompressed_size,
+        is_compressed: compressed_size != uncompressed_size,
+        belongs_to,
+        file_type: None,
+    }
+}
+
+/// Write a pak with `(entry path, content)` files at `path` and open it.
+pub(crate) fn write_pak(path: &Path, files: &[(&str, &[u8])]) -> PakFile {
+    let mut writer = PakWriter::new(File::create(path).unwrap(), files.len() as u64);
+    for (entry_path, content) in files {
+        writer
+            .start_file_hash(entry_path.hash_mixed(), FileOptions::default())
+            .unwrap();
+        writer.write_all(content).unwrap();
+    }
+    writer.finish().unwrap();
+    PakFile::from_file(File::open(path).unwrap()).unwrap()
+}
+
+pub(crate) fn name_table(paths: &[&str]) -> NameTable {
+    let mut table = NameTable::default();
+    for path in paths {
+        table.push_str(path);
+    }
+    table
+}
@@ -1,11 +1,19 @@
+use std::cmp::Ordering;
+
 use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
 
 use crate::common::JsSafeHash;
-use crate::error::Result;
+use crate::path_components::PathComponents;
 
-use super::PakId;
+use super::{
+    PakId, PakInfo,
+    arena_tree::{ArenaTree, NodeId, ROOT_NODE_ID},
+    file_type::FileType,
+    streaming::StreamingPair,
+    variant::{AssetVariant, ChildGroup, group_variant_children, primary_variant, variant_sizes},
+};
 
 #[derive(Debug, Clone, Default, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -35,13 +43,27 @@ pub struct NodeInfo {
     /// Belonging to which pak.
     /// If node is a directory, it will be None.
     pub belongs_to: Option<PakId>,
+    /// Whether this entry is overridden by a newer pak.
+    pub is_overridden: bool,
+    /// Type detected from the magic, only for entries missing from the file list.
+    pub file_type: Option<FileType>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RenderTreeOptions {
+    /// (legacy) Use `sort_key` instead.
     sort_by_name: Option<bool>,
+    /// (legacy) Use `sort_key` instead.
     sort_by_size: Option<bool>,
+    sort_key: Option<TreeSortKey>,
+    sort_order: Option<TreeSortOrder>,
+    directories_first: Option<bool>,
+    show_overridden: Option<bool>,
+    mode: Option<TreeMode>,
+    /// Paks to render in per-pak mode, all paks if `None`.
+    pak_ids: Option<Vec<PakId>>,
+    group_variants: Option<bool>,
 }
 
 impl Default for RenderTreeOptions {
@@ -49,10 +71,53 @@ impl Default for RenderTreeOptions {
         Self {
             sort_by_name: Some(true),
             sort_by_size: Some(false),
+            sort_key: None,
+            sort_order: None,
+            directories_first: Some(true),
+            show_overridden: Some(false),
+            mode: None,
+            pak_ids: None,
+            group_variants: Some(false),
         }
     }
 }
 
+/// How loaded paks are combined into a tree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TreeMode {
+    /// One merged tree, newer paks override older ones.
+    #[default]
+    Combined,
+    /// One root per pak, including entries shadowed by newer paks.
+    PerPak,
+}
+
+/// What to sort tree nodes by.
+///
+/// Directory sizes are the totals of their children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TreeSortKey {
+    Name,
+    UncompressedSize,
+    CompressedSize,
+    /// Compressed size / uncompressed size.
+    CompressionRatio,
+    /// File extension, ignoring version and platform suffixes.
+    Extension,
+    /// Load order of the owning pak.
+    Pak,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TreeSortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
 impl RenderTreeOptions {
     pub fn sort_by_name(&self) -> bool {
         self.sort_by_name.unwrap_or(true)
@@ -61,6 +126,62 @@ impl RenderTreeOptions {
     pub fn sort_by_size(&self) -> bool {
         self.sort_by_size.unwrap_or(false)
     }
+
+    /// Sort key, falling back to the legacy flags.
+    ///
+    /// Returns `None` if sorting is disabled.
+    pub fn sort_key(&self) -> Option<TreeSortKey> {
+        if let Some(sort_key) = self.sort_key {
+            return Some(sort_key);
+        }
+
+        if self.sort_by_name() {
+            Some(TreeSortKey::Name)
+        } else if self.sort_by_size() {
+            Some(TreeSortKey::UncompressedSize)
+        } else {
+            None
+        }
+    }
+
+    pub fn sort_order(&self) -> TreeSortOrder {
+        self.sort_order.unwrap_or_default()
+    }
+
+    pub fn directories_first(&self) -> bool {
+        self.directories_first.unwrap_or(true)
+    }
+
+    /// Include entries overridden by newer paks, marked with `is_overridden`.
+    pub fn show_overridden(&self) -> bool {
+        self.show_overridden.unwrap_or(false)
+    }
+
+    pub fn mode(&self) -> TreeMode {
+        self.mode.unwrap_or_default()
+    }
+
+    pub fn pak_ids(&self) -> Option<&[PakId]> {
+        self.pak_ids.as_deref()
+    }
+
+    /// Collapse language and platform variants of a file into one node.
+    pub fn group_variants(&self) -> bool {
+        self.group_variants.unwrap_or(false)
+    }
+}
+
+/// Contents of a single pak, including entries shadowed by newer paks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PakTreeStats {
+    pub file_count: u64,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    /// Entries shadowed by newer paks.
+    pub overridden_count: u64,
+    pub overridden_compressed_size: u64,
+    pub overridden_uncompressed_size: u64,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -78,126 +199,466 @@ pub struct RenderTreeNode {
     pub uncompressed_size: u64,
     /// 节点所属的 Pak
     pub belongs_to: Option<PakId>,
+    /// 是否被更新的 Pak 覆盖
+    pub is_overridden: bool,
+    /// 根据文件头识别的类型，仅文件列表中没有的条目有
+    pub file_type: Option<FileType>,
+    /// Pak 统计信息，仅按 Pak 分组模式下的根节点有
+    pub pak_stats: Option<PakTreeStats>,
+    /// 合并的语言和平台变体，仅开启 `group_variants` 时有
+    pub variants: Option<Vec<AssetVariant>>,
+    /// 对应的 streaming 文件
+    pub streaming: Option<StreamingPair>,
     /// 子节点
     pub children: Vec<RenderTreeNode>,
 }
 
 impl RenderTreeNode {
-    pub fn try_from_file_tree(
-        file_tree: FileTree,
+    /// Convert an arena tree into render nodes.
+    ///
+    /// `pak_order` is the load order of paks, used by [`TreeSortKey::Pak`].
+    pub fn from_arena_tree(
+        tree: &ArenaTree,
         options: &RenderTreeOptions,
-    ) -> Result<Vec<Self>> {
-        let mut roots = file_tree
-            .roots
-            .iter()
-            .map(convert_to_render_node)
-            .collect::<Vec<_>>();
+        pak_order: &[PakId],
+    ) -> Vec<Self> {
+        let mut roots = convert_children(
+            tree,
+            ROOT_NODE_ID,
+            options.show_overridden(),
+            options.group_variants(),
+        );
 
-        if options.sort_by_name() {
-            sort_by_name(&mut roots);
-        } else if options.sort_by_size() {
-            // TODO
+        if let Some(sorter) = TreeSorter::new(options, pak_order) {
+            sorter.sort(&mut roots);
         }
 
-        for root in &mut roots {
-            apply_dir_size(root);
-        }
+        roots
+    }
 
-        Ok(roots)
+    /// Convert trees of single paks into one root per pak, kept in load order.
+    ///
+    /// Root names are pak paths, shadowed entries are always included.
+    pub fn from_pak_trees(
+        trees: &[(PakInfo, ArenaTree)],
+        options: &RenderTreeOptions,
+        pak_order: &[PakId],
+    ) -> Vec<Self> {
+        let sorter = TreeSorter::new(options, pak_order);
+        trees
+            .iter()
+            .map(|(pak_info, tree)| {
+                let root = tree.node(ROOT_NODE_ID).expect("tree always has a root");
+                let mut children =
+                    convert_children(tree, ROOT_NODE_ID, true, options.group_variants());
+                if let Some(sorter) = &sorter {
+                    sorter.sort(&mut children);
+                }
+
+                RenderTreeNode {
+                    is_dir: true,
+                    name: SmolStr::new(&pak_info.path),
+                    hash: None,
+                    compressed_size: root.compressed_size,
+                    uncompressed_size: root.uncompressed_size,
+                    belongs_to: Some(pak_info.id),
+                    is_overridden: false,
+                    file_type: None,
+                    pak_stats: Some(tree.pak_stats()),
+                    variants: None,
+                    streaming: None,
+                    children,
+                }
+            })
+            .collect()
     }
 }
 
-fn convert_to_render_node(node: &FileTreeNode) -> RenderTreeNode {
-    let info = &node.info;
+fn convert_children(
+    tree: &ArenaTree,
+    id: NodeId,
+    show_overridden: bool,
+    group_variants: bool,
+) -> Vec<RenderTreeNode> {
+    let children = tree.visible_children(id, show_overridden);
+    if !group_variants {
+        return children
+            .map(|child_id| convert_to_render_node(tree, child_id, show_overridden, false))
+            .collect();
+    }
+
+    group_variant_children(tree, children)
+        .into_iter()
+        .map(|group| match group {
+            ChildGroup::Node(child_id) => {
+                convert_to_render_node(tree, child_id, show_overridden, true)
+            }
+            ChildGroup::Variants { name, variants, .. } => {
+                RenderTreeNode::from_variants(name, variants)
+            }
+        })
+        .collect()
+}
+
+fn convert_to_render_node(
+    tree: &ArenaTree,
+    id: NodeId,
+    show_overridden: bool,
+    group_variants: bool,
+) -> RenderTreeNode {
+    let node = tree.node(id).expect("node id comes from the tree");
 
     RenderTreeNode {
-        is_dir: info.is_dir,
-        name: info.relative_path.clone(),
-        hash: info.hash,
-        compressed_size: info.compressed_size,
-        uncompressed_size: info.uncompressed_size,
-        belongs_to: info.belongs_to,
-        children: node.children.values().map(convert_to_render_node).collect(),
+        is_dir: node.is_dir,
+        name: tree.name(id).clone(),
+        hash: node.hash.map(JsSafeHash::from_u64),
+        compressed_size: node.compressed_size,
+        uncompressed_size: node.uncompressed_size,
+        belongs_to: node.belongs_to,
+        is_overridden: node.is_overridden,
+        file_type: node.file_type,
+        pak_stats: None,
+        variants: None,
+        streaming: tree.streaming_pair(id),
+        children: convert_children(tree, id, show_overridden, group_variants),
     }
 }
 
-fn apply_dir_size(node: &mut RenderTreeNode) {
-    if node.is_dir {
-        let mut total_compressed_size = 0;
-        let mut total_uncompressed_size = 0;
+impl RenderTreeNode {
+    /// A file node standing for all variants of an asset.
+    ///
+    /// Hash and pak are taken from the primary variant, sizes are totals of active variants.
+    fn from_variants(name: SmolStr, variants: Vec<AssetVariant>) -> Self {
+        let primary = primary_variant(&variants);
+        let (compressed_size, uncompressed_size) = variant_sizes(&variants, primary);
 
-        for child in &mut node.children {
-            apply_dir_size(child);
-            total_compressed_size += child.compressed_size;
-            total_uncompressed_size += child.uncompressed_size;
+        RenderTreeNode {
+            is_dir: false,
+            name,
+            hash: variants[primary].hash,
+            compressed_size,
+            uncompressed_size,
+            belongs_to: variants[primary].belongs_to,
+            is_overridden: variants[primary].is_overridden,
+            file_type: None,
+            pak_stats: None,
+            children: vec![],
+            variants: Some(variants),
+            streaming: None,
         }
+    }
+}
+
+/// Node fields used by [`TreeSorter`].
+pub(super) trait SortableNode {
+    fn is_dir(&self) -> bool;
+    fn name(&self) -> &str;
+    fn compressed_size(&self) -> u64;
+    fn uncompressed_size(&self) -> u64;
+    fn belongs_to(&self) -> Option<PakId>;
+}
+
+impl SortableNode for RenderTreeNode {
+    fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
 
-        node.compressed_size = total_compressed_size;
-        node.uncompressed_size = total_uncompressed_size;
+    fn compressed_size(&self) -> u64 {
+        self.compressed_size
+    }
+
+    fn uncompressed_size(&self) -> u64 {
+        self.uncompressed_size
+    }
+
+    fn belongs_to(&self) -> Option<PakId> {
+        self.belongs_to
     }
 }
 
-fn sort_by_name(nodes: &mut [RenderTreeNode]) {
-    nodes.sort_by(|a, b| match (a.is_dir, b.is_dir) {
-        (true, false) => std::cmp::Ordering::Less,
-        (false, true) => std::cmp::Ordering::Greater,
-        _ => a.name.cmp(&b.name),
-    });
-    for node in nodes {
-        sort_by_name(&mut node.children);
+pub(super) struct TreeSorter<'a> {
+    key: TreeSortKey,
+    order: TreeSortOrder,
+    directories_first: bool,
+    pak_order: &'a [PakId],
+}
+
+impl<'a> TreeSorter<'a> {
+    /// Returns `None` if sorting is disabled.
+    pub(super) fn new(options: &RenderTreeOptions, pak_order: &'a [PakId]) -> Option<Self> {
+        options.sort_key().map(|key| Self {
+            key,
+            order: options.sort_order(),
+            directories_first: options.directories_first(),
+            pak_order,
+        })
     }
+
+    fn sort(&self, nodes: &mut [RenderTreeNode]) {
+        nodes.sort_by(|a, b| self.compare(a, b));
+        for node in nodes {
+            self.sort(&mut node.children);
+        }
+    }
+
+    pub(super) fn compare<N: SortableNode>(&self, a: &N, b: &N) -> Ordering {
+        if self.directories_first {
+            match (a.is_dir(), b.is_dir()) {
+                (true, false) => return Ordering::Less,
+                (false, true) => return Ordering::Greater,
+                _ => {}
+            }
+        }
+
+        let ordering = self.compare_key(a, b).then_with(|| a.name().cmp(b.name()));
+        match self.order {
+            TreeSortOrder::Ascending => ordering,
+            TreeSortOrder::Descending => ordering.reverse(),
+        }
+    }
+
+    fn compare_key<N: SortableNode>(&self, a: &N, b: &N) -> Ordering {
+        match self.key {
+            TreeSortKey::Name => Ordering::Equal,
+            TreeSortKey::UncompressedSize => a.uncompressed_size().cmp(&b.uncompressed_size()),
+            TreeSortKey::CompressedSize => a.compressed_size().cmp(&b.compressed_size()),
+            TreeSortKey::CompressionRatio => compression_ratio(a).total_cmp(&compression_ratio(b)),
+            TreeSortKey::Extension => node_extension(a).cmp(&node_extension(b)),
+            TreeSortKey::Pak => self.pak_index(a).cmp(&self.pak_index(b)),
+        }
+    }
+
+    /// Directories have no owning pak and sort before files.
+    fn pak_index<N: SortableNode>(&self, node: &N) -> Option<usize> {
+        node.belongs_to()
+            .and_then(|pak_id| self.pak_order.iter().position(|id| *id == pak_id))
+    }
+}
+
+fn compression_ratio<N: SortableNode>(node: &N) -> f64 {
+    if node.uncompressed_size() == 0 {
+        return 1.0;
+    }
+    node.compressed_size() as f64 / node.uncompressed_size() as f64
+}
+
+fn node_extension<N: SortableNode>(node: &N) -> Option<String> {
+    if node.is_dir() {
+        return None;
+    }
+    PathComponents::parse(node.name())
+        .and_then(|components| components.extension().map(str::to_ascii_lowercase))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::pak::test_util::{compressed_entry, entry, new_pak_id};
 
-    fn directory(name: &str, children: Vec<FileTreeNode>) -> FileTreeNode {
-        FileTreeNode {
-            info: NodeInfo {
-                is_dir: true,
-                relative_path: SmolStr::new(name),
-                ..NodeInfo::default()
-            },
-            children: children
-                .into_iter()
-                .map(|child| (child.info.relative_path.clone(), child))
-                .collect(),
-        }
+    fn tree_from_paths(paths: &[(&str, u64)]) -> ArenaTree {
+        let pak = new_pak_id();
+        let part = ArenaTree::from_entries(
+            paths
+                .iter()
+                .enumerate()
+                .map(|(i, (path, size))| (*path, entry(i as u64, *size, pak))),
+        );
+        ArenaTree::merge(vec![part])
+    }
+
+    fn sorted_names(nodes: &[RenderTreeNode]) -> Vec<&str> {
+        nodes.iter().map(|node| node.name.as_str()).collect()
     }
 
-    fn file(name: &str, size: u64) -> FileTreeNode {
-        FileTreeNode {
-            info: NodeInfo {
-                is_dir: false,
-                relative_path: SmolStr::new(name),
-                compressed_size: size,
-                uncompressed_size: size,
-                ..NodeInfo::default()
-            },
-            children: HashMap::new(),
+    fn options(
+        key: TreeSortKey,
+        order: TreeSortOrder,
+        directories_first: bool,
+    ) -> RenderTreeOptions {
+        RenderTreeOptions {
+            sort_key: Some(key),
+            sort_order: Some(order),
+            directories_first: Some(directories_first),
+            ..RenderTreeOptions::default()
         }
     }
 
     #[test]
     fn render_tree_keeps_real_directory_chain() {
-        let tree = FileTree {
-            roots: vec![directory(
-                "A",
-                vec![directory(
-                    "B",
-                    vec![directory("C", vec![file("x.tex", 12)])],
-                )],
-            )],
-            ..FileTree::default()
-        };
+        let tree = tree_from_paths(&[("A/B/C/x.tex", 12)]);
 
-        let roots = RenderTreeNode::try_from_file_tree(tree, &RenderTreeOptions::default())
-            .expect("tree should render");
+        let roots = RenderTreeNode::from_arena_tree(&tree, &RenderTreeOptions::default(), &[]);
 
         assert_eq!(roots[0].name, "A");
         assert_eq!(roots[0].children[0].name, "B");
         assert_eq!(roots[0].children[0].children[0].name, "C");
         assert_eq!(roots[0].children[0].children[0].children[0].name, "x.tex");
+        assert_eq!(roots[0].uncompressed_size, 12);
+    }
+
+    #[test]
+    fn render_tree_excludes_overridden_entries_from_dir_size() {
+        let parts = vec![
+            ArenaTree::from_entries([("A/x.tex", entry(1, 100, new_pak_id()))]),
+            ArenaTree::from_entries([("A/x.tex", entry(1, 12, new_pak_id()))]),
+        ];
+        let tree = ArenaTree::merge(parts);
+        let options = RenderTreeOptions {
+            show_overridden: Some(true),
+            ..RenderTreeOptions::default()
+        };
+
+        let roots = RenderTreeNode::from_arena_tree(&tree, &options, &[]);
+
+        assert_eq!(roots[0].children.len(), 2);
+        assert_eq!(roots[0].compressed_size, 12);
+        assert_eq!(roots[0].uncompressed_size, 12);
+    }
+
+    #[test]
+    fn render_tree_sorts_by_size_using_directory_totals() {
+        let tree = tree_from_paths(&[
+            ("big/a.tex", 60),
+            ("big/b.tex", 60),
+            ("medium.tex", 100),
+            ("small/c.tex", 1),
+        ]);
+
+        let roots = RenderTreeNode::from_arena_tree(
+            &tree,
+            &options(
+                TreeSortKey::UncompressedSize,
+                TreeSortOrder::Descending,
+                false,
+            ),
+            &[],
+        );
+
+        assert_eq!(sorted_names(&roots), ["big", "medium.tex", "small"]);
+        assert_eq!(roots[0].uncompressed_size, 120);
+    }
+
+    #[test]
+    fn render_tree_keeps_directories_first_when_sorting_by_size() {
+        let tree = tree_from_paths(&[("huge.tex", 1000), ("dir/c.tex", 1)]);
+
+        let roots = RenderTreeNode::from_arena_tree(
+            &tree,
+            &options(
+                TreeSortKey::UncompressedSize,
+                TreeSortOrder::Descending,
+                true,
+            ),
+            &[],
+        );
+
+        assert_eq!(sorted_names(&roots), ["dir", "huge.tex"]);
+    }
+
+    #[test]
+    fn render_tree_sorts_by_extension_ignoring_version_suffix() {
+        let tree = tree_from_paths(&[
+            ("b.tex.241106027", 1),
+            ("a.mesh.2109148288", 1),
+            ("c.mdf2.40", 1),
+        ]);
+
+        let roots = RenderTreeNode::from_arena_tree(
+            &tree,
+            &options(TreeSortKey::Extension, TreeSortOrder::Ascending, true),
+            &[],
+        );
+
+        assert_eq!(
+            sorted_names(&roots),
+            ["c.mdf2.40", "a.mesh.2109148288", "b.tex.241106027"]
+        );
+    }
+
+    #[test]
+    fn render_tree_sorts_by_compression_ratio_recursively() {
+        let pak = new_pak_id();
+        let part = ArenaTree::from_entries([
+            ("A/raw.tex", entry(1, 100, pak)),
+            ("A/packed.tex", compressed_entry(2, 10, 100, pak)),
+        ]);
+        let tree = ArenaTree::merge(vec![part]);
+
+        let roots = RenderTreeNode::from_arena_tree(
+            &tree,
+            &options(
+                TreeSortKey::CompressionRatio,
+                TreeSortOrder::Ascending,
+                true,
+            ),
+            &[],
+        );
+
+        assert_eq!(sorted_names(&roots[0].children), ["packed.tex", "raw.tex"]);
+    }
+
+    #[test]
+    fn render_tree_sorts_by_pak_load_order() {
+        let first = new_pak_id();
+        let second = new_pak_id();
+        let parts = vec![
+            ArenaTree::from_entries([("b.tex", entry(2, 1, first))]),
+            ArenaTree::from_entries([("a.tex", entry(1, 1, second))]),
+        ];
+        let tree = ArenaTree::merge(parts);
+
+        let roots = RenderTreeNode::from_arena_tree(
+            &tree,
+            &options(TreeSortKey::Pak, TreeSortOrder::Ascending, true),
+            &[first, second],
+        );
+
+        assert_eq!(sorted_names(&roots), ["b.tex", "a.tex"]);
+    }
+
+    #[test]
+    fn render_pak_trees_keep_shadowed_entries_and_stats() {
+        let old = PakInfo {
+            id: new_pak_id(),
+            path: "re_chunk_000.pak".to_string(),
+            archive_path: None,
+        };
+        let new = PakInfo {
+            id: new_pak_id(),
+            path: "re_chunk_000.pak.patch_001.pak".to_string(),
+            archive_path: None,
+        };
+        let shadowed = compressed_entry(1, 40, 100, old.id);
+        let kept = entry(2, 5, old.id);
+        let old_tree = ArenaTree::from_entries([("A/x.tex", shadowed), ("A/y.tex", kept)])
+            .finish_single(|hash| hash == 1);
+
+        let roots = RenderTreeNode::from_pak_trees(
+            &[(old.clone(), old_tree)],
+            &RenderTreeOptions::default(),
+            &[old.id, new.id],
+        );
+
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].name, "re_chunk_000.pak");
+        assert_eq!(roots[0].belongs_to, Some(old.id));
+        assert_eq!(roots[0].children[0].children.len(), 2);
+        assert!(roots[0].children[0].children[0].is_overridden);
+        assert_eq!(roots[0].uncompressed_size, 5);
+        assert_eq!(
+            roots[0].pak_stats,
+            Some(PakTreeStats {
+                file_count: 2,
+                compressed_size: 45,
+                uncompressed_size: 105,
+                overridden_count: 1,
+                overridden_compressed_size: 40,
+                overridden_uncompressed_size: 100,
+            })
+        );
     }
 }
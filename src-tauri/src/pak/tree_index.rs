@@ -0,0 +1,342 @@
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+use crate::common::JsSafeHash;
+use crate::error::{Error, Result};
+
+use super::{
+    PakId,
+    arena_tree::{ArenaTree, NodeId},
+    file_type::FileType,
+    streaming::StreamingPair,
+    tree::{RenderTreeOptions, SortableNode, TreeSorter},
+    variant::{AssetVariant, ChildGroup, group_variant_children, primary_variant, variant_sizes},
+};
+
+/// Id of a node in a [`TreeIndex`], only valid for the generation it was read from.
+pub type TreeNodeId = NodeId;
+
+pub use super::arena_tree::ROOT_NODE_ID;
+
+const DEFAULT_PAGE_SIZE: usize = 500;
+
+/// Indexed file tree, used to read directories on demand.
+///
+/// Directory sizes are aggregated once on build.
+#[derive(Debug, Clone)]
+pub struct TreeIndex {
+    generation: u64,
+    tree: ArenaTree,
+}
+
+/// Query for a page of directory children.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeChildrenQuery {
+    /// Directory to read, the root if `None`.
+    pub node_id: Option<TreeNodeId>,
+    /// Generation the node id was read from.
+    ///
+    /// If set and the tree has been rebuilt since, the query fails.
+    pub generation: Option<u64>,
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+    /// Sort and filter options, the indexed tree is always combined.
+    pub options: Option<RenderTreeOptions>,
+}
+
+/// A page of directory children.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeChildrenPage {
+    pub generation: u64,
+    pub parent: LazyTreeNode,
+    pub children: Vec<LazyTreeNode>,
+    pub offset: usize,
+    /// Total number of visible children.
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LazyTreeNode {
+    pub id: TreeNodeId,
+    pub is_dir: bool,
+    pub name: SmolStr,
+    pub hash: Option<JsSafeHash>,
+    /// 节点大小或目录所有子节点压缩后大小
+    pub compressed_size: u64,
+    /// 节点大小或目录所有子节点压缩前大小
+    pub uncompressed_size: u64,
+    pub is_compressed: bool,
+    pub belongs_to: Option<PakId>,
+    pub is_overridden: bool,
+    /// Type detected from the magic, only for entries missing from the file list.
+    pub file_type: Option<FileType>,
+    /// Number of visible children, 0 for files.
+    pub child_count: usize,
+    /// Collapsed language and platform variants, if `group_variants` is set.
+    pub variants: Option<Vec<AssetVariant>>,
+    /// Streaming counterpart of a natives file.
+    pub streaming: Option<StreamingPair>,
+}
+
+impl TreeIndex {
+    /// Overridden entries are filtered on read.
+    pub fn new(tree: ArenaTree, generation: u64) -> Self {
+        Self { generation, tree }
+    }
+
+    /// Read a page of directory children, sorted by options.
+    pub fn read_children(
+        &self,
+        node_id: TreeNodeId,
+        options: &RenderTreeOptions,
+        pak_order: &[PakId],
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<TreeChildrenPage> {
+        self.tree
+            .node(node_id)
+            .filter(|node| node.is_dir)
+            .ok_or(Error::TreeNodeNotFound(node_id))?;
+
+        let show_overridden = options.show_overridden();
+        if options.group_variants() {
+            return Ok(self.read_grouped_children(node_id, options, pak_order, offset, limit));
+        }
+
+        let mut children = self
+            .tree
+            .visible_children(node_id, show_overridden)
+            .collect::<Vec<_>>();
+        if let Some(sorter) = TreeSorter::new(options, pak_order) {
+            children
+                .sort_by(|a, b| sorter.compare(&self.tree.node_ref(*a), &self.tree.node_ref(*b)));
+        }
+
+        let total = children.len();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        let page = children
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|child_id| self.lazy_node(child_id, show_overridden))
+            .collect();
+
+        Ok(TreeChildrenPage {
+            generation: self.generation,
+            parent: self.lazy_node(node_id, show_overridden),
+            children: page,
+            offset,
+            total,
+        })
+    }
+
+    /// Read children with variants collapsed.
+    ///
+    /// Groups can only be sorted after they are built, so the whole directory is converted.
+    fn read_grouped_children(
+        &self,
+        node_id: TreeNodeId,
+        options: &RenderTreeOptions,
+        pak_order: &[PakId],
+        offset: usize,
+        limit: Option<usize>,
+    ) -> TreeChildrenPage {
+        let show_overridden = options.show_overridden();
+        let mut children = group_variant_children(
+            &self.tree,
+            self.tree.visible_children(node_id, show_overridden),
+        )
+        .into_iter()
+        .map(|group| match group {
+            ChildGroup::Node(id) => self.lazy_node(id, show_overridden),
+            ChildGroup::Variants {
+                name,
+                ids,
+                variants,
+            } => {
+                let primary = primary_variant(&variants);
+                let (compressed_size, uncompressed_size) = variant_sizes(&variants, primary);
+                LazyTreeNode {
+                    id: ids[primary],
+                    is_dir: false,
+                    name,
+                    hash: variants[primary].hash,
+                    compressed_size,
+                    uncompressed_size,
+                    is_compressed: variants[primary].is_compressed,
+                    belongs_to: variants[primary].belongs_to,
+                    is_overridden: variants[primary].is_overridden,
+                    file_type: None,
+                    child_count: 0,
+                    variants: Some(variants),
+                    streaming: None,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+        if let Some(sorter) = TreeSorter::new(options, pak_order) {
+            children.sort_by(|a, b| sorter.compare(a, b));
+        }
+
+        let total = children.len();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        TreeChildrenPage {
+            generation: self.generation,
+            parent: self.lazy_node(node_id, show_overridden),
+            children: children.into_iter().skip(offset).take(limit).collect(),
+            offset,
+            total,
+        }
+    }
+
+    fn lazy_node(&self, id: TreeNodeId, show_overridden: bool) -> LazyTreeNode {
+        let node = self.tree.node(id).expect("node id comes from the tree");
+        LazyTreeNode {
+            id,
+            is_dir: node.is_dir,
+            name: self.tree.name(id).clone(),
+            hash: node.hash.map(JsSafeHash::from_u64),
+            compressed_size: node.compressed_size,
+            uncompressed_size: node.uncompressed_size,
+            is_compressed: node.is_compressed,
+            belongs_to: node.belongs_to,
+            is_overridden: node.is_overridden,
+            file_type: node.file_type,
+            child_count: self.tree.visible_children(id, show_overridden).count(),
+            variants: None,
+            streaming: self.tree.streaming_pair(id),
+        }
+    }
+}
+
+impl SortableNode for LazyTreeNode {
+    fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compressed_size(&self) -> u64 {
+        self.compressed_size
+    }
+
+    fn uncompressed_size(&self) -> u64 {
+        self.uncompressed_size
+    }
+
+    fn belongs_to(&self) -> Option<PakId> {
+        self.belongs_to
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pak::test_util::{entry, new_pak_id};
+
+    fn index_from_paths(paths: &[(&str, u64)]) -> TreeIndex {
+        let pak = new_pak_id();
+        let part = ArenaTree::from_entries(
+            paths
+                .iter()
+                .enumerate()
+                .map(|(i, (path, size))| (*path, entry(i as u64, *size, pak))),
+        );
+        TreeIndex::new(ArenaTree::merge(vec![part]), 0)
+    }
+
+    fn names(nodes: &[LazyTreeNode]) -> Vec<&str> {
+        nodes.iter().map(|node| node.name.as_str()).collect()
+    }
+
+    #[test]
+    fn tree_index_reads_directories_on_demand() {
+        let index = index_from_paths(&[("natives/stm/a.tex", 3), ("root.txt", 1)]);
+        let options = RenderTreeOptions::default();
+
+        let root = index
+            .read_children(ROOT_NODE_ID, &options, &[], 0, None)
+            .unwrap();
+        assert_eq!(root.parent.uncompressed_size, 4);
+        assert_eq!(root.total, 2);
+        assert_eq!(names(&root.children), ["natives", "root.txt"]);
+        assert_eq!(root.children[0].child_count, 1);
+        assert_eq!(root.children[0].uncompressed_size, 3);
+
+        let natives = index
+            .read_children(root.children[0].id, &options, &[], 0, None)
+            .unwrap();
+        assert_eq!(names(&natives.children), ["stm"]);
+
+        let file_id = index
+            .read_children(natives.children[0].id, &options, &[], 0, None)
+            .unwrap()
+            .children[0]
+            .id;
+        assert!(matches!(
+            index.read_children(file_id, &options, &[], 0, None),
+            Err(Error::TreeNodeNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn tree_index_pages_large_directories() {
+        let paths = (0..10)
+            .map(|i| format!("big/{i:02}.tex"))
+            .collect::<Vec<_>>();
+        let index = index_from_paths(
+            &paths
+                .iter()
+                .map(|path| (path.as_str(), 1))
+                .collect::<Vec<_>>(),
+        );
+        let options = RenderTreeOptions::default();
+        let big = index
+            .read_children(ROOT_NODE_ID, &options, &[], 0, None)
+            .unwrap()
+            .children[0]
+            .id;
+
+        let page = index.read_children(big, &options, &[], 4, Some(3)).unwrap();
+        assert_eq!(page.total, 10);
+        assert_eq!(page.offset, 4);
+        assert_eq!(names(&page.children), ["04.tex", "05.tex", "06.tex"]);
+    }
+
+    #[test]
+    fn tree_index_hides_overridden_entries_by_default() {
+        let old = new_pak_id();
+        let new = new_pak_id();
+        let parts = vec![
+            ArenaTree::from_entries([("A/a.tex", entry(1, 100, old))]),
+            ArenaTree::from_entries([("A/a.tex", entry(1, 1, new))]),
+        ];
+        let index = TreeIndex::new(ArenaTree::merge(parts), 0);
+
+        let roots = index
+            .read_children(ROOT_NODE_ID, &RenderTreeOptions::default(), &[], 0, None)
+            .unwrap()
+            .children;
+        assert_eq!(roots[0].child_count, 1);
+        assert_eq!(roots[0].uncompressed_size, 1);
+
+        let options: RenderTreeOptions =
+            serde_json::from_str(r#"{"showOverridden": true}"#).unwrap();
+        let page = index
+            .read_children(roots[0].id, &options, &[], 0, None)
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(
+            page.children
+                .iter()
+                .filter(|node| node.is_overridden)
+                .count(),
+            1
+        );
+    }
+}
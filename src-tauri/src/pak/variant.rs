@@ -0,0 +1,244 @@
+use indexmap::IndexMap;
+use serde::Serialize;
+use smol_str::SmolStr;
+
+use crate::common::JsSafeHash;
+use crate::path_components::PathComponents;
+
+use super::{
+    PakId,
+    arena_tree::{ArenaTree, NodeId},
+};
+
+/// A language or platform variant of a logical asset.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetVariant {
+    /// Full file name of the variant, e.g. `a.msg.23.En`.
+    pub name: SmolStr,
+    pub language: Option<SmolStr>,
+    pub platform: Option<SmolStr>,
+    pub hash: Option<JsSafeHash>,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub is_compressed: bool,
+    pub belongs_to: Option<PakId>,
+    pub is_overridden: bool,
+}
+
+/// Languages and platforms found in loaded paks, sorted by tag.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VariantSummary {
+    pub languages: Vec<VariantTagCount>,
+    pub platforms: Vec<VariantTagCount>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VariantTagCount {
+    pub tag: String,
+    pub file_count: u64,
+}
+
+/// Directory children after collapsing variants.
+pub(super) enum ChildGroup {
+    Node(NodeId),
+    /// Files sharing a name without language and platform tags.
+    Variants {
+        name: SmolStr,
+        /// Node ids of `variants`, in the same order.
+        ids: Vec<NodeId>,
+        variants: Vec<AssetVariant>,
+    },
+}
+
+/// Index of the representative variant of a group, the first active one.
+pub(super) fn primary_variant(variants: &[AssetVariant]) -> usize {
+    variants
+        .iter()
+        .position(|variant| !variant.is_overridden)
+        .unwrap_or(0)
+}
+
+/// Total (compressed, uncompressed) size of variants in the same state as the primary one.
+pub(super) fn variant_sizes(variants: &[AssetVariant], primary: usize) -> (u64, u64) {
+    variants
+        .iter()
+        .filter(|variant| variant.is_overridden == variants[primary].is_overridden)
+        .fold((0, 0), |(compressed, uncompressed), variant| {
+            (
+                compressed + variant.compressed_size,
+                uncompressed + variant.uncompressed_size,
+            )
+        })
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum GroupKey {
+    Node(NodeId),
+    VariantBase(SmolStr),
+}
+
+/// Collapse tagged file children into one group per logical asset.
+///
+/// Directories, untagged files and assets with a single variant are kept as they are,
+/// in the original order.
+pub(super) fn group_variant_children(
+    tree: &ArenaTree,
+    children: impl Iterator<Item = NodeId>,
+) -> Vec<ChildGroup> {
+    let mut groups: IndexMap<GroupKey, Vec<(NodeId, PathComponents)>> = IndexMap::new();
+    for id in children {
+        let node = tree.node(id).expect("node id comes from the tree");
+        let name = tree.name(id);
+        let components = PathComponents::parse(name)
+            .filter(|components| !node.is_dir && components.variant_base() != name.as_str());
+        match components {
+            Some(components) => groups
+                .entry(GroupKey::VariantBase(SmolStr::new(
+                    components.variant_base(),
+                )))
+                .or_default()
+                .push((id, components)),
+            None => {
+                groups.insert(GroupKey::Node(id), vec![]);
+            }
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(key, mut members)| {
+            let base = match key {
+                GroupKey::Node(id) => return ChildGroup::Node(id),
+                GroupKey::VariantBase(base) => base,
+            };
+            if let [(id, _)] = members.as_slice() {
+                return ChildGroup::Node(*id);
+            }
+
+            members.sort_by(|(a, _), (b, _)| tree.name(*a).cmp(tree.name(*b)));
+            let ids = members.iter().map(|(id, _)| *id).collect();
+            let variants = members
+                .into_iter()
+                .map(|(id, components)| {
+                    let node = tree.node(id).expect("node id comes from the tree");
+                    AssetVariant {
+                        name: tree.name(id).clone(),
+                        language: components.language().map(SmolStr::new),
+                        platform: components.platform().map(SmolStr::new),
+                        hash: node.hash.map(JsSafeHash::from_u64),
+                        compressed_size: node.compressed_size,
+                        uncompressed_size: node.uncompressed_size,
+                        is_compressed: node.is_compressed,
+                        belongs_to: node.belongs_to,
+                        is_overridden: node.is_overridden,
+                    }
+                })
+                .collect();
+            ChildGroup::Variants {
+                name: base,
+                ids,
+                variants,
+            }
+        })
+        .collect()
+}
+
+/// Count files per language and platform tag.
+#[derive(Default)]
+pub(super) struct VariantCollector {
+    languages: IndexMap<String, u64>,
+    platforms: IndexMap<String, u64>,
+}
+
+impl VariantCollector {
+    pub(super) fn add_path(&mut self, path: &str) {
+        let Some(components) = PathComponents::parse(path) else {
+            return;
+        };
+        if let Some(language) = components.language() {
+            *self.languages.entry(language.to_string()).or_default() += 1;
+        }
+        if let Some(platform) = components.platform() {
+            *self
+                .platforms
+                .entry(platform.to_ascii_uppercase())
+                .or_default() += 1;
+        }
+    }
+
+    pub(super) fn finish(self) -> VariantSummary {
+        let into_counts = |tags: IndexMap<String, u64>| {
+            let mut counts = tags
+                .into_iter()
+                .map(|(tag, file_count)| VariantTagCount { tag, file_count })
+                .collect::<Vec<_>>();
+            counts.sort_by(|a, b| a.tag.cmp(&b.tag));
+            counts
+        };
+
+        VariantSummary {
+            languages: into_counts(self.languages),
+            platforms: into_counts(self.platforms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pak::{
+        arena_tree::ROOT_NODE_ID,
+        test_util::{entry, new_pak_id},
+    };
+
+    #[test]
+    fn group_variant_children_collapses_language_variants() {
+        let pak = new_pak_id();
+        let paths = [
+            "gui/a.msg.23.Ja",
+            "gui/a.msg.23.En",
+            "gui/b.tex.143230",
+            "gui/sub/c.tex.143230",
+        ];
+        let part = ArenaTree::from_entries(
+            paths
+                .iter()
+                .enumerate()
+                .map(|(i, path)| (*path, entry(i as u64, 1, pak))),
+        );
+        let tree = ArenaTree::merge(vec![part]);
+        let gui = tree.children(ROOT_NODE_ID)[0];
+
+        let groups = group_variant_children(&tree, tree.visible_children(gui, false));
+
+        assert_eq!(groups.len(), 3);
+        let ChildGroup::Variants { name, variants, .. } = &groups[0] else {
+            panic!("expected variants");
+        };
+        assert_eq!(name, "a.msg.23");
+        let languages = variants
+            .iter()
+            .map(|variant| variant.language.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(languages, [Some("En"), Some("Ja")]);
+        assert!(matches!(groups[1], ChildGroup::Node(_)));
+    }
+
+    #[test]
+    fn variant_collector_counts_tags() {
+        let mut collector = VariantCollector::default();
+        collector.add_path("natives/STM/gui/a.msg.23.Ja");
+        collector.add_path("natives/STM/gui/a.msg.23.En");
+        collector.add_path("natives/NSW/b.tex.143230");
+
+        let summary = collector.finish();
+
+        assert_eq!(summary.languages.len(), 2);
+        assert_eq!(summary.languages[0].tag, "En");
+        assert_eq!(summary.platforms[1].tag, "STM");
+        assert_eq!(summary.platforms[1].file_count, 2);
+    }
+}
@@ -1,7 +1,7 @@
 use std::ops::Range;
 
 const PREFIXES: &[&str] = &["natives/STM/", "natives/MSG/", "natives/NSW/"];
-const LANGUAGES: &[&str] = &[
+pub(crate) const LANGUAGES: &[&str] = &[
     "Ja", "En", "Fr", "It", "De", "Es", "Ru", "Pl", "Nl", "Pt", "PtBR", "Ko", "ZhTW", "ZhCN", "Fi",
     "Sv", "Da", "No", "Cs", "Hu", "Sk", "Ar", "Tr", "Bu", "Gr", "Ro", "Th", "Uk", "Vi", "Id", "Fc",
     "Hi", "Es419",
@@ -38,8 +38,39 @@ impl PathComponents {
         &self.normalized_full[self.raw_path.clone()]
     }
 
-    #[cfg(test)]
+    /// Normalized full path, with `/` separators.
+    pub fn full_path(&self) -> &str {
+        &self.normalized_full
+    }
+
+    /// Version segment, e.g. `241106027` in `a.tex.241106027.X64`.
     pub fn version_str(&self) -> Option<&str> {
+        self.version_range()
+            .map(|range| &self.normalized_full[range])
+    }
+
+    /// Full path without version and tags.
+    ///
+    /// Example: `natives/STM/a.tex.241106027.X64` -> `natives/STM/a.tex`
+    pub fn base_path(&self) -> &str {
+        &self.normalized_full[..self.raw_path.end]
+    }
+
+    /// Full path without the version segment, tags are kept.
+    ///
+    /// Example: `a.tex.241106027.X64` -> `a.tex.X64`
+    pub fn without_version(&self) -> String {
+        match self.version_range() {
+            Some(range) => format!(
+                "{}{}",
+                &self.normalized_full[..range.start - 1],
+                &self.normalized_full[range.end..]
+            ),
+            None => self.normalized_full.clone(),
+        }
+    }
+
+    fn version_range(&self) -> Option<Range<usize>> {
         let dot = self.raw_path.end;
         if dot >= self.normalized_full.len() {
             return None;
@@ -54,8 +85,7 @@ impl PathComponents {
             .map(|rel| start + rel)
             .unwrap_or(self.normalized_full.len());
 
-        let seg = &self.normalized_full[start..end];
-        if is_digits(seg) { Some(seg) } else { None }
+        is_digits(&self.normalized_full[start..end]).then_some(start..end)
     }
 
     pub fn extension(&self) -> Option<&str> {
@@ -63,6 +93,44 @@ impl PathComponents {
         let dot = raw.rfind('.')?;
         Some(&raw[dot + 1..])
     }
+
+    /// Language tag of a localized variant, e.g. `En` in `a.msg.23.En`.
+    pub fn language(&self) -> Option<&str> {
+        self.suffix_segments().find(|seg| is_language_tag(seg))
+    }
+
+    /// Platform tag from the version suffix, or from the `natives/<platform>/` prefix.
+    pub fn platform(&self) -> Option<&str> {
+        self.suffix_segments()
+            .find(|seg| is_platform_tag(seg))
+            .or_else(|| {
+                PREFIXES
+                    .iter()
+                    .any(|prefix| starts_with_ignore_ascii_case(&self.normalized_full, prefix))
+                    .then(|| &self.normalized_full["natives/".len().."natives/STM".len()])
+            })
+    }
+
+    /// Path without trailing language, platform and arch tags.
+    ///
+    /// All variants of the same asset share this path, e.g. `a.msg.23.En` and `a.msg.23.Ja`.
+    pub fn variant_base(&self) -> &str {
+        let mut end = self.normalized_full.len();
+        while let Some((seg, dot)) = last_segment_range(&self.normalized_full, end) {
+            if dot < self.raw_path.end || !is_tag(&self.normalized_full[seg]) {
+                break;
+            }
+            end = dot;
+        }
+        &self.normalized_full[..end]
+    }
+
+    /// Version and tag segments after the raw path.
+    fn suffix_segments(&self) -> impl Iterator<Item = &str> {
+        self.normalized_full[self.raw_path.end..]
+            .split('.')
+            .filter(|seg| !seg.is_empty())
+    }
 }
 
 fn last_segment_range(s: &str, end: usize) -> Option<(Range<usize>, usize)> {
@@ -204,5 +272,21 @@ mod tests {
         assert_eq!(path.raw_path(), "foo.tex");
         assert_eq!(path.version_str(), Some("241106027"));
         assert_eq!(path.extension(), Some("tex"));
+        assert_eq!(path.without_version(), "foo.tex.X64");
+        assert_eq!(path.base_path(), "foo.tex");
+    }
+
+    #[test]
+    fn test_parse_variant_tags() {
+        let path = PathComponents::parse("natives/STM/gui/message/a.msg.23.X64.Ja").unwrap();
+
+        assert_eq!(path.language(), Some("Ja"));
+        assert_eq!(path.platform(), Some("STM"));
+        assert_eq!(path.variant_base(), "natives/STM/gui/message/a.msg.23");
+
+        let path = PathComponents::parse("a.tex.241106027.NSW").unwrap();
+        assert_eq!(path.language(), None);
+        assert_eq!(path.platform(), Some("NSW"));
+        assert_eq!(path.variant_base(), "a.tex.241106027");
     }
 }
@@ -0,0 +1,455 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+    thread::{self, JoinHandle},
+};
+
+use parking_lot::Mutex;
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use tauri::ipc::Channel;
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+use uuid::Uuid;
+
+use crate::{
+    channel::{PackProgressChannel, UnpackProgressChannel},
+    command::PackOptions,
+    common::JsSafeHash,
+    error::{Error, Result},
+    get_local_dir,
+    pak::{EntryQuery, ExtractOptions, PakId, filter::PathPattern, search::EntrySearchQuery},
+    service::pak::PakService,
+};
+
+static API_SERVER_SERVICE: OnceLock<ApiServerService> = OnceLock::new();
+
+const API_SERVER_HOST: &str = "127.0.0.1";
+const DEFAULT_API_SERVER_PORT: u16 = 47810;
+/// Connection info written next to the app while the server runs, for tools to discover.
+const API_SERVER_INFO_FILE: &str = "api-server.json";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiServerInfo {
+    pub url: String,
+    pub port: u16,
+    /// Sent as `Authorization: Bearer <token>` or the `token` query parameter.
+    pub token: String,
+}
+
+struct RunningServer {
+    server: Arc<Server>,
+    info: ApiServerInfo,
+    accept_thread: JoinHandle<()>,
+}
+
+/// Local HTTP/JSON API exposing the loaded paks to external tools.
+///
+/// Bound to 127.0.0.1 only, every request must carry the token generated on start.
+///
+/// Routes:
+/// - `GET /api/paks`: loaded paks in load order.
+/// - `GET /api/search?glob=|regex=&limit=&groupVariants=`: active entries whose path matches.
+/// - `GET /api/entry?path=|hash=`: every version of an entry.
+/// - `GET /api/entry/data?path=|hash=&belongsTo=`: raw bytes of an entry.
+/// - `POST /api/extract`: extract with `ExtractOptions`, responds with the job report.
+/// - `POST /api/pack`: start packing with `PackOptions`.
+pub struct ApiServerService {
+    pak_service: &'static PakService,
+    running: Mutex<Option<RunningServer>>,
+}
+
+impl ApiServerService {
+    pub fn initialize() -> &'static Self {
+        API_SERVER_SERVICE.get_or_init(|| Self {
+            pak_service: PakService::get(),
+            running: Mutex::new(None),
+        })
+    }
+
+    pub fn get() -> &'static Self {
+        API_SERVER_SERVICE.get().unwrap()
+    }
+
+    /// Start the server, or return the running one.
+    pub fn start(&self, port: Option<u16>) -> Result<ApiServerInfo> {
+        let mut running = self.running.lock();
+        if let Some(running) = running.as_ref() {
+            return Ok(running.info.clone());
+        }
+
+        let port = port.unwrap_or(DEFAULT_API_SERVER_PORT);
+        let server = Server::http((API_SERVER_HOST, port)).map_err(|e| {
+            Error::ApiServer(format!("failed to bind {API_SERVER_HOST}:{port}: {e}"))
+        })?;
+        let port = server
+            .server_addr()
+            .to_ip()
+            .map(|addr| addr.port())
+            .unwrap_or(port);
+        let info = ApiServerInfo {
+            url: format!("http://{API_SERVER_HOST}:{port}"),
+            port,
+            token: Uuid::new_v4().simple().to_string(),
+        };
+
+        let server = Arc::new(server);
+        let accept_thread = {
+            let server = Arc::clone(&server);
+            let pak_service = self.pak_service;
+            let token = info.token.clone();
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    let token = token.clone();
+                    thread::spawn(move || handle_request(pak_service, &token, request));
+                }
+            })
+        };
+
+        if let Err(e) = write_info_file(&info) {
+            log::warn!("Failed to write API server info file: {}", e);
+        }
+        log::info!("API server listening on {}", info.url);
+        *running = Some(RunningServer {
+            server,
+            info: info.clone(),
+            accept_thread,
+        });
+        Ok(info)
+    }
+
+    pub fn stop(&self) {
+        let Some(running) = self.running.lock().take() else {
+            return;
+        };
+        running.server.unblock();
+        if running.accept_thread.join().is_err() {
+            log::error!("API server thread panicked");
+        }
+        let _ = std::fs::remove_file(info_file_path());
+        log::info!("API server stopped");
+    }
+
+    pub fn status(&self) -> Option<ApiServerInfo> {
+        self.running
+            .lock()
+            .as_ref()
+            .map(|running| running.info.clone())
+    }
+}
+
+fn info_file_path() -> PathBuf {
+    get_local_dir().join(API_SERVER_INFO_FILE)
+}
+
+fn write_info_file(info: &ApiServerInfo) -> Result<()> {
+    let path = info_file_path();
+    let content = serde_json::to_string_pretty(info).map_err(|e| Error::Internal(e.to_string()))?;
+    std::fs::write(&path, content).map_err(|e| Error::FileIO {
+        path: path.to_string_lossy().to_string(),
+        source: e,
+    })
+}
+
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+impl ApiError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        let status = match &error {
+            Error::PakEntryNotFound(_) | Error::PakIdNotFound(_) => 404,
+            Error::NoPaksLoaded
+            | Error::MissingFileList
+            | Error::UnpackAlreadyRunning
+            | Error::PackAlreadyRunning => 409,
+            Error::InvalidExtractFilter(_) => 400,
+            _ => 500,
+        };
+        Self::new(status, error.to_string())
+    }
+}
+
+fn handle_request(pak_service: &PakService, token: &str, mut request: Request) {
+    let url = request.url().to_string();
+    let (path, params) = parse_url(&url);
+    let method = request.method().clone();
+    log::debug!("API request: {} {}", method, path);
+
+    let result = if !is_authorized(&request, &params, token) {
+        Err(ApiError::new(401, "missing or invalid token"))
+    } else {
+        match (&method, path) {
+            (Method::Get, "/api/paks") => to_json(pak_service.list_all_paks()),
+            (Method::Get, "/api/search") => search_query(&params)
+                .and_then(|query| Ok(pak_service.search_entries(&query)?))
+                .and_then(to_json),
+            (Method::Get, "/api/entry") => entry_query(&params)
+                .and_then(|query| Ok(pak_service.get_entry_history(&query)?))
+                .and_then(to_json),
+            (Method::Get, "/api/entry/data") => {
+                stream_entry(pak_service, &params, request);
+                return;
+            }
+            (Method::Post, "/api/extract") => read_json_body::<ExtractOptions>(&mut request)
+                .and_then(|options| {
+                    let progress = UnpackProgressChannel::new(Channel::new(|_| Ok(())));
+                    let report = tauri::async_runtime::block_on(
+                        pak_service.unpack_optional(&options, progress),
+                    )?;
+                    to_json(report)
+                }),
+            (Method::Post, "/api/pack") => {
+                read_json_body::<PackOptions>(&mut request).and_then(|options| {
+                    let progress = PackProgressChannel::new(Channel::new(|_| Ok(())));
+                    pak_service.pack(&options, progress)?;
+                    Ok(json!({ "started": true }))
+                })
+            }
+            _ => Err(ApiError::new(404, format!("no route for {method} {path}"))),
+        }
+    };
+
+    let response = match result {
+        Ok(body) => json_response(200, &body),
+        Err(error) => json_response(error.status, &json!({ "error": error.message })),
+    };
+    if let Err(e) = request.respond(response) {
+        log::warn!("Failed to send API response: {}", e);
+    }
+}
+
+/// Respond with the raw bytes of an entry, streamed from the pak.
+fn stream_entry(pak_service: &PakService, params: &HashMap<String, String>, request: Request) {
+    let mut request = Some(request);
+    let result = entry_query(params).and_then(|query| {
+        let belongs_to = params
+            .get("belongsTo")
+            .map(|id| parse_pak_id(id))
+            .transpose()?;
+        let respond = pak_service.read_entry(&query, belongs_to, |size, reader| {
+            let response = Response::new(
+                StatusCode(200),
+                vec![header("Content-Type", "application/octet-stream")],
+                reader,
+                Some(size as usize),
+                None,
+            );
+            request.take().unwrap().respond(response)
+        })?;
+        if let Err(e) = respond {
+            log::warn!("Failed to stream API entry: {}", e);
+        }
+        Ok(())
+    });
+
+    if let (Err(error), Some(request)) = (result, request)
+        && let Err(e) = request.respond(json_response(
+            error.status,
+            &json!({ "error": error.message }),
+        ))
+    {
+        log::warn!("Failed to send API response: {}", e);
+    }
+}
+
+fn is_authorized(request: &Request, params: &HashMap<String, String>, token: &str) -> bool {
+    let bearer = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .and_then(|header| header.value.as_str().strip_prefix("Bearer "));
+    bearer.or(params.get("token").map(String::as_str)) == Some(token)
+}
+
+fn search_query(params: &HashMap<String, String>) -> ApiResult<EntrySearchQuery> {
+    let pattern = match (params.get("glob"), params.get("regex")) {
+        (Some(glob), _) => PathPattern::Glob(glob.clone()),
+        (None, Some(regex)) => PathPattern::Regex(regex.clone()),
+        (None, None) => return Err(ApiError::new(400, "missing `glob` or `regex` parameter")),
+    };
+    let limit = params
+        .get("limit")
+        .map(|limit| {
+            limit
+                .parse()
+                .map_err(|_| ApiError::new(400, format!("invalid limit: {limit}")))
+        })
+        .transpose()?;
+    let group_variants = params
+        .get("groupVariants")
+        .map(|group| {
+            group
+                .parse()
+                .map_err(|_| ApiError::new(400, format!("invalid groupVariants: {group}")))
+        })
+        .transpose()?;
+    Ok(EntrySearchQuery {
+        pattern,
+        limit,
+        group_variants,
+    })
+}
+
+/// Entry query from a `path` or a 16 digit hex `hash` parameter.
+fn entry_query(params: &HashMap<String, String>) -> ApiResult<EntryQuery> {
+    let hash = params
+        .get("hash")
+        .map(|hash| {
+            u64::from_str_radix(hash, 16)
+                .map(JsSafeHash::from_u64)
+                .map_err(|_| ApiError::new(400, format!("invalid hash: {hash}")))
+        })
+        .transpose()?;
+    let path = params.get("path").cloned();
+    if hash.is_none() && path.is_none() {
+        return Err(ApiError::new(400, "missing `path` or `hash` parameter"));
+    }
+    Ok(EntryQuery { hash, path })
+}
+
+fn parse_pak_id(id: &str) -> ApiResult<PakId> {
+    serde_json::from_value(Value::String(id.to_string()))
+        .map_err(|_| ApiError::new(400, format!("invalid pak id: {id}")))
+}
+
+fn read_json_body<T: DeserializeOwned>(request: &mut Request) -> ApiResult<T> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .read_to_string(&mut body)
+        .map_err(|e| ApiError::new(400, format!("failed to read body: {e}")))?;
+    serde_json::from_str(&body).map_err(|e| ApiError::new(400, format!("invalid body: {e}")))
+}
+
+fn to_json(value: impl Serialize) -> ApiResult<Value> {
+    serde_json::to_value(value).map_err(|e| ApiError::new(500, e.to_string()))
+}
+
+fn json_response(status: u16, body: &Value) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).unwrap()
+}
+
+/// Split a request url into its path and decoded query parameters.
+fn parse_url(url: &str) -> (&str, HashMap<String, String>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let params = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+    (path, params)
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if bytes
+                .get(i + 1..i + 3)
+                .is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit)) =>
+            {
+                decoded.push(hex_value(bytes[i + 1]) << 4 | hex_value(bytes[i + 2]));
+                i += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    use super::*;
+    use crate::pak::group::PakGroup;
+
+    #[test]
+    fn parse_url_decodes_query() {
+        let (path, params) =
+            parse_url("/api/search?glob=natives%2FSTM%2F**%2F*.tex.*&limit=10&token=a+b");
+        assert_eq!(path, "/api/search");
+        assert_eq!(params["glob"], "natives/STM/**/*.tex.*");
+        assert_eq!(params["limit"], "10");
+        assert_eq!(params["token"], "a b");
+
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%E6%97%A5"), "日");
+    }
+
+    #[test]
+    fn entry_query_accepts_hex_hash() {
+        let (_, params) = parse_url("/api/entry?hash=10015D55056456A1");
+        let query = entry_query(&params).ok().unwrap();
+        assert_eq!(query.resolve_hash(), Some(0x10015D55056456A1));
+        assert!(entry_query(&HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn requests_need_the_token() {
+        PakService::initialize(PakGroup::new());
+        let api_server = ApiServerService::initialize();
+        let info = api_server.start(Some(0)).unwrap();
+        let status = |path: &str, token: Option<&str>| {
+            let mut stream = TcpStream::connect((API_SERVER_HOST, info.port)).unwrap();
+            let auth = token
+                .map(|token| format!("Authorization: Bearer {token}\r\n"))
+                .unwrap_or_default();
+            write!(
+                stream,
+                "GET {path} HTTP/1.1\r\nHost: {API_SERVER_HOST}\r\n{auth}Connection: close\r\n\r\n"
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response.split(' ').nth(1).unwrap().parse::<u16>().unwrap()
+        };
+
+        let statuses = [
+            status("/api/paks", None),
+            status("/api/paks", Some("wrong")),
+            status("/api/paks", Some(&info.token)),
+            status(&format!("/api/paks?token={}", info.token), None),
+            status("/api/missing", Some(&info.token)),
+        ];
+        api_server.stop();
+
+        assert_eq!(statuses, [401, 401, 200, 200, 404]);
+    }
+}
@@ -8,6 +8,7 @@ use re_sound::{bnk::BnkRaw, pck::Pck};
 
 use crate::{
     error::{Error, Result},
+    pak::file_type::{self, FileType},
     path_components::PathComponents,
 };
 
@@ -196,11 +197,42 @@ where
     })
 }
 
+const BNK_EXTENSIONS: &[&str] = &["bnk", "sbnk"];
+const PCK_EXTENSIONS: &[&str] = &["pck", "spck"];
+/// All container extensions, for the format handler.
+pub(super) const CONTAINER_EXTENSIONS: &[&str] = &["bnk", "sbnk", "pck", "spck"];
+
 pub(super) fn audio_container_kind_from_path(path: &str) -> Option<AudioContainerKind> {
     let components = PathComponents::parse(path)?;
-    match components.extension()?.to_ascii_lowercase().as_str() {
-        "bnk" | "sbnk" => Some(AudioContainerKind::Bnk),
-        "pck" | "spck" => Some(AudioContainerKind::Pck),
+    let extension = components.extension()?.to_ascii_lowercase();
+    if BNK_EXTENSIONS.contains(&extension.as_str()) {
+        Some(AudioContainerKind::Bnk)
+    } else if PCK_EXTENSIONS.contains(&extension.as_str()) {
+        Some(AudioContainerKind::Pck)
+    } else {
+        None
+    }
+}
+
+/// Container kind from the entry path, or from the magic if the extension is unknown.
+pub(super) fn audio_container_kind_from_file(
+    container_path: &Path,
+    entry_path: &str,
+) -> Option<AudioContainerKind> {
+    audio_container_kind_from_path(entry_path).or_else(|| {
+        let mut file = File::open(container_path).ok()?;
+        audio_container_kind_from_file_type(FileType::detect(
+            &file_type::read_header(&mut file).ok()?,
+        )?)
+    })
+}
+
+pub(super) fn audio_container_kind_from_file_type(
+    file_type: FileType,
+) -> Option<AudioContainerKind> {
+    match file_type {
+        FileType::Bnk => Some(AudioContainerKind::Bnk),
+        FileType::Pck => Some(AudioContainerKind::Pck),
         _ => None,
     }
 }
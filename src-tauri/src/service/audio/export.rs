@@ -15,7 +15,10 @@ use crate::{
     external_tools,
 };
 
-use super::{AudioExtractBatchOptions, AudioService};
+use super::{
+    AudioContainerKind, AudioExtractBatchOptions, AudioService,
+    container::{extract_wems_from_file, list_container_from_file},
+};
 
 const MAX_AUDIO_CONVERSION_THREADS: usize = 8;
 
@@ -178,6 +181,30 @@ fn build_wav_path(wem_path: &Path, output_dir: &Path) -> Result<PathBuf> {
     Ok(output_dir.join(Path::new(&file_name)))
 }
 
+/// Convert every wem of a container file into `output_dir`, named `<index>_<wem id>.wav`.
+pub(super) fn convert_container_to_wavs(
+    kind: AudioContainerKind,
+    container_path: &Path,
+    output_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let entries = list_container_from_file(String::new(), kind, container_path)?.entries;
+    let indices = (0..entries.len()).collect::<Vec<_>>();
+    let wems = extract_wems_from_file(kind, container_path, &indices)?;
+    std::fs::create_dir_all(output_dir)?;
+
+    wems.into_iter()
+        .map(|wem| {
+            let name = format!("{:04}_{}", wem.index, wem.wem_id);
+            let wem_path = output_dir.join(format!("{name}.wem"));
+            let wav_path = output_dir.join(format!("{name}.wav"));
+            std::fs::write(&wem_path, &wem.data)?;
+            let result = convert_wem_to_wav(&wem_path, &wav_path);
+            let _ = std::fs::remove_file(&wem_path);
+            result.map(|_| wav_path)
+        })
+        .collect()
+}
+
 fn convert_wem_to_wav(wem_path: &Path, wav_path: &Path) -> Result<()> {
     if wav_path.exists() {
         return Ok(());
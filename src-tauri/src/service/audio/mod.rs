@@ -19,12 +19,16 @@ use crate::{
     common::JsSafeHash,
     error::{Error, Result},
     get_local_dir,
-    pak::PakId,
-    service::pak::PakService,
+    pak::{PakId, file_type::FileType},
+    service::{
+        format::{FormatHandler, FormatRegistry},
+        pak::PakService,
+    },
 };
 
 use self::container::{
-    ExtractedWem, audio_container_kind_from_path, audio_type_error_hint,
+    CONTAINER_EXTENSIONS, ExtractedWem, audio_container_kind_from_file,
+    audio_container_kind_from_file_type, audio_container_kind_from_path, audio_type_error_hint,
     build_temp_container_file_name, build_temp_wem_file_name, extract_wems_from_file,
     list_container_from_file,
 };
@@ -98,6 +102,33 @@ impl AudioService {
         result
     }
 
+    /// Convert an extracted `.bnk` or `.pck` file to wavs in a `<file name>_wav` directory beside it.
+    ///
+    /// `entry_path` is the path of the entry in the pak, used to detect the container kind.
+    pub fn convert_container_file(container_path: &Path, entry_path: &str) -> Result<Vec<PathBuf>> {
+        let kind = audio_container_kind_from_file(container_path, entry_path)
+            .ok_or_else(|| Error::AudioFileNotSupported(audio_type_error_hint(entry_path)))?;
+        let mut dir_name = container_path
+            .file_name()
+            .unwrap_or_default()
+            .to_os_string();
+        dir_name.push("_wav");
+        export::convert_container_to_wavs(
+            kind,
+            container_path,
+            &container_path.with_file_name(dir_name),
+        )
+    }
+
+    pub fn describe_container_file(
+        container_path: &Path,
+        entry_path: &str,
+    ) -> Result<AudioContainerInfo> {
+        let kind = audio_container_kind_from_file(container_path, entry_path)
+            .ok_or_else(|| Error::AudioFileNotSupported(audio_type_error_hint(entry_path)))?;
+        list_container_from_file(entry_path.to_string(), kind, container_path)
+    }
+
     pub fn terminate_extract(&self) {
         self.export_should_terminate.store(true, Ordering::SeqCst);
     }
@@ -128,11 +159,29 @@ impl AudioService {
         write_wems_to_dir(source_hash, wems, output_dir)
     }
 
+    /// Resolve a container entry, detected by extension or by magic like other formats.
     fn resolve_source_file(&self, source: AudioSourceRef) -> Result<AudioSourceFile> {
+        let hash = source.hash.hash_u64();
         let path = self
             .pak_service
-            .get_entry_path_by_hash(source.hash.hash_u64())?;
-        let kind = audio_container_kind_from_path(&path)
+            .pak_group()
+            .lock()
+            .resolve_entry_path(hash, None)?;
+        let mut file_type = None;
+        let kind = FormatRegistry::get()
+            .detect(&path, || {
+                file_type = self
+                    .pak_service
+                    .pak_group()
+                    .lock()
+                    .detect_entry_type(hash, None);
+                file_type
+            })
+            .filter(|handler| handler.id() == AudioContainerFormatHandler.id())
+            .and_then(|_| {
+                audio_container_kind_from_path(&path)
+                    .or_else(|| file_type.and_then(audio_container_kind_from_file_type))
+            })
             .ok_or_else(|| Error::AudioFileNotSupported(audio_type_error_hint(&path)))?;
 
         Ok(AudioSourceFile { path, kind })
@@ -158,13 +207,57 @@ impl AudioService {
             source_path,
             kind,
         ));
-        self.pak_service.unpack_file(source_path, &output_path)?;
+        self.pak_service
+            .unpack_file_by_hash(source_hash, None, &output_path)?;
         container_files.insert(source_hash, output_path.clone());
 
         Ok(output_path)
     }
 }
 
+/// `.bnk` and `.pck` containers, exported to one `.wav` per wem.
+pub struct AudioContainerFormatHandler;
+
+impl FormatHandler for AudioContainerFormatHandler {
+    fn id(&self) -> &'static str {
+        "audio"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        CONTAINER_EXTENSIONS
+    }
+
+    fn file_types(&self) -> &'static [FileType] {
+        &[FileType::Bnk, FileType::Pck]
+    }
+
+    fn convert_formats(&self) -> &'static [&'static str] {
+        &["wav"]
+    }
+
+    fn has_metadata(&self) -> bool {
+        true
+    }
+
+    fn convert_file(
+        &self,
+        source: &Path,
+        entry_path: &str,
+        _hash: u64,
+        format: &str,
+    ) -> Result<Vec<PathBuf>> {
+        if !self.supports_conversion(format) {
+            return Err(self.unsupported_format(format));
+        }
+        AudioService::convert_container_file(source, entry_path)
+    }
+
+    fn metadata(&self, source: &Path, entry_path: &str) -> Result<serde_json::Value> {
+        let info = AudioService::describe_container_file(source, entry_path)?;
+        serde_json::to_value(info).map_err(|e| Error::Internal(e.to_string()))
+    }
+}
+
 #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AudioSourceRef {
@@ -0,0 +1,280 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use serde::Serialize;
+
+use crate::{
+    error::{Error, Result},
+    pak::file_type::FileType,
+    path_components::PathComponents,
+    service::{
+        audio::AudioContainerFormatHandler,
+        model_insight::{MaterialFormatHandler, MeshFormatHandler},
+        preview::TextureFormatHandler,
+    },
+};
+
+static FORMAT_REGISTRY: OnceLock<FormatRegistry> = OnceLock::new();
+
+/// What a format handler can do, shown to the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatCapabilities {
+    pub id: &'static str,
+    pub extensions: &'static [&'static str],
+    pub preview: bool,
+    /// Extensions of the single files an entry exports to, e.g. `png`.
+    pub export_formats: &'static [&'static str],
+    /// Formats extracted files are converted to, the outputs may be several files.
+    pub convert_formats: &'static [&'static str],
+    pub metadata: bool,
+}
+
+/// Preview, export and metadata of one file format.
+///
+/// Handlers are matched by extension first, then by the type detected from the magic.
+pub trait FormatHandler: Send + Sync {
+    /// Unique id, e.g. `texture`.
+    fn id(&self) -> &'static str;
+
+    /// Extensions without version suffix, lowercase.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Types detected from the magic of entries.
+    fn file_types(&self) -> &'static [FileType];
+
+    fn can_preview(&self) -> bool {
+        false
+    }
+
+    /// Formats [`FormatHandler::export_reader`] writes.
+    fn export_formats(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Formats [`FormatHandler::convert_file`] handles, the export formats by default.
+    fn convert_formats(&self) -> &'static [&'static str] {
+        self.export_formats()
+    }
+
+    fn has_metadata(&self) -> bool {
+        false
+    }
+
+    /// Version of the format, from the suffix of an entry path, 0 without a suffix.
+    ///
+    /// Parsers of versioned formats like `mesh` need it.
+    fn file_version(&self, entry_path: &str) -> Result<u32> {
+        u32::try_from(entry_version(entry_path).unwrap_or(0)).map_err(|_| {
+            Error::FormatNotSupported(format!(
+                "{} file version suffix is too large: {entry_path}",
+                self.id()
+            ))
+        })
+    }
+
+    fn capabilities(&self) -> FormatCapabilities {
+        FormatCapabilities {
+            id: self.id(),
+            extensions: self.extensions(),
+            preview: self.can_preview(),
+            export_formats: self.export_formats(),
+            convert_formats: self.convert_formats(),
+            metadata: self.has_metadata(),
+        }
+    }
+
+    /// Extension of the preview files, e.g. `png`.
+    fn preview_extension(&self) -> &'static str {
+        "png"
+    }
+
+    /// Convert an unpacked file to a file the frontend can show.
+    fn preview(&self, source: &Path, output_path: &Path) -> Result<()> {
+        let _ = (source, output_path);
+        Err(Error::PreviewFileNotSupported(self.id().to_string()))
+    }
+
+    /// Export an entry to a single file.
+    fn export_reader(&self, reader: &mut dyn Read, output_path: &Path, format: &str) -> Result<()> {
+        let _ = (reader, output_path);
+        Err(self.unsupported_format(format))
+    }
+
+    /// Convert an extracted file, writing the outputs next to it.
+    ///
+    /// Handlers exporting to several files override this, the default exports a single file.
+    fn convert_file(
+        &self,
+        source: &Path,
+        entry_path: &str,
+        hash: u64,
+        format: &str,
+    ) -> Result<Vec<PathBuf>> {
+        let _ = entry_path;
+        let file_name = source
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let output_path = source.with_file_name(export_file_name(&file_name, format, hash));
+        let mut reader = File::open(source)?;
+        self.export_reader(&mut reader, &output_path, format)?;
+        Ok(vec![output_path])
+    }
+
+    /// Describe an unpacked file.
+    fn metadata(&self, source: &Path, entry_path: &str) -> Result<serde_json::Value> {
+        let _ = (source, entry_path);
+        Err(Error::FormatNotSupported(format!("{} metadata", self.id())))
+    }
+
+    fn supports_export(&self, format: &str) -> bool {
+        self.export_formats().contains(&format)
+    }
+
+    fn supports_conversion(&self, format: &str) -> bool {
+        self.convert_formats().contains(&format)
+    }
+
+    fn unsupported_format(&self, format: &str) -> Error {
+        Error::FormatNotSupported(format!("{} to {format}", self.id()))
+    }
+}
+
+/// Registered format handlers, checked in order.
+pub struct FormatRegistry {
+    handlers: Vec<Box<dyn FormatHandler>>,
+}
+
+impl FormatRegistry {
+    pub fn get() -> &'static Self {
+        FORMAT_REGISTRY.get_or_init(|| {
+            Self::new(vec![
+                Box::new(TextureFormatHandler),
+                Box::new(AudioContainerFormatHandler),
+                Box::new(MeshFormatHandler),
+                Box::new(MaterialFormatHandler),
+            ])
+        })
+    }
+
+    fn new(handlers: Vec<Box<dyn FormatHandler>>) -> Self {
+        Self { handlers }
+    }
+
+    /// Find a handler by the extension of an entry path, version suffix and tags are ignored.
+    pub fn find_by_path(&self, path: &str) -> Option<&dyn FormatHandler> {
+        let extension = PathComponents::parse(path)?
+            .extension()?
+            .to_ascii_lowercase();
+        self.handlers
+            .iter()
+            .find(|handler| handler.extensions().contains(&extension.as_str()))
+            .map(|handler| handler.as_ref())
+    }
+
+    pub fn find_by_file_type(&self, file_type: FileType) -> Option<&dyn FormatHandler> {
+        self.handlers
+            .iter()
+            .find(|handler| handler.file_types().contains(&file_type))
+            .map(|handler| handler.as_ref())
+    }
+
+    /// Find a handler by path, falling back to the detected type.
+    ///
+    /// `detect` is only called if no handler matches the path, it usually reads the entry.
+    pub fn detect(
+        &self,
+        path: &str,
+        detect: impl FnOnce() -> Option<FileType>,
+    ) -> Option<&dyn FormatHandler> {
+        self.find_by_path(path)
+            .or_else(|| detect().and_then(|file_type| self.find_by_file_type(file_type)))
+    }
+
+    pub fn capabilities(&self) -> Vec<FormatCapabilities> {
+        self.handlers
+            .iter()
+            .map(|handler| handler.capabilities())
+            .collect()
+    }
+}
+
+/// Version from the suffix of an entry path, e.g. `2109148288` in `a.mesh.2109148288.X64`.
+pub fn entry_version(path: &str) -> Option<u64> {
+    PathComponents::parse(path)?.version_str()?.parse().ok()
+}
+
+/// Name of an exported file, the version suffix and tags are replaced by the format.
+///
+/// Example: `a.tex.241106027.X64` -> `a.png`
+pub(crate) fn export_file_name(file_name: &str, format: &str, hash: u64) -> String {
+    let base = PathComponents::parse(file_name)
+        .and_then(|components| {
+            Path::new(components.raw_path())
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+        })
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or_else(|| fallback_file_stem(file_name, hash));
+
+    format!("{base}.{format}")
+}
+
+fn fallback_file_stem(file_name: &str, hash: u64) -> String {
+    Path::new(file_name)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or_else(|| format!("{hash:016X}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_finds_handlers_by_path_and_magic() {
+        let registry = FormatRegistry::get();
+
+        let handler = registry.find_by_path("natives/STM/a.tex.241106027.X64");
+        assert_eq!(handler.map(|handler| handler.id()), Some("texture"));
+        let handler = registry.find_by_path("natives/STM/Sound/b.SBNK.1.X64");
+        assert_eq!(handler.map(|handler| handler.id()), Some("audio"));
+        // containers convert to several wav files but have no single file export
+        assert!(handler.is_some_and(|handler| {
+            handler.supports_conversion("wav") && !handler.supports_export("wav")
+        }));
+        let handler = registry.find_by_path("natives/STM/a.mesh.2109148288");
+        assert_eq!(handler.map(|handler| handler.id()), Some("mesh"));
+        assert_eq!(
+            handler.map(|handler| handler
+                .file_version("natives/STM/a.mesh.2109148288")
+                .unwrap()),
+            Some(2109148288)
+        );
+        assert!(registry.find_by_path("natives/STM/a.pfb.17").is_none());
+
+        // named entries with an unknown extension fall back to the magic
+        let handler = registry.detect("natives/STM/a.bin", || Some(FileType::Pck));
+        assert_eq!(handler.map(|handler| handler.id()), Some("audio"));
+        let handler = registry.detect("natives/STM/a.bin", || Some(FileType::Mdf2));
+        assert_eq!(handler.map(|handler| handler.id()), Some("mdf2"));
+        let handler = registry.detect("natives/STM/a.tex.1", || unreachable!());
+        assert!(handler.is_some_and(|handler| handler.supports_export("dds")));
+    }
+
+    #[test]
+    fn entry_version_ignores_tags() {
+        assert_eq!(
+            entry_version("natives/STM/a.mesh.2109148288"),
+            Some(2109148288)
+        );
+        assert_eq!(entry_version("natives/STM/a.mdf2.45.X64"), Some(45));
+        assert_eq!(entry_version("natives/STM/a.mesh"), None);
+    }
+}
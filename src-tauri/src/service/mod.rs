@@ -1,5 +1,9 @@
+pub mod api_server;
 pub mod audio;
+pub mod format;
 pub mod model_insight;
 pub mod pak;
 pub mod preview;
+pub mod preview_cache;
+pub mod script;
 pub mod update;
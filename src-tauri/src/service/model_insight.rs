@@ -1,19 +1,26 @@
 use std::{
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::OnceLock,
     time::Instant,
 };
 
+use model_insight::{MdfFile, MeshFile};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    TEMP_DIR_NAME,
     common::JsSafeHash,
     error::{Error, Result},
-    get_local_dir,
-    pak::{Pak, PakId},
-    service::pak::PakService,
+    pak::{
+        Pak, PakId,
+        file_type::FileType,
+        streaming::{is_streaming_entry_path, natives_root_entry, streaming_entry_path},
+    },
+    service::{
+        format::{FormatHandler, FormatRegistry, entry_version},
+        pak::{PakService, PreviewEntry},
+        preview_cache::PreviewCache,
+    },
 };
 
 static MODEL_INSIGHT_SERVICE: OnceLock<ModelInsightService> = OnceLock::new();
@@ -66,17 +73,15 @@ pub struct ModelInsightTexturePreview {
 
 pub struct ModelInsightService {
     pak_service: &'static PakService,
-    temp_dir: PathBuf,
+    /// Unpacked assets and texture previews are kept across sessions.
+    preview_cache: &'static PreviewCache,
 }
 
 impl ModelInsightService {
     pub fn initialize() -> Result<&'static Self> {
-        let temp_dir = get_local_dir().join(TEMP_DIR_NAME).join("model-insight");
-        std::fs::create_dir_all(&temp_dir)?;
-
         Ok(MODEL_INSIGHT_SERVICE.get_or_init(|| Self {
             pak_service: PakService::get(),
-            temp_dir,
+            preview_cache: PreviewCache::get(),
         }))
     }
 
@@ -89,15 +94,17 @@ impl ModelInsightService {
         options: ModelInsightLoadMeshAssetsOptions,
     ) -> Result<ModelInsightMeshAssets> {
         let mesh_entry_path = normalize_entry_path(&options.entry_path);
-        let mesh_file_version = checked_version_suffix(&mesh_entry_path, "mesh")?;
-        let asset_dir = self.temp_dir.join("wasm-assets");
-        std::fs::create_dir_all(&asset_dir)?;
+        let mesh_file_version = self.file_version(
+            &MeshFormatHandler,
+            &mesh_entry_path,
+            options.hash.hash_u64(),
+            options.belongs_to,
+        )?;
 
         let mesh_data = self.materialize_hash_bytes(
             options.hash.hash_u64(),
             options.belongs_to,
             &mesh_entry_path,
-            &asset_dir,
         )?;
 
         let streaming_buffer = match find_streaming_mesh_buffer_entry(
@@ -110,7 +117,6 @@ impl ModelInsightService {
                     resolved.hash,
                     resolved.belongs_to,
                     &resolved.entry_path,
-                    &asset_dir,
                 )?;
                 Some((resolved.entry_path, streaming_buffer_data))
             }
@@ -121,12 +127,16 @@ impl ModelInsightService {
         let mdf =
             match find_adjacent_mdf_entry(self.pak_service, &mesh_entry_path, options.belongs_to) {
                 Ok(resolved) => {
-                    let mdf_file_version = checked_version_suffix(&resolved.entry_path, "mdf2")?;
+                    let mdf_file_version = self.file_version(
+                        &MaterialFormatHandler,
+                        &resolved.entry_path,
+                        resolved.hash,
+                        resolved.belongs_to,
+                    )?;
                     let mdf_data = self.materialize_hash_bytes(
                         resolved.hash,
                         resolved.belongs_to,
                         &resolved.entry_path,
-                        &asset_dir,
                     )?;
                     Some((resolved.entry_path, mdf_file_version, mdf_data))
                 }
@@ -153,6 +163,12 @@ impl ModelInsightService {
         })
     }
 
+    /// Find the mdf2 next to a mesh, returns its entry path.
+    pub fn find_mdf_path(&self, mesh_entry_path: &str) -> Result<String> {
+        find_adjacent_mdf_entry(self.pak_service, mesh_entry_path, None)
+            .map(|resolved| resolved.entry_path)
+    }
+
     pub fn load_texture_previews(
         &self,
         options: ModelInsightLoadTexturePreviewsOptions,
@@ -161,8 +177,6 @@ impl ModelInsightService {
         let texture_resolution = options
             .texture_resolution
             .unwrap_or(ModelTextureResolution::Standard);
-        let asset_dir = self.temp_dir.join("wasm-assets");
-        std::fs::create_dir_all(&asset_dir)?;
         let resolve_context_started_at = Instant::now();
         let resolve_context = TextureResolveContext::new(self.pak_service)?;
         log::info!(
@@ -201,30 +215,32 @@ impl ModelInsightService {
             };
             let resolve_elapsed_ms = resolve_started_at.elapsed().as_millis();
 
-            let raw_path =
-                self.materialized_asset_path(resolved.hash, &resolved.entry_path, &asset_dir);
-            let raw_cached = raw_path.exists();
-            if !raw_path.exists() {
-                self.pak_service.unpack_file_by_hash(
-                    resolved.hash,
-                    resolved.belongs_to,
-                    &raw_path,
-                )?;
-            }
+            let preview_entry = self
+                .pak_service
+                .preview_entry(resolved.hash, resolved.belongs_to)?;
+            let cache_key = &preview_entry.cache_key;
+            let raw_cached = self
+                .preview_cache
+                .contains(cache_key, file_name(&resolved.entry_path));
+            let raw_path = self.materialize_entry_file(&preview_entry, &resolved.entry_path)?;
 
             let preview_lod = texture_preview_lod(texture_resolution, &resolved.entry_path);
-            let preview_path = asset_dir.join(format!(
-                "{:016X}-lod{}-{}.png",
-                resolved.hash,
-                preview_lod,
-                sanitize_file_name(&file_stem(&resolved.entry_path))
-            ));
-            let preview_cached = preview_path.exists();
-            if !preview_cached {
-                let convert_started_at = Instant::now();
-                if let Err(error) =
-                    crate::service::preview::tex_to_png_lod(&raw_path, &preview_path, preview_lod)
-                {
+            let preview_name =
+                format!("lod{}-{}.png", preview_lod, file_stem(&resolved.entry_path));
+            let preview_cached = self.preview_cache.contains(cache_key, &preview_name);
+            let convert_started_at = Instant::now();
+            let converted =
+                self.preview_cache
+                    .get_or_create(cache_key, &preview_name, |preview_path| {
+                        crate::service::preview::tex_to_png_lod(
+                            &raw_path,
+                            preview_path,
+                            preview_lod,
+                        )
+                    });
+            let preview_path = match converted {
+                Ok(path) => path,
+                Err(error) => {
                     log::warn!(
                         "model insight texture conversion skipped: entry={} error={}",
                         resolved.entry_path,
@@ -232,6 +248,8 @@ impl ModelInsightService {
                     );
                     continue;
                 }
+            };
+            if !preview_cached {
                 log::info!(
                     "model insight texture converted: entry={} elapsed={} ms",
                     resolved.entry_path,
@@ -270,28 +288,125 @@ impl ModelInsightService {
         hash: u64,
         belongs_to: Option<PakId>,
         entry_path: &str,
-        asset_dir: &std::path::Path,
     ) -> Result<Vec<u8>> {
-        let output_path = self.materialized_asset_path(hash, entry_path, asset_dir);
-
-        if !output_path.exists() {
-            self.pak_service
-                .unpack_file_by_hash(hash, belongs_to, &output_path)?;
-        }
+        let preview_entry = self.pak_service.preview_entry(hash, belongs_to)?;
+        let path = self.materialize_entry_file(&preview_entry, entry_path)?;
+        std::fs::read(&path).map_err(Into::into)
+    }
 
-        std::fs::read(&output_path).map_err(Into::into)
+    /// Unpack an entry into the preview cache, named after its file name.
+    fn materialize_entry_file(
+        &self,
+        preview_entry: &PreviewEntry,
+        entry_path: &str,
+    ) -> Result<PathBuf> {
+        self.preview_cache.get_or_create(
+            &preview_entry.cache_key,
+            file_name(entry_path),
+            |output_path| preview_entry.unpack(output_path),
+        )
     }
 
-    fn materialized_asset_path(
+    /// File version of an entry, which must be detected as the format of `expected`.
+    fn file_version(
         &self,
-        hash: u64,
+        expected: &dyn FormatHandler,
         entry_path: &str,
-        asset_dir: &std::path::Path,
-    ) -> PathBuf {
-        asset_dir.join(format!(
-            "{hash:016X}-{}",
-            sanitize_file_name(file_name(entry_path))
-        ))
+        hash: u64,
+        belongs_to: Option<PakId>,
+    ) -> Result<u32> {
+        let handler = FormatRegistry::get()
+            .detect(entry_path, || {
+                self.pak_service
+                    .pak_group()
+                    .lock()
+                    .detect_entry_type(hash, belongs_to)
+            })
+            .filter(|handler| handler.id() == expected.id())
+            .ok_or_else(|| {
+                Error::FormatNotSupported(format!("{entry_path} is not a {} file", expected.id()))
+            })?;
+        handler.file_version(entry_path)
+    }
+}
+
+/// `.mesh` files, described by their version and material names.
+pub struct MeshFormatHandler;
+
+impl FormatHandler for MeshFormatHandler {
+    fn id(&self) -> &'static str {
+        "mesh"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["mesh"]
+    }
+
+    fn file_types(&self) -> &'static [FileType] {
+        &[FileType::Mesh]
+    }
+
+    fn has_metadata(&self) -> bool {
+        true
+    }
+
+    fn metadata(&self, source: &Path, entry_path: &str) -> Result<serde_json::Value> {
+        let data = std::fs::read(source)?;
+        let mesh = MeshFile::read_bytes(&data, self.file_version(entry_path)?)
+            .map_err(|e| Error::ModelParse(format!("{entry_path}: {e}")))?;
+        Ok(serde_json::json!({
+            "version": mesh.header.version,
+            "materials": mesh.material_names,
+        }))
+    }
+}
+
+/// `.mdf2` material files, described by their materials and textures.
+pub struct MaterialFormatHandler;
+
+impl FormatHandler for MaterialFormatHandler {
+    fn id(&self) -> &'static str {
+        "mdf2"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["mdf2"]
+    }
+
+    fn file_types(&self) -> &'static [FileType] {
+        &[FileType::Mdf2]
+    }
+
+    fn has_metadata(&self) -> bool {
+        true
+    }
+
+    fn metadata(&self, source: &Path, entry_path: &str) -> Result<serde_json::Value> {
+        let data = std::fs::read(source)?;
+        let mdf = MdfFile::read_bytes(&data, self.file_version(entry_path)?)
+            .map_err(|e| Error::ModelParse(format!("{entry_path}: {e}")))?;
+        let materials = mdf
+            .materials
+            .iter()
+            .map(|material| {
+                let textures = material
+                    .textures
+                    .iter()
+                    .map(|texture| {
+                        serde_json::json!({
+                            "type": texture.texture_type,
+                            "path": texture.texture_path,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                serde_json::json!({
+                    "name": material.name(),
+                    "mmtr": material.header.mmtr_path,
+                    "textures": textures,
+                })
+            })
+            .collect::<Vec<_>>();
+        Ok(serde_json::json!({ "materials": materials }))
     }
 }
 
@@ -318,9 +433,7 @@ impl TextureResolveContext {
 
         let mut tex_candidates_by_base = HashMap::<String, Vec<ResolvedPakEntry>>::new();
         for (hash, path) in file_name_table.file_names() {
-            let Some(path) = path.to_string().ok().map(|path| path.replace('\\', "/")) else {
-                continue;
-            };
+            let path = path.replace('\\', "/");
             let normalized = normalize_entry_path(&path);
             let lowered = normalized.to_ascii_lowercase();
 
@@ -329,7 +442,7 @@ impl TextureResolveContext {
                     .entry(base.to_string())
                     .or_default()
                     .push(ResolvedPakEntry {
-                        hash: *hash,
+                        hash,
                         version: version_suffix(&normalized),
                         entry_path: normalized,
                         belongs_to: None,
@@ -402,7 +515,7 @@ fn find_streaming_mesh_buffer_entry(
     preferred_pak: Option<PakId>,
 ) -> Result<ResolvedPakEntry> {
     let mesh_entry_path = normalize_entry_path(mesh_entry_path);
-    let Some(streaming_path) = streaming_texture_candidate(&mesh_entry_path) else {
+    let Some(streaming_path) = streaming_entry_path(&mesh_entry_path) else {
         return Err(Error::PakEntryNotFound(format!(
             "streaming mesh buffer for {mesh_entry_path}"
         )));
@@ -469,7 +582,7 @@ fn texture_entry_candidates(
     let standard_candidates = candidates.clone();
     let mut streaming_candidates = Vec::new();
     for candidate in &standard_candidates {
-        if let Some(streaming_candidate) = streaming_texture_candidate(candidate) {
+        if let Some(streaming_candidate) = streaming_entry_path(candidate) {
             push_unique_candidate(&mut streaming_candidates, streaming_candidate);
         }
     }
@@ -501,42 +614,6 @@ fn normalize_material_texture_path(texture_path: &str) -> String {
     normalized
 }
 
-fn natives_root_entry(path: &str) -> Option<String> {
-    let components = path
-        .split('/')
-        .filter(|component| !component.is_empty())
-        .collect::<Vec<_>>();
-    components
-        .iter()
-        .position(|component| component.eq_ignore_ascii_case("natives"))
-        .and_then(|index| {
-            (index + 1 < components.len()).then(|| components[..=index + 1].join("/"))
-        })
-}
-
-fn streaming_texture_candidate(path: &str) -> Option<String> {
-    let mut components = path
-        .split('/')
-        .filter(|component| !component.is_empty())
-        .map(str::to_string)
-        .collect::<Vec<_>>();
-    let natives_index = components
-        .iter()
-        .position(|component| component.eq_ignore_ascii_case("natives"))?;
-    let streaming_index = natives_index + 2;
-    if streaming_index > components.len() {
-        return None;
-    }
-    if components
-        .get(streaming_index)
-        .is_some_and(|component| component.eq_ignore_ascii_case("streaming"))
-    {
-        return Some(components.join("/"));
-    }
-    components.insert(streaming_index, "streaming".to_string());
-    Some(components.join("/"))
-}
-
 fn texture_preview_lod(texture_resolution: ModelTextureResolution, entry_path: &str) -> usize {
     match texture_resolution {
         ModelTextureResolution::High => 0,
@@ -545,12 +622,6 @@ fn texture_preview_lod(texture_resolution: ModelTextureResolution, entry_path: &
     }
 }
 
-fn is_streaming_entry_path(path: &str) -> bool {
-    path.split('/')
-        .filter(|component| !component.is_empty())
-        .any(|component| component.eq_ignore_ascii_case("streaming"))
-}
-
 fn join_entry_path(parent: &str, child: &str) -> String {
     if parent.is_empty() {
         return child.trim_start_matches('/').to_string();
@@ -581,9 +652,9 @@ fn collect_named_candidates(
     Ok(file_name_table
         .file_names()
         .filter_map(|(hash, path)| {
-            let path = path.to_string().ok()?.replace('\\', "/");
+            let path = path.replace('\\', "/");
             matches_path(&path).then(|| ResolvedPakEntry {
-                hash: *hash,
+                hash,
                 version: version_suffix(&path),
                 entry_path: path,
                 belongs_to: None,
@@ -693,32 +764,13 @@ fn file_stem(path: &str) -> String {
 }
 
 fn version_suffix(path: &str) -> u64 {
-    path.rsplit_once('.')
-        .and_then(|(_, suffix)| suffix.parse::<u64>().ok())
-        .unwrap_or(0)
-}
-
-fn checked_version_suffix(path: &str, kind: &str) -> Result<u32> {
-    u32::try_from(version_suffix(path)).map_err(|_| {
-        Error::Internal(format!(
-            "{kind} file version suffix is too large for wasm API: {path}"
-        ))
-    })
+    entry_version(path).unwrap_or(0)
 }
 
 fn normalize_entry_path(path: &str) -> String {
     path.replace('\\', "/").trim_start_matches('/').to_string()
 }
 
-fn sanitize_file_name(name: &str) -> String {
-    name.chars()
-        .map(|ch| match ch {
-            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
-            other => other,
-        })
-        .collect()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -785,14 +837,14 @@ mod tests {
     #[test]
     fn inserts_streaming_after_natives_platform() {
         assert_eq!(
-            streaming_texture_candidate("natives/STM/Art/foo_ALBD"),
+            streaming_entry_path("natives/STM/Art/foo_ALBD"),
             Some("natives/STM/streaming/Art/foo_ALBD".to_string())
         );
         assert_eq!(
-            streaming_texture_candidate("natives/STM/streaming/Art/foo_ALBD"),
+            streaming_entry_path("natives/STM/streaming/Art/foo_ALBD"),
             Some("natives/STM/streaming/Art/foo_ALBD".to_string())
         );
-        assert_eq!(streaming_texture_candidate("Art/foo_ALBD"), None);
+        assert_eq!(streaming_entry_path("Art/foo_ALBD"), None);
     }
 
     #[test]
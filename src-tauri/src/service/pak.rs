@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, hash_map::Entry},
     fs::File,
     io::{BufReader, BufWriter, Read},
     path::{Path, PathBuf},
@@ -12,8 +12,8 @@ use std::{
 
 use indexmap::IndexMap;
 use parking_lot::Mutex;
+use rayon::prelude::*;
 use ree_pak_core::{
-    filename::FileNameTable,
     pak::PakEntry as CorePakEntry,
     pak::PakMetadata,
     pakfile::PakFile,
@@ -32,13 +32,33 @@ use crate::{
     common::JsSafeHash,
     error::{Error, Result},
     pak::{
-        ExtractMode, ExtractOptions, Pak, PakId, PakInfo,
+        EntryHistory, EntryQuery, ExtractMode, ExtractOptions, Pak, PakId, PakInfo,
+        archive::{self, ArchiveReader, ModArchive},
+        archive_output::ExtractArchiveWriter,
+        convert::ExtractConverter,
+        entry_path,
+        file_type::{self, FileType},
+        filter::ExtractFilter,
         group::PakGroup,
-        tree::{FileTree, RenderTreeNode, RenderTreeOptions},
+        incremental::{ExtractJournal, WriteOutcome, write_if_changed},
+        list_analysis::ListAnalysisReport,
+        list_file,
+        name_layers::{BASE_LAYER_NAME, LayeredNames, NameLayerInfo, NameLayerSource, NameLookup},
+        path_list::{ExtractListOptions, ExtractListReport},
+        report::{JobReport, JobReportCollector},
+        search::{EntrySearchQuery, EntrySearchResult},
+        stats::{PakGroupStats, PakStatsOptions},
+        suffix_resolver::{SuffixResolveOptions, SuffixResolveReport},
+        tree::{FileTree, RenderTreeNode, RenderTreeOptions, TreeMode},
+        tree_index::{TreeChildrenPage, TreeChildrenQuery},
+        variant::VariantSummary,
     },
+    service::{format::FormatRegistry, preview_cache::PreviewCacheKey},
 };
 
 const FILENAME_HASH_DIRECTORY: &str = "_FilenameHash";
+/// Entries extracted by a worker at a time.
+const EXTRACT_CHUNK_SIZE: usize = 256;
 
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -172,8 +192,17 @@ impl PakTreeBuilder {
 
 #[derive(Debug, Clone)]
 enum ManifestSource {
-    LooseFile { real_path: PathBuf },
-    PakEntry { pak_path: PathBuf, entry_hash: u64 },
+    LooseFile {
+        real_path: PathBuf,
+    },
+    PakEntry {
+        pak_path: PathBuf,
+        entry_hash: u64,
+    },
+    ArchiveEntry {
+        archive_path: PathBuf,
+        entry_name: String,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -188,6 +217,21 @@ struct FileManifest {
     source: ManifestSource,
 }
 
+/// An entry resolved once for previews, with the key of its cached files.
+#[derive(Clone)]
+pub struct PreviewEntry {
+    pub cache_key: PreviewCacheKey,
+    pakfile: Arc<PakFile>,
+    entry: CorePakEntry,
+}
+
+impl PreviewEntry {
+    /// Unpack the entry to a file.
+    pub fn unpack(&self, output_path: impl AsRef<Path>) -> Result<()> {
+        write_entry_file(&self.pakfile, &self.entry, output_path.as_ref())
+    }
+}
+
 pub struct PakService {
     pak_group: Arc<Mutex<PakGroup>>,
     work_thread: Mutex<Option<JoinHandle<()>>>,
@@ -207,30 +251,95 @@ impl PakService {
     }
 
     pub fn open_pak(&self, path: &str) -> Result<PakId> {
-        let file = File::open(path).map_err(|source| Error::FileIO {
-            path: path.to_string(),
-            source,
-        })?;
-
-        let pakfile = PakFile::from_file(file).map_err(|e| match e {
-            ree_pak_core::error::PakError::IO(source) => Error::FileIO {
-                path: path.to_string(),
-                source,
-            },
-            other => Error::PakCore(other),
-        })?;
-
-        let path_abs = Path::new(path)
-            .canonicalize()
-            .unwrap_or_else(|_| PathBuf::from(path))
-            .display()
-            .to_string();
-        let pak = Pak::new(&path_abs, pakfile);
+        let pakfile = open_pakfile(path)?;
+        let pak = Pak::new(&absolute_display_path(path), pakfile);
         let id = pak.id;
 
         self.pak_group.lock().add_pak(pak);
         Ok(id)
     }
+
+    /// Open a pak file or a zip mod archive.
+    ///
+    /// A zip archive may contain several paks and loose `natives/` files,
+    /// so all loaded pak ids are returned in load order.
+    pub fn open_path(&self, path: &str) -> Result<Vec<PakId>> {
+        if archive::is_zip_archive(path) {
+            return self.open_archive(path);
+        }
+
+        self.open_pak(path).map(|id| vec![id])
+    }
+
+    /// Open all paks inside a zip mod archive.
+    ///
+    /// Pak entries are spilled into the temp directory, loose `natives/` files
+    /// are packed into one temporary pak loaded after them.
+    fn open_archive(&self, path: &str) -> Result<Vec<PakId>> {
+        let archive_path = absolute_display_path(path);
+        let archive = ModArchive::open(path)?;
+
+        let mut paks = vec![];
+        for entry_name in archive.paks() {
+            let spill_path = archive.spill_pak(entry_name)?;
+            let pakfile = open_pakfile(&spill_path)?;
+            paks.push(Pak::new_in_archive(
+                &archive::archive_virtual_path(&archive_path, entry_name),
+                &archive_path,
+                pakfile,
+            ));
+        }
+
+        if let Some(spill_path) = archive.spill_natives_as_pak()? {
+            let pakfile = open_pakfile(&spill_path)?;
+            paks.push(Pak::new_in_archive(
+                &archive::archive_virtual_path(&archive_path, "natives"),
+                &archive_path,
+                pakfile,
+            ));
+        }
+
+        if paks.is_empty() {
+            return Err(Error::ArchiveEmpty(archive_path));
+        }
+
+        let mut pak_group = self.pak_group.lock();
+        let natives_paths = archive
+            .natives_files()
+            .map(|file| file.relative_path.clone())
+            .collect::<Vec<_>>();
+        pak_group.push_file_paths(NameLayerSource::Discovered, &natives_paths);
+
+        let ids = paks.iter().map(|pak| pak.id).collect();
+        for pak in paks {
+            pak_group.add_pak(pak);
+        }
+        Ok(ids)
+    }
+}
+
+fn open_pakfile(path: impl AsRef<Path>) -> Result<PakFile> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|source| Error::FileIO {
+        path: path.display().to_string(),
+        source,
+    })?;
+
+    PakFile::from_file(file).map_err(|e| match e {
+        ree_pak_core::error::PakError::IO(source) => Error::FileIO {
+            path: path.display().to_string(),
+            source,
+        },
+        other => Error::PakCore(other),
+    })
+}
+
+fn absolute_display_path(path: &str) -> String {
+    Path::new(path)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(path))
+        .display()
+        .to_string()
 }
 
 impl PakService {
@@ -298,17 +407,16 @@ impl PakService {
 
     pub fn get_pak_info(&self, id: PakId) -> Result<PakInfo> {
         if let Some(pak) = self.pak_group.lock().get_pak(&id) {
-            Ok(PakInfo {
-                id,
-                path: pak.path.to_string(),
-            })
+            Ok(pak.info())
         } else {
             Err(Error::PakIdNotFound(id))
         }
     }
 
     pub fn read_file_tree(&self) -> Result<FileTree> {
-        self.pak_group.lock().render_tree_combined()
+        self.pak_group
+            .lock()
+            .render_tree_combined(&RenderTreeOptions::default())
     }
 
     pub async fn read_file_tree_optimized_async(
@@ -325,8 +433,30 @@ impl PakService {
         let pak_group = self.pak_group.clone();
         let file_tree_running = self.file_tree_running.clone();
         let result = tokio::task::spawn_blocking(move || {
-            let basic_tree = pak_group.lock().render_tree_combined()?;
-            RenderTreeNode::try_from_file_tree(basic_tree, &options)
+            let pak_group = pak_group.lock();
+            let pak_order = pak_group
+                .paks()
+                .iter()
+                .map(|pak| pak.id)
+                .collect::<Vec<_>>();
+            match options.mode() {
+                TreeMode::Combined => {
+                    let arena_tree = pak_group.render_arena_tree()?;
+                    drop(pak_group);
+                    Ok::<_, Error>(RenderTreeNode::from_arena_tree(
+                        &arena_tree,
+                        &options,
+                        &pak_order,
+                    ))
+                }
+                TreeMode::PerPak => {
+                    let pak_trees = pak_group.render_pak_trees(options.pak_ids())?;
+                    drop(pak_group);
+                    Ok(RenderTreeNode::from_pak_trees(
+                        &pak_trees, &options, &pak_order,
+                    ))
+                }
+            }
         })
         .await;
 
@@ -349,7 +479,34 @@ impl PakService {
         }
     }
 
+    /// Read a page of directory children, building the tree index on first read.
+    pub async fn read_tree_children_async(
+        &self,
+        query: TreeChildrenQuery,
+    ) -> Result<TreeChildrenPage> {
+        let pak_group = self.pak_group.clone();
+        tokio::task::spawn_blocking(move || pak_group.lock().read_tree_children(&query))
+            .await
+            .map_err(|error| Error::Internal(error.to_string()))?
+    }
+
+    pub async fn get_variant_summary_async(&self) -> Result<VariantSummary> {
+        let pak_group = self.pak_group.clone();
+        tokio::task::spawn_blocking(move || pak_group.lock().variant_summary())
+            .await
+            .map_err(|error| Error::Internal(error.to_string()))?
+    }
+
+    pub async fn get_statistics_async(&self, options: PakStatsOptions) -> Result<PakGroupStats> {
+        let pak_group = self.pak_group.clone();
+        tokio::task::spawn_blocking(move || pak_group.lock().statistics(&options))
+            .await
+            .map_err(|error| Error::Internal(error.to_string()))?
+    }
+
     /// Unpack all loaded paks with given options.
+    ///
+    /// Returns the job report, which is also sent with the finish event.
     /// # Errors
     ///
     /// - No paks or no file list is loaded.
@@ -358,7 +515,7 @@ impl PakService {
         &self,
         options: &ExtractOptions,
         progress: UnpackProgressChannel,
-    ) -> Result<()> {
+    ) -> Result<JobReport> {
         if self.unpack_running.swap(true, Ordering::SeqCst) {
             return Err(Error::UnpackAlreadyRunning);
         }
@@ -375,6 +532,14 @@ impl PakService {
             }
         }
 
+        let extract_filter = match ExtractFilter::new(options) {
+            Ok(extract_filter) => extract_filter,
+            Err(error) => {
+                self.unpack_running.store(false, Ordering::SeqCst);
+                return Err(error);
+            }
+        };
+
         self.unpack_should_terminate.store(false, Ordering::SeqCst);
 
         let pak_group = self.pak_group.clone();
@@ -383,7 +548,13 @@ impl PakService {
         let unpack_running = self.unpack_running.clone();
 
         let result = tokio::task::spawn_blocking(move || {
-            unpack_optional_blocking(pak_group, options, should_terminate, progress)
+            unpack_optional_blocking(
+                pak_group,
+                options,
+                extract_filter,
+                should_terminate,
+                progress,
+            )
         })
         .await
         .map_err(|error| Error::Internal(error.to_string()))?;
@@ -392,6 +563,47 @@ impl PakService {
         result
     }
 
+    /// Extract entries listed in a path list file.
+    ///
+    /// Each path is resolved to its newest entry, missing paths are reported.
+    pub async fn extract_from_list(
+        &self,
+        options: ExtractListOptions,
+        progress: UnpackProgressChannel,
+    ) -> Result<ExtractListReport> {
+        let pak_group = self.pak_group.clone();
+        let list_path = options.list_path.clone();
+        let ignore_version = options.ignore_version();
+        let resolution = tokio::task::spawn_blocking(move || {
+            let list = list_file::read_list_paths(&list_path)?;
+            Ok::<_, Error>(pak_group.lock().resolve_path_list(&list, ignore_version))
+        })
+        .await
+        .map_err(|error| Error::Internal(error.to_string()))??;
+
+        let extract_options = ExtractOptions {
+            output_path: options.output_path,
+            r#override: options.r#override,
+            mode: options.mode,
+            extract_all: false,
+            extract_files: resolution.files,
+            languages: None,
+            with_streaming: None,
+            filter: None,
+            archive: options.archive,
+            save_report: options.save_report,
+            skip_unchanged: None,
+            resume: None,
+            workers: None,
+            converters: Vec::new(),
+            keep_original: None,
+        };
+        let mut report = resolution.report;
+        report.job = self.unpack_optional(&extract_options, progress).await?;
+
+        Ok(report)
+    }
+
     /// Unpack a specific file from Paks.
     pub fn unpack_file(&self, entry_path: &str, output_path: impl AsRef<Path>) -> Result<()> {
         {
@@ -405,19 +617,7 @@ impl PakService {
         }
 
         let (pakfile, entry) = self.find_entry(entry_path)?;
-        let mut entry_reader = pakfile.open_entry(&entry)?;
-
-        let output_path = output_path.as_ref();
-        if let Some(file_dir) = output_path
-            .parent()
-            .filter(|parent| !parent.as_os_str().is_empty())
-            && !file_dir.exists()
-        {
-            std::fs::create_dir_all(file_dir)?;
-        }
-        let mut file = File::create(output_path)?;
-        std::io::copy(&mut entry_reader, &mut file)?;
-        Ok(())
+        write_entry_file(&pakfile, &entry, output_path.as_ref())
     }
 
     pub fn unpack_file_by_hash(
@@ -426,27 +626,52 @@ impl PakService {
         belongs_to: Option<PakId>,
         output_path: impl AsRef<Path>,
     ) -> Result<()> {
-        {
-            let pak_group = self.pak_group.lock();
-            if pak_group.paks().is_empty() {
-                return Err(Error::NoPaksLoaded);
-            }
+        let (pakfile, entry) = self.find_entry_by_hash(hash, belongs_to)?;
+        write_entry_file(&pakfile, &entry, output_path.as_ref())
+    }
+
+    /// Get every version of an entry across the load order.
+    pub fn get_entry_history(&self, query: &EntryQuery) -> Result<EntryHistory> {
+        let Some(hash) = query.resolve_hash() else {
+            return Err(Error::PakEntryNotFound("empty entry query".to_string()));
+        };
+
+        let pak_group = self.pak_group.lock();
+        if pak_group.paks().is_empty() {
+            return Err(Error::NoPaksLoaded);
+        }
+
+        let mut history = pak_group.entry_history(hash);
+        if history.versions.is_empty() {
+            return Err(Error::PakEntryNotFound(format!("{hash:016X}")));
+        }
+        if history.path.is_none() {
+            history.path = query.path.clone();
         }
 
+        Ok(history)
+    }
+
+    /// Open the newest version of an entry, or the one in `belongs_to`, and pass its
+    /// uncompressed size and reader to `f`.
+    pub fn read_entry<T>(
+        &self,
+        query: &EntryQuery,
+        belongs_to: Option<PakId>,
+        f: impl FnOnce(u64, &mut dyn Read) -> T,
+    ) -> Result<T> {
+        let Some(hash) = query.resolve_hash() else {
+            return Err(Error::PakEntryNotFound("empty entry query".to_string()));
+        };
+
         let (pakfile, entry) = self.find_entry_by_hash(hash, belongs_to)?;
         let mut entry_reader = pakfile.open_entry(&entry)?;
+        Ok(f(entry.uncompressed_size(), &mut entry_reader))
+    }
 
-        let output_path = output_path.as_ref();
-        if let Some(file_dir) = output_path
-            .parent()
-            .filter(|parent| !parent.as_os_str().is_empty())
-            && !file_dir.exists()
-        {
-            std::fs::create_dir_all(file_dir)?;
-        }
-        let mut file = File::create(output_path)?;
-        std::io::copy(&mut entry_reader, &mut file)?;
-        Ok(())
+    /// Find the active versions of entries whose path matches a pattern.
+    pub fn search_entries(&self, query: &EntrySearchQuery) -> Result<EntrySearchResult> {
+        self.pak_group.lock().search_entries(query)
     }
 
     pub(crate) fn get_entry_path_by_hash(&self, hash: u64) -> Result<String> {
@@ -457,7 +682,7 @@ impl PakService {
 
         file_name_table
             .get_file_name(hash)
-            .map(|path| path.to_string().unwrap())
+            .map(str::to_string)
             .ok_or_else(|| Error::PakEntryNotFound(hash.to_string()))
     }
 
@@ -474,11 +699,7 @@ impl PakService {
             .iter()
             .rev()
             .find_map(|pak| {
-                pak.pakfile
-                    .metadata()
-                    .entries()
-                    .iter()
-                    .find(|entry| entry.hash() == file_hash)
+                pak.find_entry(file_hash)
                     .cloned()
                     .map(|entry| (Arc::clone(&pak.pakfile), entry))
             })
@@ -491,53 +712,92 @@ impl PakService {
         belongs_to: Option<PakId>,
     ) -> Result<(Arc<PakFile>, CorePakEntry)> {
         let pak_group = self.pak_group.lock();
-        if pak_group.paks().is_empty() {
-            return Err(Error::NoPaksLoaded);
-        }
-
-        if let Some(pak_id) = belongs_to {
-            let pak = pak_group
-                .get_pak(&pak_id)
-                .ok_or(Error::PakIdNotFound(pak_id))?;
-            return pak
-                .pakfile
-                .metadata()
-                .entries()
-                .iter()
-                .find(|entry| entry.hash() == hash)
-                .cloned()
-                .map(|entry| (Arc::clone(&pak.pakfile), entry))
-                .ok_or_else(|| Error::PakEntryNotFound(format!("{hash:016X}")));
-        }
+        let (pak, entry) = find_pak_entry(&pak_group, hash, belongs_to)?;
+        Ok((Arc::clone(&pak.pakfile), entry.clone()))
+    }
 
-        pak_group
-            .paks()
-            .iter()
-            .rev()
-            .find_map(|pak| {
-                pak.pakfile
-                    .metadata()
-                    .entries()
-                    .iter()
-                    .find(|entry| entry.hash() == hash)
-                    .cloned()
-                    .map(|entry| (Arc::clone(&pak.pakfile), entry))
-            })
-            .ok_or_else(|| Error::PakEntryNotFound(format!("{hash:016X}")))
+    /// Resolve an entry for previews, the active version or the one in `belongs_to`.
+    ///
+    /// Resolve once and reuse it, for both the cache key and unpacking.
+    pub fn preview_entry(&self, hash: u64, belongs_to: Option<PakId>) -> Result<PreviewEntry> {
+        let pak_group = self.pak_group.lock();
+        let (pak, entry) = find_pak_entry(&pak_group, hash, belongs_to)?;
+        let pak_path = pak.archive_path.as_deref().unwrap_or(&pak.path);
+        Ok(PreviewEntry {
+            cache_key: PreviewCacheKey::new(pak_path, hash, entry.checksum()),
+            pakfile: Arc::clone(&pak.pakfile),
+            entry: entry.clone(),
+        })
     }
 
-    pub fn set_file_name_table(&self, table: FileNameTable) {
-        self.pak_group.lock().set_file_name_table(table);
+    /// Load a list file as a name layer, `base` if no name is given.
+    ///
+    /// See [`list_file::load_list_file`] for supported formats.
+    pub fn load_file_table(&self, path: &str, name: Option<&str>) -> Result<()> {
+        let table = list_file::load_list_file(path)?;
+        self.pak_group.lock().load_name_layer(
+            name.unwrap_or(BASE_LAYER_NAME),
+            Some(path.to_string()),
+            table,
+        );
+        Ok(())
     }
 
     pub fn push_file_paths(&self, paths: Vec<String>) {
-        let mut pak_group = self.pak_group.lock();
-        let file_list = pak_group.file_name_table_mut();
-        if let Some(file_list) = file_list {
-            for file_name in paths {
-                file_list.push_str(&file_name);
-            }
-        }
+        self.pak_group
+            .lock()
+            .push_file_paths(NameLayerSource::Manual, &paths);
+    }
+
+    pub fn list_name_layers(&self) -> Vec<NameLayerInfo> {
+        self.pak_group.lock().name_layers().infos()
+    }
+
+    pub fn set_name_layer_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        self.pak_group.lock().set_name_layer_enabled(name, enabled)
+    }
+
+    pub fn remove_name_layer(&self, name: &str) -> Result<()> {
+        self.pak_group.lock().remove_name_layer(name)
+    }
+
+    /// Find the name of a hash and the layer it was resolved from.
+    pub fn lookup_name(&self, hash: u64) -> Option<NameLookup> {
+        self.pak_group.lock().name_layers().lookup(hash)
+    }
+
+    /// Export names of enabled layers as one list file, returns the number of names.
+    pub fn export_file_table(&self, output_path: &str) -> Result<usize> {
+        self.pak_group.lock().name_layers().export(output_path)
+    }
+
+    /// Report duplicates and hash collisions in a list file.
+    ///
+    /// Collisions are checked against the loaded paks and file name table.
+    pub async fn analyze_list_file_async(&self, path: String) -> Result<ListAnalysisReport> {
+        let pak_group = self.pak_group.clone();
+        tokio::task::spawn_blocking(move || {
+            let paths = list_file::read_list_paths(&path)?;
+            let mut report = ListAnalysisReport::analyze(&paths);
+            pak_group.lock().annotate_list_analysis(&mut report);
+            Ok(report)
+        })
+        .await
+        .map_err(|error| Error::Internal(error.to_string()))?
+    }
+
+    /// Resolve unknown entries by trying version and variant suffixes on base paths.
+    pub async fn resolve_suffixes_async(
+        &self,
+        options: SuffixResolveOptions,
+    ) -> Result<SuffixResolveReport> {
+        let pak_group = self.pak_group.clone();
+        tokio::task::spawn_blocking(move || {
+            let base_paths = options.read_base_paths()?;
+            pak_group.lock().resolve_suffixes(&base_paths, &options)
+        })
+        .await
+        .map_err(|error| Error::Internal(error.to_string()))?
     }
 
     pub fn analyze_conflicts(&self, options: &PackAnalyzeOptions) -> Result<Vec<PackConflictInfo>> {
@@ -588,6 +848,8 @@ impl PakService {
 
                 // wrapper pak_writer to ensure it is finished
                 let mut write_files_into_pak = || -> Result<()> {
+                    // archives stay open for the whole pack, they may hold many entries
+                    let mut archives = HashMap::new();
                     for manifest in &selected_manifests {
                         if should_terminate.load(Ordering::Relaxed) {
                             return Err(Error::Terminated);
@@ -622,6 +884,20 @@ impl PakService {
                                     .start_file_hash(manifest.hash, FileOptions::default())?;
                                 std::io::copy(&mut reader, &mut pak_writer)?;
                             }
+                            ManifestSource::ArchiveEntry {
+                                archive_path,
+                                entry_name,
+                            } => {
+                                let archive = match archives.entry(archive_path.clone()) {
+                                    Entry::Occupied(entry) => entry.into_mut(),
+                                    Entry::Vacant(entry) => {
+                                        entry.insert(ArchiveReader::open(archive_path)?)
+                                    }
+                                };
+                                pak_writer
+                                    .start_file_hash(manifest.hash, FileOptions::default())?;
+                                archive.copy_entry(entry_name, &mut pak_writer)?;
+                            }
                         }
 
                         progress1.file_done(&manifest.source_label);
@@ -652,136 +928,406 @@ impl PakService {
     }
 }
 
+/// Pak captured from the group, so extraction runs without holding the group lock.
+struct ExtractSource {
+    path: String,
+    pakfile: Arc<PakFile>,
+}
+
+/// State shared by the extraction workers.
+struct ExtractContext {
+    options: ExtractOptions,
+    output_root: PathBuf,
+    file_name_table: Arc<LayeredNames>,
+    relative_roots: HashMap<u64, Option<String>>,
+    archive_writer: Option<ExtractArchiveWriter>,
+    journal: Option<ExtractJournal>,
+    report: JobReportCollector,
+    should_terminate: Arc<AtomicBool>,
+    progress: UnpackProgressChannel,
+}
+
 fn unpack_optional_blocking(
     pak_group: Arc<Mutex<PakGroup>>,
-    options: ExtractOptions,
+    mut options: ExtractOptions,
+    extract_filter: Option<ExtractFilter>,
     should_terminate: Arc<AtomicBool>,
     progress: UnpackProgressChannel,
-) -> Result<()> {
-    let file_count = {
+) -> Result<JobReport> {
+    let (file_name_table, sources) = {
         let pak_group = pak_group.lock();
-        if options.extract_all {
-            pak_group.total_files() as u32
-        } else {
-            options.extract_files.len() as u32
+        if !options.extract_all && options.with_streaming() {
+            let counterparts = pak_group.streaming_counterparts(&options.extract_files);
+            options.extract_files.extend(counterparts);
         }
-    };
-    progress.work_start(file_count);
-
-    let mut terminated = false;
-    let pak_group = pak_group.lock();
-    let file_name_table = Arc::new(
-        pak_group
+        let file_name_table = pak_group
             .file_name_table()
             .expect("file name table checked before spawn")
-            .clone(),
+            .clone();
+        let sources = pak_group
+            .paks()
+            .iter()
+            .map(|pak| {
+                (
+                    pak.id,
+                    ExtractSource {
+                        path: pak.path.clone(),
+                        pakfile: Arc::clone(&pak.pakfile),
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        (Arc::new(file_name_table), sources)
+    };
+
+    let (journal, resumed_entries) = if options.resume() {
+        let pak_paths = sources
+            .iter()
+            .map(|(_, source)| source.path.as_str())
+            .collect::<Vec<_>>();
+        let (journal, done) = ExtractJournal::open(&options.output_path, &pak_paths)?;
+        (Some(journal), done)
+    } else {
+        (None, hashbrown::HashSet::new())
+    };
+    let (targets, resumed) = plan_extract_targets(
+        &sources,
+        &file_name_table,
+        &options,
+        extract_filter.as_ref(),
+        &resumed_entries,
     );
-    let output_root = PathBuf::from(&options.output_path);
-    let relative_roots = Arc::new(
-        options
+    let archive_writer = match options.archive {
+        Some(format) => Some(ExtractArchiveWriter::create(
+            &options.output_path,
+            format,
+            options.r#override,
+        )?),
+        None => None,
+    };
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.workers())
+        .thread_name(|index| format!("extract-{index}"))
+        .build()
+        .map_err(|error| Error::Internal(format!("Failed to build extract pool: {error}")))?;
+
+    let report = JobReportCollector::default();
+    report.resumed(resumed);
+    progress.work_start(targets.len() as u32);
+
+    let ctx = ExtractContext {
+        output_root: PathBuf::from(&options.output_path),
+        file_name_table,
+        relative_roots: options
             .extract_files
             .iter()
             .map(|info| (info.hash.hash_u64(), info.relative_root.clone()))
-            .collect::<std::collections::HashMap<_, _>>(),
-    );
-
-    for pak in pak_group.paks() {
-        if should_terminate.load(Ordering::Relaxed) {
-            terminated = true;
-            break;
+            .collect(),
+        options,
+        archive_writer,
+        journal,
+        report,
+        should_terminate: should_terminate.clone(),
+        progress: progress.clone(),
+    };
+    // chunks of entries instead of whole paks, so a single large pak uses all workers
+    pool.install(|| {
+        targets
+            .par_chunks(EXTRACT_CHUNK_SIZE)
+            .for_each(|chunk| extract_chunk(&ctx, &sources, chunk));
+    });
+
+    let ExtractContext {
+        options,
+        archive_writer,
+        journal,
+        report,
+        ..
+    } = ctx;
+    let terminated = should_terminate.load(Ordering::Relaxed);
+
+    if let Some(archive_writer) = archive_writer {
+        if terminated {
+            // drop the incomplete archive
+            drop(archive_writer);
+            let _ = std::fs::remove_file(&options.output_path);
+        } else if let Err(error) = archive_writer.finish() {
+            progress.error(error.to_string());
+            return Err(error);
         }
+    }
 
-        let target_hashes = if options.extract_all {
-            None
-        } else {
-            Some(Arc::new(
-                options
-                    .extract_files
-                    .iter()
-                    .filter_map(|info| {
-                        if info.belongs_to == pak.id {
-                            Some(info.hash.hash_u64())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<std::collections::HashSet<_>>(),
-            ))
-        };
+    if terminated {
+        // keep the journal to resume from
+        progress.error(Error::Terminated.to_string());
+        return Err(Error::Terminated);
+    }
 
-        let mut extractor = pak
-            .pakfile
-            .extractor_callback()
-            .file_name_table_arc(file_name_table.clone())
-            .skip_unknown(false)
-            .continue_on_error(true)
-            .cancel_flag(should_terminate.clone());
+    if let Some(journal) = journal
+        && let Err(error) = journal.complete()
+    {
+        log::error!("Failed to remove extract journal: {}", error);
+    }
 
-        if let Some(target_hashes) = target_hashes {
-            extractor = extractor.filter(move |entry, _path| target_hashes.contains(&entry.hash()));
+    let report = report.into_report();
+    if options.save_report() {
+        let report_path =
+            JobReport::path_for_output(&options.output_path, options.archive.is_some());
+        if let Err(error) = report.save_json(&report_path) {
+            log::error!("Failed to save extract report: {}", error);
         }
+    }
 
-        let progress1 = progress.clone();
-        let result = extractor
-            .on_event(move |event| {
-                if let ree_pak_core::extract::ExtractEvent::FileDone { hash, path, error } = event {
-                    progress1.file_done(path.to_string_lossy().as_ref(), hash, error);
-                }
+    progress.work_finished(report.clone());
+    Ok(report)
+}
+
+/// Pick the pak each entry is extracted from.
+///
+/// An entry selected in several paks is taken from the last one in load order, so parallel
+/// workers never write the same output. Returns the `(pak index, entry)` targets in pak
+/// order, and the number of entries skipped because an interrupted run already extracted them.
+fn plan_extract_targets<'a>(
+    sources: &'a [(PakId, ExtractSource)],
+    file_name_table: &LayeredNames,
+    options: &ExtractOptions,
+    extract_filter: Option<&ExtractFilter>,
+    resumed_entries: &hashbrown::HashSet<(usize, u64)>,
+) -> (Vec<(usize, &'a CorePakEntry)>, usize) {
+    let selected = (!options.extract_all).then(|| {
+        options
+            .extract_files
+            .iter()
+            .map(|info| (info.belongs_to, info.hash.hash_u64()))
+            .collect::<std::collections::HashSet<_>>()
+    });
+
+    let mut newest = HashMap::new();
+    for (pak_index, (pak_id, source)) in sources.iter().enumerate() {
+        for entry in source.pakfile.metadata().entries() {
+            if selected
+                .as_ref()
+                .is_none_or(|selected| selected.contains(&(*pak_id, entry.hash())))
+            {
+                newest.insert(entry.hash(), (pak_index, entry));
+            }
+        }
+    }
+
+    let mut resumed = 0;
+    let mut targets = newest
+        .into_iter()
+        .filter(|(hash, (pak_index, entry))| {
+            if resumed_entries.contains(&(*pak_index, *hash)) {
+                resumed += 1;
+                return false;
+            }
+            extract_filter.is_none_or(|filter| {
+                filter.matches_entry(file_name_table, &sources[*pak_index].1.pakfile, entry)
             })
-            .run_with_reader({
-                let output_root = output_root.clone();
-                let extract_mode = options.mode;
-                let relative_roots = relative_roots.clone();
-                move |entry, rel_path, reader| {
-                    let relative_root = relative_roots
-                        .get(&entry.hash())
-                        .and_then(|value| value.as_deref());
-                    let output_path = match extract_mode {
-                        ExtractMode::RelativePath => {
-                            output_root.join(build_extract_relative_path(rel_path, relative_root))
-                        }
-                        ExtractMode::AbsolutePath => output_root.join(rel_path),
-                    };
+        })
+        .map(|(_, target)| target)
+        .collect::<Vec<_>>();
+    // read each pak front to back
+    targets.sort_unstable_by_key(|(pak_index, entry)| (*pak_index, entry.offset()));
+    (targets, resumed)
+}
 
-                    if let Some(parent) = output_path.parent()
-                        && !parent.exists()
-                    {
-                        std::fs::create_dir_all(parent)?;
-                    }
+/// Extract a chunk of planned entries, opening a reader per entry on the mapped pak.
+///
+/// Failures are recorded in the report, the other entries of the chunk are still extracted.
+fn extract_chunk(
+    ctx: &ExtractContext,
+    sources: &[(PakId, ExtractSource)],
+    chunk: &[(usize, &CorePakEntry)],
+) {
+    for &(pak_index, entry) in chunk {
+        if ctx.should_terminate.load(Ordering::Relaxed) {
+            return;
+        }
 
-                    let mut open_options = std::fs::OpenOptions::new();
-                    open_options.write(true).create(true);
+        let rel_path = PathBuf::from(entry_path(&ctx.file_name_table, entry.hash()));
+        let pakfile = &sources[pak_index].1.pakfile;
+        let result = pakfile
+            .open_entry(entry)
+            .and_then(|mut reader| extract_entry(ctx, pak_index, entry, &rel_path, &mut reader));
+        let rel_path = rel_path.to_string_lossy();
+        let error = result.err().map(|error| {
+            let error = error.to_string();
+            ctx.report
+                .failed(entry.hash(), rel_path.as_ref(), error.clone());
+            error
+        });
+        ctx.progress.file_done(&rel_path, entry.hash(), error);
+    }
+}
 
-                    if options.r#override {
-                        open_options.truncate(true);
-                    } else {
-                        open_options.create_new(true);
-                    }
+fn extract_entry(
+    ctx: &ExtractContext,
+    pak_index: usize,
+    entry: &CorePakEntry,
+    rel_path: &Path,
+    reader: &mut dyn Read,
+) -> ree_pak_core::error::Result<()> {
+    let options = &ctx.options;
+    // name unknown entries by their magic, the header is put back in front of the reader
+    let header = if ctx.file_name_table.get_file_name(entry.hash()).is_none() {
+        file_type::read_header(reader)?
+    } else {
+        Vec::new()
+    };
+    let mut rel_path = rel_path.to_path_buf();
+    if let Some(file_type) = FileType::detect(&header) {
+        file_type.append_extension(&mut rel_path);
+    }
+    let rel_path = rel_path.as_path();
+    let reader: &mut dyn Read = &mut std::io::Cursor::new(header).chain(reader);
+
+    let relative_root = ctx
+        .relative_roots
+        .get(&entry.hash())
+        .and_then(|value| value.as_deref());
+    let entry_path = match options.mode {
+        ExtractMode::RelativePath => build_extract_relative_path(rel_path, relative_root),
+        ExtractMode::AbsolutePath => rel_path.to_path_buf(),
+    };
 
-                    let mut file = open_options.open(&output_path)?;
-                    std::io::copy(reader, &mut file)?;
-                    Ok(())
-                }
-            });
+    if let Some(archive_writer) = &ctx.archive_writer {
+        let written = archive_writer.append(&entry_path, entry.uncompressed_size(), reader)?;
+        ctx.report.succeeded(written);
+        return Ok(());
+    }
 
-        if let Err(error) = result {
-            if should_terminate.load(Ordering::Relaxed) {
-                terminated = true;
-                break;
+    let output_path = ctx.output_root.join(entry_path);
+    let record_done = || {
+        if let Some(journal) = &ctx.journal
+            && let Err(error) = journal.record(pak_index, entry.hash())
+        {
+            log::error!("Failed to write extract journal: {}", error);
+        }
+    };
+    if !options.r#override && output_path.exists() {
+        ctx.report.skipped(
+            entry.hash(),
+            rel_path.to_string_lossy(),
+            "output file already exists",
+        );
+        record_done();
+        return Ok(());
+    }
+
+    if let Some(parent) = output_path.parent()
+        && !parent.exists()
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let outcome = if options.skip_unchanged() && options.r#override {
+        write_if_changed(reader, &output_path, entry.uncompressed_size())?
+    } else {
+        let mut open_options = std::fs::OpenOptions::new();
+        open_options.write(true).create(true);
+
+        if options.r#override {
+            open_options.truncate(true);
+        } else {
+            open_options.create_new(true);
+        }
+
+        let mut file = open_options.open(&output_path)?;
+        WriteOutcome::Written(std::io::copy(reader, &mut file)?)
+    };
+
+    // convert before recording the entry, so a failed conversion is its only outcome and a
+    // resumed run retries it
+    let entry_path = rel_path.to_string_lossy();
+    let converted = match convert_extracted_file(
+        &options.converters,
+        &entry_path,
+        entry.hash(),
+        &output_path,
+    ) {
+        Some(Ok(outputs)) => {
+            if !options.keep_original() {
+                std::fs::remove_file(&output_path)?;
             }
-            eprintln!("Error unpacking pak: {}", error);
+            outputs.len()
+        }
+        Some(Err(error)) => {
+            return Err(std::io::Error::other(format!("convert: {error}")).into());
         }
+        None => 0,
+    };
+
+    match outcome {
+        WriteOutcome::Unchanged => ctx.report.unchanged(),
+        WriteOutcome::Written(written) => ctx.report.succeeded(written),
     }
+    ctx.report.converted(converted);
+    record_done();
+    Ok(())
+}
 
-    if terminated {
-        progress.error(Error::Terminated.to_string());
-        return Err(Error::Terminated);
+/// Find the active version of an entry, or the one in `belongs_to`.
+fn find_pak_entry(
+    pak_group: &PakGroup,
+    hash: u64,
+    belongs_to: Option<PakId>,
+) -> Result<(&Pak, &CorePakEntry)> {
+    if pak_group.paks().is_empty() {
+        return Err(Error::NoPaksLoaded);
     }
 
-    progress.work_finished();
+    if let Some(pak_id) = belongs_to {
+        let pak = pak_group
+            .get_pak(&pak_id)
+            .ok_or(Error::PakIdNotFound(pak_id))?;
+        return pak
+            .find_entry(hash)
+            .map(|entry| (pak, entry))
+            .ok_or_else(|| Error::PakEntryNotFound(format!("{hash:016X}")));
+    }
+
+    pak_group
+        .paks()
+        .iter()
+        .rev()
+        .find_map(|pak| pak.find_entry(hash).map(|entry| (pak, entry)))
+        .ok_or_else(|| Error::PakEntryNotFound(format!("{hash:016X}")))
+}
+
+/// Unpack an entry to a file, creating its parent dir.
+fn write_entry_file(pakfile: &PakFile, entry: &CorePakEntry, output_path: &Path) -> Result<()> {
+    let mut entry_reader = pakfile.open_entry(entry)?;
+    if let Some(file_dir) = output_path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        && !file_dir.exists()
+    {
+        std::fs::create_dir_all(file_dir)?;
+    }
+    let mut file = File::create(output_path)?;
+    std::io::copy(&mut entry_reader, &mut file)?;
     Ok(())
 }
 
+/// Run the first converter handling an extracted file, with the format handler of the entry.
+///
+/// Returns `None` if no converter handles the entry.
+fn convert_extracted_file(
+    converters: &[ExtractConverter],
+    entry_path: &str,
+    hash: u64,
+    file_path: &Path,
+) -> Option<Result<Vec<PathBuf>>> {
+    let handler = FormatRegistry::get().find_by_path(entry_path)?;
+    let format = converters
+        .iter()
+        .find_map(|converter| converter.export_format(handler.id()))
+        .filter(|format| handler.supports_conversion(format))?;
+    Some(handler.convert_file(file_path, entry_path, hash, format))
+}
+
 impl PakService {
     pub fn get_header_raw(path: impl AsRef<Path>) -> Result<PakMetadata> {
         let path = path.as_ref();
@@ -830,6 +1376,15 @@ fn collect_manifest_groups(
 
         let root_path = source_path.to_path_buf();
 
+        if source_path.is_file() && archive::is_zip_archive(source_path) {
+            collect_archive_manifests(
+                &mut manifest_groups,
+                source_path,
+                allow_file_name_as_path_hash,
+            )?;
+            continue;
+        }
+
         if source_path.is_file() {
             let mut magic = [0; 4];
             let mut file = File::open(source_path)?;
@@ -841,27 +1396,7 @@ fn collect_manifest_groups(
                 });
             }
 
-            let header = PakService::get_header_raw(source_path)?;
-            for entry in header.entries() {
-                let hash = entry.hash();
-                let target_key = build_target_key(hash);
-                manifest_groups
-                    .entry(target_key.clone())
-                    .or_default()
-                    .push(FileManifest {
-                        hash,
-                        target_key,
-                        display_path: None,
-                        source_id: format!("{}#{:016X}", source_path.display(), hash),
-                        source_label: format!("{}:{:016X}", source_path.display(), hash),
-                        size: entry.uncompressed_size(),
-                        modified_timestamp_ms: None,
-                        source: ManifestSource::PakEntry {
-                            pak_path: source_path.to_path_buf(),
-                            entry_hash: hash,
-                        },
-                    });
-            }
+            collect_pak_manifests(&mut manifest_groups, source_path, source_path)?;
             continue;
         }
 
@@ -913,6 +1448,85 @@ fn collect_manifest_groups(
     Ok(manifest_groups)
 }
 
+/// Add all entries of a pak file.
+///
+/// `label_path` is shown to the user, `pak_path` is the real file to read from.
+fn collect_pak_manifests(
+    manifest_groups: &mut IndexMap<String, Vec<FileManifest>>,
+    pak_path: &Path,
+    label_path: &Path,
+) -> Result<()> {
+    let header = PakService::get_header_raw(pak_path)?;
+    for entry in header.entries() {
+        let hash = entry.hash();
+        let target_key = build_target_key(hash);
+        manifest_groups
+            .entry(target_key.clone())
+            .or_default()
+            .push(FileManifest {
+                hash,
+                target_key,
+                display_path: None,
+                source_id: format!("{}#{:016X}", label_path.display(), hash),
+                source_label: format!("{}:{:016X}", label_path.display(), hash),
+                size: entry.uncompressed_size(),
+                modified_timestamp_ms: None,
+                source: ManifestSource::PakEntry {
+                    pak_path: pak_path.to_path_buf(),
+                    entry_hash: hash,
+                },
+            });
+    }
+
+    Ok(())
+}
+
+/// Add all paks and loose files of a zip mod archive.
+fn collect_archive_manifests(
+    manifest_groups: &mut IndexMap<String, Vec<FileManifest>>,
+    archive_path: &Path,
+    allow_file_name_as_path_hash: bool,
+) -> Result<()> {
+    let archive = ModArchive::open(archive_path)?;
+    let archive_display_path = archive_path.display().to_string();
+
+    for entry_name in archive.paks() {
+        let spill_path = archive.spill_pak(entry_name)?;
+        let label_path = archive::archive_virtual_path(&archive_display_path, entry_name);
+        collect_pak_manifests(manifest_groups, &spill_path, Path::new(&label_path))?;
+    }
+
+    let modified_timestamp_ms = get_path_modified_timestamp_ms(archive_path);
+    for file in archive.loose_files() {
+        let resolved_target = resolve_loose_file_target(
+            &file.relative_path,
+            Path::new(&file.entry_name),
+            allow_file_name_as_path_hash,
+        );
+        let label_path = archive::archive_virtual_path(&archive_display_path, &file.entry_name);
+
+        log::debug!("Adding archive file: {}", label_path);
+        manifest_groups
+            .entry(resolved_target.target_key.clone())
+            .or_default()
+            .push(FileManifest {
+                hash: resolved_target.hash,
+                target_key: resolved_target.target_key,
+                display_path: Some(resolved_target.display_path),
+                source_id: label_path.clone(),
+                source_label: label_path,
+                size: file.size,
+                modified_timestamp_ms,
+                source: ManifestSource::ArchiveEntry {
+                    archive_path: archive_path.to_path_buf(),
+                    entry_name: file.entry_name.clone(),
+                },
+            });
+    }
+
+    Ok(())
+}
+
 fn build_pack_conflicts(
     manifest_groups: &IndexMap<String, Vec<FileManifest>>,
 ) -> Vec<PackConflictInfo> {
@@ -1114,7 +1728,176 @@ fn path_string_components(path: &str) -> Vec<&str> {
 
 #[cfg(test)]
 mod tests {
+    use tauri::ipc::Channel;
+
     use super::*;
+    use crate::pak::{
+        convert::ConvertTextureFormat,
+        test_util::{name_table, new_pak_id, write_pak},
+    };
+
+    fn extract_options(output_path: &Path) -> ExtractOptions {
+        ExtractOptions {
+            output_path: output_path.to_string_lossy().to_string(),
+            r#override: true,
+            mode: ExtractMode::AbsolutePath,
+            extract_all: true,
+            extract_files: vec![],
+            languages: None,
+            with_streaming: None,
+            filter: None,
+            archive: None,
+            save_report: None,
+            skip_unchanged: None,
+            resume: None,
+            workers: None,
+            converters: vec![],
+            keep_original: None,
+        }
+    }
+
+    fn extract_context(options: ExtractOptions, paths: &[&str]) -> ExtractContext {
+        let mut pak_group = PakGroup::new();
+        pak_group.load_name_layer(BASE_LAYER_NAME, None, name_table(paths));
+        ExtractContext {
+            output_root: PathBuf::from(&options.output_path),
+            file_name_table: Arc::new(pak_group.file_name_table().unwrap().clone()),
+            relative_roots: HashMap::new(),
+            options,
+            archive_writer: None,
+            journal: None,
+            report: JobReportCollector::default(),
+            should_terminate: Arc::new(AtomicBool::new(false)),
+            progress: UnpackProgressChannel::new(Channel::new(|_| Ok(()))),
+        }
+    }
+
+    #[test]
+    fn failed_conversion_is_the_only_outcome_of_an_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = "natives/STM/a.tex.1";
+        let pakfile = write_pak(&dir.path().join("a.pak"), &[(path, b"not a texture")]);
+        let entry = &pakfile.metadata().entries()[0];
+
+        let mut options = extract_options(&dir.path().join("out"));
+        options.converters = vec![ExtractConverter::Texture {
+            format: ConvertTextureFormat::Png,
+        }];
+        options.keep_original = Some(false);
+        let ctx = extract_context(options, &[path]);
+
+        let mut reader = pakfile.open_entry(entry).unwrap();
+        let result = extract_entry(&ctx, 0, entry, Path::new(path), &mut reader);
+        assert!(result.unwrap_err().to_string().contains("convert"));
+        // the caller records the error, the entry is neither succeeded nor converted
+        let report = ctx.report.into_report();
+        assert_eq!(report.succeeded, 0);
+        assert_eq!(report.converted, 0);
+        assert_eq!(report.bytes_written, 0);
+        assert!(report.failed.is_empty());
+    }
+
+    #[test]
+    fn plan_takes_entries_from_the_last_pak_in_pak_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let pak_a = write_pak(
+            &dir.path().join("a.pak"),
+            &[("natives/STM/a.txt", b"a"), ("natives/STM/b.txt", b"old")],
+        );
+        let pak_b = write_pak(&dir.path().join("b.pak"), &[("natives/STM/b.txt", b"new")]);
+        let sources = [pak_a, pak_b]
+            .into_iter()
+            .map(|pakfile| {
+                let source = ExtractSource {
+                    path: String::new(),
+                    pakfile: Arc::new(pakfile),
+                };
+                (new_pak_id(), source)
+            })
+            .collect::<Vec<_>>();
+        let names = extract_context(extract_options(dir.path()), &[]).file_name_table;
+
+        let (targets, resumed) = plan_extract_targets(
+            &sources,
+            &names,
+            &extract_options(dir.path()),
+            None,
+            &hashbrown::HashSet::new(),
+        );
+        let targets = targets
+            .iter()
+            .map(|(pak_index, entry)| (*pak_index, entry.hash()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            targets,
+            [
+                (0, "natives/STM/a.txt".hash_mixed()),
+                (1, "natives/STM/b.txt".hash_mixed())
+            ]
+        );
+        assert_eq!(resumed, 0);
+
+        // entries done by an interrupted run are counted, not planned again
+        let done = hashbrown::HashSet::from([(1, "natives/STM/b.txt".hash_mixed())]);
+        let (targets, resumed) =
+            plan_extract_targets(&sources, &names, &extract_options(dir.path()), None, &done);
+        assert_eq!(targets.len(), 1);
+        assert_eq!(resumed, 1);
+    }
+
+    #[test]
+    fn parallel_extraction_reports_each_failure_once() {
+        let dir = tempfile::tempdir().unwrap();
+        // more entries than a chunk, so chunks span both paks
+        let paths = (0..EXTRACT_CHUNK_SIZE + 20)
+            .map(|index| format!("natives/STM/{index}.txt"))
+            .collect::<Vec<_>>();
+        let (first, second) = paths.split_at(EXTRACT_CHUNK_SIZE - 10);
+        fn files(paths: &[String]) -> Vec<(&str, &[u8])> {
+            paths
+                .iter()
+                .map(|path| (path.as_str(), path.as_bytes()))
+                .collect()
+        }
+        let mut files_b = files(second);
+        files_b.push((&first[0], b"newer"));
+
+        let mut pak_group = PakGroup::new();
+        for (name, files) in [("a.pak", files(first)), ("b.pak", files_b)] {
+            let path = dir.path().join(name);
+            let pakfile = write_pak(&path, &files);
+            pak_group.add_pak(Pak::new(&path.to_string_lossy(), pakfile));
+        }
+        let names = paths.iter().map(String::as_str).collect::<Vec<_>>();
+        pak_group.load_name_layer(BASE_LAYER_NAME, None, name_table(&names));
+
+        // the output of one entry is a directory, so writing it fails
+        let output = dir.path().join("out");
+        std::fs::create_dir_all(output.join(&second[0])).unwrap();
+        let mut options = extract_options(&output);
+        options.workers = Some(3);
+        assert_eq!(options.workers(), 3);
+
+        let progress = UnpackProgressChannel::new(Channel::new(|_| Ok(())));
+        let report = unpack_optional_blocking(
+            Arc::new(Mutex::new(pak_group)),
+            options,
+            None,
+            Arc::new(AtomicBool::new(false)),
+            progress.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(report.succeeded, paths.len() - 1);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].path, second[0]);
+        assert_eq!(progress.finish_count(), paths.len() as u32);
+        assert_eq!(std::fs::read(output.join(&first[0])).unwrap(), b"newer");
+        assert_eq!(
+            std::fs::read(output.join(&first[1])).unwrap(),
+            first[1].as_bytes()
+        );
+    }
 
     #[test]
     fn test_get_relative_path_with_parent() {
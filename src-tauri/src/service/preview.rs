@@ -9,19 +9,26 @@ use std::{
     },
 };
 
-use parking_lot::Mutex;
 use re_tex::tex::Tex;
 use ree_pak_core::utf16_hash::Utf16HashExt;
+use serde::Serialize;
 
-use crate::pak::{ExtractFileInfo, PakId};
+use crate::pak::{
+    ExtractFileInfo, PakId,
+    file_type::FileType,
+    report::{JobReport, JobReportCollector},
+};
 
 use crate::{
     TEMP_DIR_NAME,
     channel::TextureExportProgressChannel,
     error::{Error, Result},
     get_local_dir,
-    path_components::PathComponents,
-    service::pak::PakService,
+    service::{
+        format::{FormatHandler, FormatRegistry, export_file_name},
+        pak::{PakService, PreviewEntry},
+        preview_cache::PreviewCache,
+    },
 };
 
 static PREVIEW_SERVICE: OnceLock<PreviewService> = OnceLock::new();
@@ -29,8 +36,8 @@ static PREVIEW_SERVICE: OnceLock<PreviewService> = OnceLock::new();
 /// Preview service.
 pub struct PreviewService {
     pak_service: &'static PakService,
+    preview_cache: &'static PreviewCache,
     temp_dir: PathBuf,
-    preview_files: Mutex<HashMap<u64, PathBuf>>,
     export_running: Arc<AtomicBool>,
     should_terminate: Arc<AtomicBool>,
 }
@@ -45,8 +52,8 @@ impl PreviewService {
 
         Ok(PREVIEW_SERVICE.get_or_init(|| Self {
             pak_service: PakService::get(),
+            preview_cache: PreviewCache::get(),
             temp_dir,
-            preview_files: Mutex::new(HashMap::new()),
             export_running: Arc::new(AtomicBool::new(false)),
             should_terminate: Arc::new(AtomicBool::new(false)),
         }))
@@ -58,124 +65,195 @@ impl PreviewService {
 
     /// Get preview file path from Pak.
     ///
-    /// If preview not found, create a new one.
-    pub async fn get_preview_file(&self, hash: u64) -> Result<PathBuf> {
+    /// Uses the active version, or the version stored in `belongs_to` if set.
+    /// The format handler is picked by extension, or by the type detected from the magic.
+    /// Previews are kept in the preview cache, a new one is only created for a new entry version.
+    pub async fn get_preview_file(&self, hash: u64, belongs_to: Option<PakId>) -> Result<PathBuf> {
         // get entry path
-        let pak_entry_path = {
-            let pak_group = self.pak_service.pak_group();
-            let pak_group = pak_group.lock();
-            let Some(file_name_table) = pak_group.file_name_table() else {
-                return Err(Error::MissingFileList);
-            };
-
-            file_name_table
-                .get_file_name(hash)
-                .map(|p| p.to_string().unwrap())
-                .ok_or_else(|| Error::PakEntryNotFound(hash.to_string()))?
-        };
+        let pak_entry_path = self
+            .pak_service
+            .pak_group()
+            .lock()
+            .resolve_entry_path(hash, belongs_to)?;
 
         // check file type
-        let file_type = preview_file_type_from_path(&pak_entry_path).ok_or_else(|| {
-            Error::PreviewFileNotSupported(preview_type_error_hint(&pak_entry_path))
-        })?;
-
-        // if preview file exists, return it
-        if let Some(path) = self.get_existing_preview_file(&pak_entry_path) {
-            return Ok(path);
-        }
+        let handler = self
+            .find_format_handler(&pak_entry_path, hash, belongs_to)
+            .filter(|handler| handler.can_preview())
+            .ok_or_else(|| {
+                Error::PreviewFileNotSupported(preview_type_error_hint(&pak_entry_path))
+            })?;
 
-        // create new preview file
-        let pak_service = self.pak_service;
-        let pak_entry_path1 = pak_entry_path.to_string();
+        let preview_entry = self.pak_service.preview_entry(hash, belongs_to)?;
+        let preview_cache = self.preview_cache;
         let temp_dir = self.temp_dir.clone();
 
-        let path = tokio::task::spawn_blocking(move || -> Result<PathBuf> {
-            let entry_path = Path::new(&pak_entry_path1);
-            let file_name = entry_path.file_name().unwrap_or_default();
-            let raw_output_path = temp_dir.join(format!(
-                "{}-{}",
-                pak_entry_path1.hash_mixed(),
-                file_name.to_string_lossy()
-            ));
-
-            // unpack raw file
-            pak_service.unpack_file(&pak_entry_path1, &raw_output_path)?;
-
-            // convert to preview format
-            let path = match file_type {
-                PreviewFileType::Tex => {
-                    let png_path = raw_output_path.with_extension("png");
-                    tex_to_png(&raw_output_path, &png_path)?;
-                    png_path
-                }
-            };
-
-            Ok(path)
+        tokio::task::spawn_blocking(move || -> Result<PathBuf> {
+            let name = format!("preview.{}", handler.preview_extension());
+            preview_cache.get_or_create(&preview_entry.cache_key, &name, |output_path| {
+                with_raw_file(&temp_dir, &pak_entry_path, &preview_entry, |raw_path| {
+                    handler.preview(raw_path, output_path)
+                })
+            })
         })
         .await
-        .map_err(|e| Error::Internal(e.to_string()))??;
-
-        // store preview file
-        let id = pak_entry_path.hash_mixed();
-        self.preview_files.lock().insert(id, path.clone());
-
-        Ok(path)
+        .map_err(|e| Error::Internal(e.to_string()))?
     }
 
-    fn get_existing_preview_file(&self, pak_entry_path: &str) -> Option<PathBuf> {
-        let id = pak_entry_path.hash_mixed();
-
-        if let Some(path) = self.preview_files.lock().get(&id) {
-            if !path.exists() {
-                return None;
-            }
-            return Some(path.clone());
-        }
+    /// Describe an entry with the metadata of its format handler.
+    pub async fn get_file_metadata(
+        &self,
+        hash: u64,
+        belongs_to: Option<PakId>,
+    ) -> Result<FileMetadata> {
+        let pak_entry_path = self
+            .pak_service
+            .pak_group()
+            .lock()
+            .resolve_entry_path(hash, belongs_to)?;
+        let handler = self
+            .find_format_handler(&pak_entry_path, hash, belongs_to)
+            .filter(|handler| handler.has_metadata())
+            .ok_or_else(|| Error::FormatNotSupported(preview_type_error_hint(&pak_entry_path)))?;
+
+        let preview_entry = self.pak_service.preview_entry(hash, belongs_to)?;
+        let temp_dir = self.temp_dir.clone();
+        tokio::task::spawn_blocking(move || -> Result<FileMetadata> {
+            let metadata = with_raw_file(&temp_dir, &pak_entry_path, &preview_entry, |raw| {
+                handler.metadata(raw, &pak_entry_path)
+            })?;
+            Ok(FileMetadata {
+                format: handler.id(),
+                path: pak_entry_path,
+                metadata,
+            })
+        })
+        .await
+        .map_err(|e| Error::Internal(e.to_string()))?
+    }
 
-        None
+    fn find_format_handler(
+        &self,
+        pak_entry_path: &str,
+        hash: u64,
+        belongs_to: Option<PakId>,
+    ) -> Option<&'static dyn FormatHandler> {
+        FormatRegistry::get().detect(pak_entry_path, || {
+            self.pak_service
+                .pak_group()
+                .lock()
+                .detect_entry_type(hash, belongs_to)
+        })
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum PreviewFileType {
-    Tex,
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileMetadata {
+    /// Id of the format handler.
+    pub format: &'static str,
+    pub path: String,
+    pub metadata: serde_json::Value,
+}
+
+/// Unpack an entry into the temp dir for `f`, the file is removed afterwards.
+fn with_raw_file<T>(
+    temp_dir: &Path,
+    pak_entry_path: &str,
+    preview_entry: &PreviewEntry,
+    f: impl FnOnce(&Path) -> Result<T>,
+) -> Result<T> {
+    // unique per call, the same entry may be unpacked by concurrent previews
+    let file_name = Path::new(pak_entry_path).file_name().unwrap_or_default();
+    let raw_output_path = temp_dir.join(format!(
+        "{}-{}-{}",
+        pak_entry_path.hash_mixed(),
+        uuid::Uuid::new_v4().simple(),
+        file_name.to_string_lossy()
+    ));
+
+    preview_entry.unpack(&raw_output_path)?;
+    let result = f(&raw_output_path);
+    let _ = std::fs::remove_file(&raw_output_path);
+    result
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum TextureExportFormat {
+enum TextureExportFormat {
     Dds,
     Png,
 }
 
-impl PreviewFileType {
-    /// Get preview file type from extension.
-    ///
-    /// Example: tex -> Tex
+impl TextureExportFormat {
     fn from_extension(extension: &str) -> Option<Self> {
         match extension {
-            "tex" => Some(Self::Tex),
+            "dds" => Some(Self::Dds),
+            "png" => Some(Self::Png),
             _ => None,
         }
     }
 }
 
-impl TextureExportFormat {
-    fn extension(self) -> &'static str {
-        match self {
-            Self::Dds => "dds",
-            Self::Png => "png",
-        }
+/// `.tex` textures, previewed as PNG.
+pub struct TextureFormatHandler;
+
+impl FormatHandler for TextureFormatHandler {
+    fn id(&self) -> &'static str {
+        "texture"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["tex"]
+    }
+
+    fn file_types(&self) -> &'static [FileType] {
+        &[FileType::Tex]
+    }
+
+    fn can_preview(&self) -> bool {
+        true
+    }
+
+    fn export_formats(&self) -> &'static [&'static str] {
+        &["png", "dds"]
+    }
+
+    fn has_metadata(&self) -> bool {
+        true
+    }
+
+    fn preview(&self, source: &Path, output_path: &Path) -> Result<()> {
+        tex_to_png(source, output_path)
+    }
+
+    fn export_reader(&self, reader: &mut dyn Read, output_path: &Path, format: &str) -> Result<()> {
+        let format = TextureExportFormat::from_extension(format)
+            .ok_or_else(|| self.unsupported_format(format))?;
+        export_tex_reader(reader, output_path, format)?;
+        Ok(())
+    }
+
+    fn metadata(&self, source: &Path, _entry_path: &str) -> Result<serde_json::Value> {
+        let mut reader = BufReader::new(File::open(source)?);
+        let tex = Tex::from_reader(&mut reader)?;
+        Ok(serde_json::json!({
+            "width": tex.header.width,
+            "height": tex.header.height,
+            "mipmapCount": tex.header.mipmap_count,
+        }))
     }
 }
 
 impl PreviewService {
+    /// Export entries with their format handlers, e.g. textures to `png`.
     pub async fn export_texture_files(
         &self,
-        format: TextureExportFormat,
+        format: String,
         output_dir: impl AsRef<Path>,
         files: &[ExtractFileInfo],
+        save_report: bool,
         progress: TextureExportProgressChannel,
-    ) -> Result<usize> {
+    ) -> Result<JobReport> {
         if self.export_running.swap(true, Ordering::SeqCst) {
             return Err(Error::TextureExportAlreadyRunning);
         }
@@ -194,6 +272,7 @@ impl PreviewService {
                 format,
                 &output_dir,
                 &files,
+                save_report,
                 should_terminate,
                 progress,
             )
@@ -212,109 +291,95 @@ impl PreviewService {
 
 fn export_texture_files_blocking(
     pak_service: &PakService,
-    format: TextureExportFormat,
+    format: String,
     output_dir: &Path,
     files: &[ExtractFileInfo],
+    save_report: bool,
     should_terminate: Arc<AtomicBool>,
     progress: TextureExportProgressChannel,
-) -> Result<usize> {
+) -> Result<JobReport> {
     if !output_dir.exists() {
         std::fs::create_dir_all(output_dir)?;
     }
 
     progress.work_start(files.len() as u32);
 
-    let export_plan = build_texture_export_plan(pak_service, output_dir, files, format)?;
-    let mut exported = 0usize;
+    let export_plan = build_texture_export_plan(pak_service, output_dir, files, &format)?;
+    let report = JobReportCollector::default();
 
     for source in &export_plan.sources {
-        if should_terminate.load(Ordering::Relaxed) {
-            progress.error(Error::Terminated.to_string());
-            return Err(Error::Terminated);
-        }
-
         let Some(task_map) = export_plan.tasks_by_pak.get(&source.id) else {
             continue;
         };
 
-        let target_hashes = Arc::new(task_map.keys().copied().collect::<HashSet<_>>());
-        let task_map = Arc::new(task_map.clone());
-        let progress1 = progress.clone();
-        let report = source
-            .pakfile
-            .extractor_callback()
-            .file_name_table_arc(export_plan.file_name_table.clone())
-            .skip_unknown(false)
-            .continue_on_error(true)
-            .cancel_flag(should_terminate.clone())
-            .filter({
-                let target_hashes = Arc::clone(&target_hashes);
-                move |entry, _path| target_hashes.contains(&entry.hash())
-            })
-            .on_event(move |event| {
-                if let ree_pak_core::extract::ExtractEvent::FileDone { path, .. } = event {
-                    progress1.file_done(path.to_string_lossy().as_ref());
-                }
-            })
-            .run_with_reader({
-                let task_map = Arc::clone(&task_map);
-                move |entry, _rel_path, reader| {
-                    let Some(task) = task_map.get(&entry.hash()) else {
-                        return Err(std::io::Error::other(format!(
-                            "missing export task for {:016X}",
-                            entry.hash()
-                        ))
-                        .into());
-                    };
-
-                    if let Some(parent) = task.output_path.parent()
-                        && !parent.exists()
-                    {
-                        std::fs::create_dir_all(parent)?;
-                    }
-
-                    match task.file_type {
-                        PreviewFileType::Tex => {
-                            export_tex_reader(reader, &task.output_path, format)?;
-                            Ok(())
-                        }
-                    }
-                }
-            })?;
-
-        exported += report.extracted;
-
-        for (hash, path, error) in report.errors {
-            log::error!(
-                "texture export skipped: hash={:016X}, entry_path={}, error={}",
-                hash,
-                path.display(),
-                error
-            );
-        }
+        for entry in source.pakfile.metadata().entries() {
+            let Some(task) = task_map.get(&entry.hash()) else {
+                continue;
+            };
+            if should_terminate.load(Ordering::Relaxed) {
+                progress.error(Error::Terminated.to_string());
+                return Err(Error::Terminated);
+            }
 
-        if should_terminate.load(Ordering::Relaxed) {
-            progress.error(Error::Terminated.to_string());
-            return Err(Error::Terminated);
+            match export_texture_entry(&source.pakfile, entry, task, &format) {
+                Ok(written) => report.succeeded(written),
+                Err(error) => {
+                    log::error!(
+                        "texture export failed: hash={:016X}, entry_path={}, error={}",
+                        entry.hash(),
+                        task.entry_path,
+                        error
+                    );
+                    report.failed(entry.hash(), &task.entry_path, error.to_string());
+                }
+            }
+            progress.file_done(&task.entry_path);
         }
     }
 
+    let report = report.into_report();
     log::info!(
-        "texture export finished: requested={}, exported={}, skipped={}",
+        "texture export finished: requested={}, exported={}, failed={}",
         export_plan.task_count,
-        exported,
-        export_plan.task_count.saturating_sub(exported)
+        report.succeeded,
+        report.failed.len()
     );
+    if save_report {
+        let report_path = JobReport::path_for_output(output_dir, false);
+        if let Err(error) = report.save_json(&report_path) {
+            log::error!("Failed to save texture export report: {}", error);
+        }
+    }
 
-    progress.work_finished();
+    progress.work_finished(report.clone());
 
-    Ok(exported)
+    Ok(report)
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 struct TextureExportTask {
+    entry_path: String,
     output_path: PathBuf,
-    file_type: PreviewFileType,
+    handler: &'static dyn FormatHandler,
+}
+
+/// Export one planned entry, returns the size of the written file.
+fn export_texture_entry(
+    pakfile: &ree_pak_core::pakfile::PakFile,
+    entry: &ree_pak_core::pak::PakEntry,
+    task: &TextureExportTask,
+    format: &str,
+) -> Result<u64> {
+    if let Some(parent) = task.output_path.parent()
+        && !parent.exists()
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut reader = pakfile.open_entry(entry)?;
+    task.handler
+        .export_reader(&mut reader, &task.output_path, format)?;
+    Ok(std::fs::metadata(&task.output_path)?.len())
 }
 
 struct TextureExportSource {
@@ -323,7 +388,6 @@ struct TextureExportSource {
 }
 
 struct TextureExportPlan {
-    file_name_table: Arc<ree_pak_core::filename::FileNameTable>,
     sources: Vec<TextureExportSource>,
     tasks_by_pak: HashMap<PakId, HashMap<u64, TextureExportTask>>,
     task_count: usize,
@@ -333,37 +397,48 @@ fn build_texture_export_plan(
     pak_service: &PakService,
     output_dir: &Path,
     files: &[ExtractFileInfo],
-    format: TextureExportFormat,
+    format: &str,
 ) -> Result<TextureExportPlan> {
-    let (file_name_table, sources) = {
+    let sources = {
         let pak_group = pak_service.pak_group();
         let pak_group = pak_group.lock();
-        let Some(file_name_table) = pak_group.file_name_table() else {
+        if pak_group.file_name_table().is_none() {
             return Err(Error::MissingFileList);
-        };
+        }
 
-        let sources = pak_group
+        pak_group
             .paks()
             .iter()
             .map(|pak| TextureExportSource {
                 id: pak.id,
                 pakfile: Arc::clone(&pak.pakfile),
             })
-            .collect::<Vec<_>>();
-
-        (Arc::new(file_name_table.clone()), sources)
+            .collect::<Vec<_>>()
     };
 
     let mut used_paths = HashSet::new();
     let mut tasks_by_pak = HashMap::<PakId, HashMap<u64, TextureExportTask>>::new();
 
     for file in files {
-        let entry_path = file_name_table
-            .get_file_name(file.hash.hash_u64())
-            .map(|path| path.to_string().unwrap())
-            .ok_or_else(|| Error::PakEntryNotFound(file.hash.hash_u64().to_string()))?;
-        let file_type = preview_file_type_from_path(&entry_path)
-            .ok_or_else(|| Error::PreviewFileNotSupported(preview_type_error_hint(&entry_path)))?;
+        let hash = file.hash.hash_u64();
+        let entry_path = pak_service
+            .pak_group()
+            .lock()
+            .resolve_entry_path(hash, Some(file.belongs_to))?;
+        let handler = FormatRegistry::get()
+            .detect(&entry_path, || {
+                pak_service
+                    .pak_group()
+                    .lock()
+                    .detect_entry_type(hash, Some(file.belongs_to))
+            })
+            .filter(|handler| handler.supports_export(format))
+            .ok_or_else(|| {
+                Error::FormatNotSupported(format!(
+                    "{} to {format}",
+                    preview_type_error_hint(&entry_path)
+                ))
+            })?;
 
         let output_path = output_dir.join(build_texture_output_path(&entry_path, file, format));
         let output_path = ensure_unique_path(output_path, file.hash.hash_u64(), &mut used_paths);
@@ -371,30 +446,26 @@ fn build_texture_export_plan(
         tasks_by_pak.entry(file.belongs_to).or_default().insert(
             file.hash.hash_u64(),
             TextureExportTask {
+                entry_path,
                 output_path,
-                file_type,
+                handler,
             },
         );
     }
 
     Ok(TextureExportPlan {
-        file_name_table,
         sources,
         tasks_by_pak,
         task_count: files.len(),
     })
 }
 
-fn build_texture_output_path(
-    entry_path: &str,
-    file: &ExtractFileInfo,
-    format: TextureExportFormat,
-) -> PathBuf {
+fn build_texture_output_path(entry_path: &str, file: &ExtractFileInfo, format: &str) -> PathBuf {
     let relative_path = build_relative_output_path(entry_path, file.relative_root.as_deref());
     let output_file_name = relative_path
         .file_name()
-        .map(|name| build_texture_file_name(&name.to_string_lossy(), format, file.hash.hash_u64()))
-        .unwrap_or_else(|| format!("{:016X}.{}", file.hash.hash_u64(), format.extension()));
+        .map(|name| export_file_name(&name.to_string_lossy(), format, file.hash.hash_u64()))
+        .unwrap_or_else(|| format!("{:016X}.{format}", file.hash.hash_u64()));
 
     let mut output_path = relative_path;
     output_path.set_file_name(output_file_name);
@@ -500,38 +571,12 @@ fn path_string_components(path: &str) -> Vec<&str> {
         .collect()
 }
 
-fn build_texture_file_name(file_name: &str, format: TextureExportFormat, hash: u64) -> String {
-    let base = PathComponents::parse(file_name)
-        .and_then(|components| {
-            Path::new(components.raw_path())
-                .file_stem()
-                .map(|stem| stem.to_string_lossy().to_string())
-        })
-        .filter(|stem| !stem.is_empty())
-        .unwrap_or_else(|| fallback_texture_file_stem(file_name, hash));
-
-    format!("{base}.{}", format.extension())
-}
-
 fn path_file_name(path: &str) -> &str {
     path.rsplit(['/', '\\'])
         .find(|segment| !segment.is_empty())
         .unwrap_or(path)
 }
 
-fn preview_file_type_from_path(path: &str) -> Option<PreviewFileType> {
-    let components = PathComponents::parse(path)?;
-    PreviewFileType::from_extension(components.extension()?)
-}
-
-fn fallback_texture_file_stem(file_name: &str, hash: u64) -> String {
-    Path::new(file_name)
-        .file_stem()
-        .map(|stem| stem.to_string_lossy().to_string())
-        .filter(|stem| !stem.is_empty())
-        .unwrap_or_else(|| format!("{hash:016X}"))
-}
-
 fn preview_type_error_hint(path: &str) -> String {
     let file_name = path_file_name(path);
     file_name
@@ -560,25 +605,39 @@ mod tests {
 
     #[test]
     fn test_build_texture_file_name_replaces_tex_suffix() {
-        let name = build_texture_file_name("foo.tex.241106027", TextureExportFormat::Png, 1);
+        let name = export_file_name("foo.tex.241106027", "png", 1);
         assert_eq!(name, "foo.png");
     }
 
+    fn preview_handler_id(path: &str) -> Option<&'static str> {
+        FormatRegistry::get()
+            .find_by_path(path)
+            .filter(|handler| handler.can_preview())
+            .map(|handler| handler.id())
+    }
+
     #[test]
     fn test_preview_file_type_supports_numeric_suffix() {
-        let file_type = preview_file_type_from_path("foo.tex.241106027");
-        assert_eq!(file_type, Some(PreviewFileType::Tex));
+        assert_eq!(preview_handler_id("foo.tex.241106027"), Some("texture"));
     }
 
     #[test]
     fn test_preview_file_type_supports_version_and_tag_suffix() {
-        let file_type = preview_file_type_from_path("foo.tex.241106027.X64");
-        assert_eq!(file_type, Some(PreviewFileType::Tex));
+        assert_eq!(preview_handler_id("foo.tex.241106027.X64"), Some("texture"));
+    }
+
+    #[test]
+    fn test_preview_file_type_supports_detected_extension() {
+        assert_eq!(
+            preview_handler_id("_Unknown/10015D55056456A1.tex"),
+            Some("texture")
+        );
+        assert_eq!(preview_handler_id("foo.bnk.2"), None);
     }
 
     #[test]
     fn test_build_texture_file_name_strips_version_and_tag_suffixes() {
-        let name = build_texture_file_name("foo.tex.241106027.X64", TextureExportFormat::Png, 1);
+        let name = export_file_name("foo.tex.241106027.X64", "png", 1);
         assert_eq!(name, "foo.png");
     }
 }
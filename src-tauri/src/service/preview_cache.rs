@@ -0,0 +1,584 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use parking_lot::Mutex;
+use ree_pak_core::utf16_hash::Utf16HashExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    get_local_dir,
+};
+
+const PREVIEW_CACHE_DIR_NAME: &str = "preview-cache";
+const INDEX_FILE_NAME: &str = "index.json";
+const DEFAULT_SIZE_LIMIT: u64 = 1024 * 1024 * 1024;
+/// Minimum time between index saves for new files and access times.
+const INDEX_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+/// Files returned within this time are not evicted, the caller may still be opening them.
+const EVICTION_GRACE: Duration = Duration::from_secs(60);
+
+static PREVIEW_CACHE: OnceLock<PreviewCache> = OnceLock::new();
+
+/// Identity of a pak entry version.
+///
+/// Files cached for an entry are no longer found once the pak is modified or the entry changes,
+/// and are evicted like any unused file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviewCacheKey {
+    /// Path of the file on disk, the archive for paks loaded from one.
+    pub pak_path: String,
+    /// Modification time of the pak, in ms since epoch.
+    pub pak_modified: u64,
+    pub hash: u64,
+    pub checksum: u64,
+}
+
+impl PreviewCacheKey {
+    pub fn new(pak_path: &str, hash: u64, checksum: u64) -> Self {
+        Self {
+            pak_path: pak_path.to_string(),
+            pak_modified: modified_ms(Path::new(pak_path)).unwrap_or_default(),
+            hash,
+            checksum,
+        }
+    }
+
+    /// Name of a file cached for the entry, `name` tells files of the same entry apart.
+    fn file_name(&self, name: &str) -> String {
+        let key = format!(
+            "{}/{}/{:016X}/{:016X}",
+            self.pak_path, self.pak_modified, self.hash, self.checksum
+        );
+        format!("{:016X}-{}", key.hash_mixed(), sanitize_file_name(name))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewCacheStatus {
+    pub path: String,
+    pub file_count: usize,
+    pub used_bytes: u64,
+    pub size_limit: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheRecord {
+    /// Empty for files recovered from an unreadable index, they are kept until evicted.
+    pak_path: String,
+    pak_modified: u64,
+    size: u64,
+    /// Last use, in ms since epoch.
+    last_access: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheIndex {
+    size_limit: u64,
+    /// Records by file name.
+    files: HashMap<String, CacheRecord>,
+}
+
+impl Default for CacheIndex {
+    fn default() -> Self {
+        Self {
+            size_limit: DEFAULT_SIZE_LIMIT,
+            files: HashMap::new(),
+        }
+    }
+}
+
+impl CacheIndex {
+    fn used_bytes(&self) -> u64 {
+        self.files.values().map(|record| record.size).sum()
+    }
+}
+
+struct IndexState {
+    index: CacheIndex,
+    /// Whether the index changed since it was last saved.
+    dirty: bool,
+    saved_at: Instant,
+}
+
+/// Converted previews and unpacked entries kept across sessions.
+///
+/// Least recently used files are evicted once the cache grows past its size limit.
+pub struct PreviewCache {
+    dir: PathBuf,
+    state: Mutex<IndexState>,
+    eviction_grace: Duration,
+}
+
+impl PreviewCache {
+    /// Open the cache in the local dir.
+    ///
+    /// Falls back to a dir in the system temp dir if it can't be opened, and to a cache whose
+    /// writes fail if neither can, so previews report errors instead of the cache missing.
+    pub fn initialize() -> Result<&'static Self> {
+        let dir = get_local_dir().join(PREVIEW_CACHE_DIR_NAME);
+        let cache = Self::open(dir.clone())
+            .or_else(|e| {
+                log::warn!(
+                    "Failed to open preview cache {}, using the temp dir: {}",
+                    dir.display(),
+                    e
+                );
+                Self::open(fallback_dir())
+            })
+            .unwrap_or_else(|e| {
+                log::error!("Failed to open a preview cache: {}", e);
+                Self::unopened(fallback_dir())
+            });
+        Ok(PREVIEW_CACHE.get_or_init(|| cache))
+    }
+
+    pub fn get() -> &'static Self {
+        PREVIEW_CACHE.get().unwrap()
+    }
+
+    /// Open a cache dir, dropping files of modified paks and files missing from the index.
+    ///
+    /// If the index can't be read, the files in the dir are kept as records of unknown paks
+    /// instead, so a damaged index doesn't empty the cache.
+    fn open(dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+
+        let index_path = dir.join(INDEX_FILE_NAME);
+        let loaded = match std::fs::read(&index_path) {
+            Ok(content) => {
+                serde_json::from_slice::<CacheIndex>(&content).map_err(|e| e.to_string())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(CacheIndex::default()),
+            Err(e) => Err(e.to_string()),
+        };
+        let (mut index, recover) = match loaded {
+            Ok(index) => (index, false),
+            Err(e) => {
+                log::warn!(
+                    "Invalid preview cache index, recovering cached files: {}",
+                    e
+                );
+                (CacheIndex::default(), true)
+            }
+        };
+
+        let mut pak_modified = HashMap::<String, Option<u64>>::new();
+        index.files.retain(|file_name, record| {
+            let pak_valid = record.pak_path.is_empty() || {
+                let current = pak_modified
+                    .entry(record.pak_path.clone())
+                    .or_insert_with(|| modified_ms(Path::new(&record.pak_path)));
+                *current == Some(record.pak_modified)
+            };
+            let valid = pak_valid && dir.join(file_name).is_file();
+            if !valid {
+                let _ = std::fs::remove_file(dir.join(file_name));
+            }
+            valid
+        });
+        for entry in std::fs::read_dir(&dir)?.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if file_name == INDEX_FILE_NAME || index.files.contains_key(&file_name) {
+                continue;
+            }
+            match entry.metadata() {
+                Ok(metadata) if recover && metadata.is_file() && !file_name.ends_with(".part") => {
+                    let record = CacheRecord {
+                        pak_path: String::new(),
+                        pak_modified: 0,
+                        size: metadata.len(),
+                        last_access: metadata
+                            .modified()
+                            .ok()
+                            .and_then(system_time_ms)
+                            .unwrap_or_default(),
+                    };
+                    index.files.insert(file_name, record);
+                }
+                _ => {
+                    let _ = std::fs::remove_file(entry.path());
+                }
+            }
+        }
+
+        let cache = Self {
+            dir,
+            state: Mutex::new(IndexState {
+                index,
+                dirty: true,
+                saved_at: Instant::now(),
+            }),
+            eviction_grace: EVICTION_GRACE,
+        };
+        cache.flush()?;
+        Ok(cache)
+    }
+
+    /// An empty cache of a dir that couldn't be opened, nothing is loaded from or saved to it.
+    fn unopened(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            state: Mutex::new(IndexState {
+                index: CacheIndex::default(),
+                dirty: false,
+                saved_at: Instant::now(),
+            }),
+            eviction_grace: EVICTION_GRACE,
+        }
+    }
+
+    /// Get a cached file of an entry, or create it with `create`.
+    ///
+    /// `create` writes to the given path, the file is only added to the cache if it succeeds.
+    pub fn get_or_create(
+        &self,
+        key: &PreviewCacheKey,
+        name: &str,
+        create: impl FnOnce(&Path) -> Result<()>,
+    ) -> Result<PathBuf> {
+        let file_name = key.file_name(name);
+        let path = self.dir.join(&file_name);
+        {
+            let mut state = self.state.lock();
+            if let Some(record) = state.index.files.get_mut(&file_name) {
+                if path.is_file() {
+                    record.last_access = now_ms();
+                    state.dirty = true;
+                    // the access time only matters for eviction, a hit doesn't fail on it
+                    if let Err(e) = self.save_index(&mut state, false) {
+                        log::warn!("Failed to save preview cache index: {}", e);
+                    }
+                    return Ok(path);
+                }
+                state.index.files.remove(&file_name);
+            }
+        }
+
+        // write to a partial file, so concurrent readers never see an incomplete one
+        let partial_path = self
+            .dir
+            .join(format!("{file_name}.{}.part", uuid::Uuid::new_v4()));
+        if let Err(e) = create(&partial_path) {
+            let _ = std::fs::remove_file(&partial_path);
+            return Err(e);
+        }
+        let size = std::fs::metadata(&partial_path)?.len();
+        if let Err(e) = std::fs::rename(&partial_path, &path) {
+            let _ = std::fs::remove_file(&partial_path);
+            // another thread created the same file first
+            if !path.is_file() {
+                return Err(e.into());
+            }
+        }
+
+        let mut state = self.state.lock();
+        state.index.files.insert(
+            file_name.clone(),
+            CacheRecord {
+                pak_path: key.pak_path.clone(),
+                pak_modified: key.pak_modified,
+                size,
+                last_access: now_ms(),
+            },
+        );
+        self.evict(&mut state.index, Some(&file_name));
+        state.dirty = true;
+        self.save_index(&mut state, false)?;
+        Ok(path)
+    }
+
+    /// Check if a file of an entry is cached.
+    pub fn contains(&self, key: &PreviewCacheKey, name: &str) -> bool {
+        let file_name = key.file_name(name);
+        self.state.lock().index.files.contains_key(&file_name) && self.dir.join(file_name).is_file()
+    }
+
+    pub fn status(&self) -> PreviewCacheStatus {
+        let index = &self.state.lock().index;
+        PreviewCacheStatus {
+            path: self.dir.to_string_lossy().to_string(),
+            file_count: index.files.len(),
+            used_bytes: index.used_bytes(),
+            size_limit: index.size_limit,
+        }
+    }
+
+    /// Change the size limit, evicting files right away if the cache is larger.
+    pub fn set_size_limit(&self, size_limit: u64) -> Result<PreviewCacheStatus> {
+        {
+            let mut state = self.state.lock();
+            state.index.size_limit = size_limit;
+            self.evict(&mut state.index, None);
+            state.dirty = true;
+            self.save_index(&mut state, true)?;
+        }
+        Ok(self.status())
+    }
+
+    /// Remove all cached files, files still in use are kept.
+    pub fn clear(&self) -> Result<PreviewCacheStatus> {
+        {
+            let mut state = self.state.lock();
+            let dir = &self.dir;
+            state
+                .index
+                .files
+                .retain(|file_name, _| std::fs::remove_file(dir.join(file_name)).is_err());
+            state.dirty = true;
+            self.save_index(&mut state, true)?;
+        }
+        Ok(self.status())
+    }
+
+    /// Save changes not written yet, index saves for new files are rate limited.
+    pub fn flush(&self) -> Result<()> {
+        let mut state = self.state.lock();
+        self.save_index(&mut state, true)
+    }
+
+    /// Remove least recently used files until the cache fits its limit.
+    ///
+    /// `keep` and files used within the eviction grace are kept, even if the cache stays larger.
+    fn evict(&self, index: &mut CacheIndex, keep: Option<&str>) {
+        let mut used_bytes = index.used_bytes();
+        if used_bytes <= index.size_limit {
+            return;
+        }
+
+        let recent_since = now_ms().saturating_sub(self.eviction_grace.as_millis() as u64);
+        let mut candidates = index
+            .files
+            .iter()
+            .filter(|(file_name, record)| {
+                Some(file_name.as_str()) != keep && record.last_access < recent_since
+            })
+            .map(|(file_name, record)| (record.last_access, file_name.clone()))
+            .collect::<Vec<_>>();
+        candidates.sort_unstable();
+
+        for (_, file_name) in candidates {
+            if used_bytes <= index.size_limit {
+                break;
+            }
+            if let Err(e) = std::fs::remove_file(self.dir.join(&file_name))
+                && e.kind() != std::io::ErrorKind::NotFound
+            {
+                // likely still open by a preview
+                log::warn!("Failed to evict preview cache file {}: {}", file_name, e);
+                continue;
+            }
+            if let Some(record) = index.files.remove(&file_name) {
+                used_bytes -= record.size;
+            }
+        }
+    }
+
+    /// Save a changed index, at most once per [`INDEX_SAVE_INTERVAL`] unless `force` is set.
+    ///
+    /// The index is written under a temp name and renamed, so it is never left half written.
+    fn save_index(&self, state: &mut IndexState, force: bool) -> Result<()> {
+        if !state.dirty || (!force && state.saved_at.elapsed() < INDEX_SAVE_INTERVAL) {
+            return Ok(());
+        }
+
+        let content =
+            serde_json::to_vec(&state.index).map_err(|e| Error::Internal(e.to_string()))?;
+        let temp_path = self.dir.join(format!("{INDEX_FILE_NAME}.part"));
+        let result = std::fs::write(&temp_path, content)
+            .and_then(|_| std::fs::rename(&temp_path, self.dir.join(INDEX_FILE_NAME)));
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(e.into());
+        }
+        state.dirty = false;
+        state.saved_at = Instant::now();
+        Ok(())
+    }
+}
+
+impl Drop for PreviewCache {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            log::warn!("Failed to save preview cache index: {}", e);
+        }
+    }
+}
+
+/// Cache dir used if the one in the local dir can't be opened.
+fn fallback_dir() -> PathBuf {
+    std::env::temp_dir()
+        .join("ree-pak-gui")
+        .join(PREVIEW_CACHE_DIR_NAME)
+}
+
+fn modified_ms(path: &Path) -> Option<u64> {
+    system_time_ms(path.metadata().ok()?.modified().ok()?)
+}
+
+fn now_ms() -> u64 {
+    system_time_ms(SystemTime::now()).unwrap_or_default()
+}
+
+fn system_time_ms(time: SystemTime) -> Option<u64> {
+    let duration = time.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(duration.as_millis()).ok()
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|ch| match ch {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            other => other,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_file(size: usize) -> impl FnOnce(&Path) -> Result<()> {
+        move |path| Ok(std::fs::write(path, vec![0; size])?)
+    }
+
+    #[test]
+    fn cache_reuses_files_and_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let pak_path = dir.path().join("re_chunk_000.pak");
+        std::fs::write(&pak_path, b"KPKA").unwrap();
+        let pak_path = pak_path.to_string_lossy().to_string();
+
+        let mut cache = PreviewCache::open(dir.path().join("cache")).unwrap();
+        cache.eviction_grace = Duration::ZERO;
+        cache.set_size_limit(250).unwrap();
+        let a = PreviewCacheKey::new(&pak_path, 1, 10);
+        let b = PreviewCacheKey::new(&pak_path, 2, 20);
+        let c = PreviewCacheKey::new(&pak_path, 3, 30);
+
+        let path_a = cache
+            .get_or_create(&a, "preview.png", write_file(100))
+            .unwrap();
+        cache
+            .get_or_create(&b, "preview.png", write_file(100))
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        // cached, `create` is not called and `a` becomes the most recently used
+        let reused = cache
+            .get_or_create(&a, "preview.png", |_| unreachable!())
+            .unwrap();
+        assert_eq!(reused, path_a);
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        cache
+            .get_or_create(&c, "preview.png", write_file(100))
+            .unwrap();
+        assert!(cache.contains(&a, "preview.png"));
+        assert!(!cache.contains(&b, "preview.png"));
+        assert!(cache.contains(&c, "preview.png"));
+        assert_eq!(cache.status().used_bytes, 200);
+
+        // changed entries get their own files
+        let a_changed = PreviewCacheKey::new(&pak_path, 1, 11);
+        assert!(!cache.contains(&a_changed, "preview.png"));
+    }
+
+    #[test]
+    fn recently_used_files_are_not_evicted() {
+        let dir = tempfile::tempdir().unwrap();
+        let pak_path = dir.path().join("re_chunk_000.pak");
+        std::fs::write(&pak_path, b"KPKA").unwrap();
+        let pak_path = pak_path.to_string_lossy().to_string();
+
+        let cache = PreviewCache::open(dir.path().join("cache")).unwrap();
+        cache.set_size_limit(150).unwrap();
+        let a = PreviewCacheKey::new(&pak_path, 1, 10);
+        let b = PreviewCacheKey::new(&pak_path, 2, 20);
+        cache.get_or_create(&a, "raw", write_file(100)).unwrap();
+        cache.get_or_create(&b, "raw", write_file(100)).unwrap();
+        // `a` was just returned, so the cache stays over its limit for now
+        assert!(cache.contains(&a, "raw"));
+        assert_eq!(cache.status().used_bytes, 200);
+
+        for record in cache.state.lock().index.files.values_mut() {
+            record.last_access = 0;
+        }
+        cache.set_size_limit(150).unwrap();
+        assert_eq!(cache.status().used_bytes, 100);
+    }
+
+    #[test]
+    fn cache_hits_save_the_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = PreviewCacheKey::new("re_chunk_000.pak", 1, 10);
+
+        let cache = PreviewCache::open(dir.path().join("cache")).unwrap();
+        cache.get_or_create(&key, "raw", write_file(10)).unwrap();
+        cache.state.lock().saved_at -= INDEX_SAVE_INTERVAL;
+        cache
+            .get_or_create(&key, "raw", |_| unreachable!())
+            .unwrap();
+        assert!(!cache.state.lock().dirty);
+
+        // a cache without a usable dir fails writes instead of panicking
+        let blocked = dir.path().join("blocked");
+        std::fs::write(&blocked, b"").unwrap();
+        let cache = PreviewCache::unopened(blocked.join("cache"));
+        assert!(cache.get_or_create(&key, "raw", write_file(10)).is_err());
+        assert_eq!(cache.status().file_count, 0);
+    }
+
+    #[test]
+    fn open_drops_files_of_modified_paks() {
+        let dir = tempfile::tempdir().unwrap();
+        let pak_path = dir.path().join("re_chunk_000.pak");
+        std::fs::write(&pak_path, b"KPKA").unwrap();
+        let pak_path = pak_path.to_string_lossy().to_string();
+        let cache_dir = dir.path().join("cache");
+
+        let cache = PreviewCache::open(cache_dir.clone()).unwrap();
+        let key = PreviewCacheKey::new(&pak_path, 1, 10);
+        cache.get_or_create(&key, "raw", write_file(10)).unwrap();
+        let mut stale = key.clone();
+        stale.pak_modified += 1;
+        cache.get_or_create(&stale, "raw", write_file(10)).unwrap();
+        std::fs::write(cache_dir.join("orphan.png"), b"").unwrap();
+        drop(cache);
+
+        let cache = PreviewCache::open(cache_dir.clone()).unwrap();
+        assert!(cache.contains(&key, "raw"));
+        assert!(!cache.contains(&stale, "raw"));
+        assert!(!cache_dir.join("orphan.png").exists());
+        assert_eq!(cache.status().file_count, 1);
+    }
+
+    #[test]
+    fn open_recovers_files_of_an_invalid_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let pak_path = dir.path().join("re_chunk_000.pak");
+        std::fs::write(&pak_path, b"KPKA").unwrap();
+        let pak_path = pak_path.to_string_lossy().to_string();
+        let cache_dir = dir.path().join("cache");
+
+        let cache = PreviewCache::open(cache_dir.clone()).unwrap();
+        let key = PreviewCacheKey::new(&pak_path, 1, 10);
+        cache.get_or_create(&key, "raw", write_file(10)).unwrap();
+        drop(cache);
+        std::fs::write(cache_dir.join(INDEX_FILE_NAME), b"{").unwrap();
+
+        // the file is kept and tracked, and still found by its key
+        let cache = PreviewCache::open(cache_dir.clone()).unwrap();
+        assert!(cache.contains(&key, "raw"));
+        assert_eq!(cache.status().used_bytes, 10);
+        drop(cache);
+        let cache = PreviewCache::open(cache_dir).unwrap();
+        assert!(cache.contains(&key, "raw"));
+    }
+}
@@ -0,0 +1,446 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+
+use model_insight::{MdfFile, MeshFile};
+use ree_pak_core::{
+    utf16_hash::Utf16HashExt,
+    write::{FileOptions, PakWriter},
+};
+use rhai::{Array, Blob, Dynamic, Engine, EvalAltResult, INT, Map, Scope};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    channel::ScriptProgressChannel,
+    error::{Error, Result},
+    pak::{
+        EntryQuery,
+        filter::PathPattern,
+        search::{EntrySearchHit, EntrySearchQuery},
+    },
+    service::{
+        format::{FormatRegistry, entry_version},
+        model_insight::ModelInsightService,
+        pak::PakService,
+    },
+};
+
+static SCRIPT_SERVICE: OnceLock<ScriptService> = OnceLock::new();
+
+type ScriptResult<T> = std::result::Result<T, Box<EvalAltResult>>;
+
+/// Receives output and progress of a running script.
+pub trait ScriptObserver: Send + Sync {
+    fn output(&self, line: &str);
+    fn progress(&self, done: u64, total: u64, message: Option<String>);
+}
+
+impl ScriptObserver for ScriptProgressChannel {
+    fn output(&self, line: &str) {
+        ScriptProgressChannel::output(self, line);
+    }
+
+    fn progress(&self, done: u64, total: u64, message: Option<String>) {
+        ScriptProgressChannel::progress(self, done, total, message);
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptRunOptions {
+    /// Script file, used if `source` is not set.
+    pub path: Option<String>,
+    /// Inline script source.
+    pub source: Option<String>,
+    /// Exposed to the script as the `ARGS` array.
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptRunReport {
+    /// Value of the last statement, empty if it has none.
+    pub result: String,
+    pub elapsed_ms: u64,
+}
+
+/// Runs Rhai scripts against the loaded paks.
+///
+/// Bindings:
+/// - `paks()`: loaded paks as `#{id, path}`, in load order.
+/// - `search(glob)`, `search_regex(regex)`: active entries as `#{path, hash, pak, size}`.
+/// - `hash(path)`, `lookup(hash)`, `exists(path)`: hashes are 16 digit hex strings.
+/// - `read(path)`: entry bytes as a blob.
+/// - `read_mesh(path)`, `find_mdf(mesh_path)`, `read_mdf(path)`: mesh materials and
+///   mdf2 texture bindings.
+/// - `extract(path, output)`, `export(path, output)`: export uses the format handler of the
+///   entry, with the format taken from the output extension, e.g. png or dds for textures.
+/// - `write_pak(output, files)`: files maps entry paths to blobs or local file paths.
+/// - `progress(done, total)`, `progress(done, total, message)` and `print`.
+pub struct ScriptService {
+    pak_service: &'static PakService,
+    running: Arc<AtomicBool>,
+    should_terminate: Arc<AtomicBool>,
+}
+
+impl ScriptService {
+    pub fn initialize() -> &'static Self {
+        SCRIPT_SERVICE.get_or_init(|| Self {
+            pak_service: PakService::get(),
+            running: Arc::new(AtomicBool::new(false)),
+            should_terminate: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    pub fn get() -> &'static Self {
+        SCRIPT_SERVICE.get().unwrap()
+    }
+
+    /// Run a script until it finishes or is terminated.
+    pub fn run(
+        &self,
+        options: &ScriptRunOptions,
+        observer: Arc<dyn ScriptObserver>,
+    ) -> Result<ScriptRunReport> {
+        let source = match (&options.source, &options.path) {
+            (Some(source), _) => source.clone(),
+            (None, Some(path)) => std::fs::read_to_string(path).map_err(|e| Error::FileIO {
+                path: path.clone(),
+                source: e,
+            })?,
+            (None, None) => return Err(Error::Script("no script source given".to_string())),
+        };
+
+        let Some(_running) = RunningGuard::acquire(&self.running) else {
+            return Err(Error::ScriptAlreadyRunning);
+        };
+        self.should_terminate.store(false, Ordering::SeqCst);
+
+        let start = Instant::now();
+        let engine = build_engine(
+            self.pak_service,
+            observer,
+            Arc::clone(&self.should_terminate),
+        );
+        let mut scope = Scope::new();
+        scope.push_constant(
+            "ARGS",
+            options
+                .args
+                .iter()
+                .cloned()
+                .map(Dynamic::from)
+                .collect::<Array>(),
+        );
+        let result = engine
+            .eval_with_scope::<Dynamic>(&mut scope, &source)
+            .map_err(|error| match *error {
+                EvalAltResult::ErrorTerminated(..) => Error::Terminated,
+                error => Error::Script(error.to_string()),
+            })?;
+        Ok(ScriptRunReport {
+            result: if result.is_unit() {
+                String::new()
+            } else {
+                result.to_string()
+            },
+            elapsed_ms: start.elapsed().as_millis() as u64,
+        })
+    }
+
+    pub fn terminate(&self) {
+        self.should_terminate.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Marks a script as running until dropped, also if the script panics.
+struct RunningGuard<'a>(&'a AtomicBool);
+
+impl<'a> RunningGuard<'a> {
+    /// `None` if a script is already running.
+    fn acquire(running: &'a AtomicBool) -> Option<Self> {
+        (!running.swap(true, Ordering::SeqCst)).then_some(Self(running))
+    }
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+fn build_engine(
+    pak_service: &'static PakService,
+    observer: Arc<dyn ScriptObserver>,
+    should_terminate: Arc<AtomicBool>,
+) -> Engine {
+    let mut engine = Engine::new();
+
+    engine.on_progress(move |_| {
+        should_terminate
+            .load(Ordering::Relaxed)
+            .then(|| Dynamic::from("terminated"))
+    });
+    {
+        let observer = Arc::clone(&observer);
+        engine.on_print(move |text| observer.output(text));
+    }
+    {
+        let observer = Arc::clone(&observer);
+        engine.on_debug(move |text, _, _| observer.output(text));
+    }
+    {
+        let observer = Arc::clone(&observer);
+        engine.register_fn("progress", move |done: INT, total: INT| {
+            observer.progress(done.max(0) as u64, total.max(0) as u64, None)
+        });
+    }
+    engine.register_fn("progress", move |done: INT, total: INT, message: &str| {
+        observer.progress(
+            done.max(0) as u64,
+            total.max(0) as u64,
+            Some(message.to_string()),
+        )
+    });
+
+    engine.register_fn("paks", move || -> Array {
+        pak_service
+            .list_all_paks()
+            .into_iter()
+            .map(|info| {
+                let mut map = Map::new();
+                map.insert("id".into(), Dynamic::from(info.id.to_string()));
+                map.insert("path".into(), Dynamic::from(info.path));
+                Dynamic::from_map(map)
+            })
+            .collect()
+    });
+    engine.register_fn("search", move |glob: &str| {
+        search(pak_service, PathPattern::Glob(glob.to_string()))
+    });
+    engine.register_fn("search_regex", move |regex: &str| {
+        search(pak_service, PathPattern::Regex(regex.to_string()))
+    });
+
+    engine.register_fn("hash", |path: &str| format_hash(path.hash_mixed()));
+    engine.register_fn("lookup", move |hash: &str| -> ScriptResult<Dynamic> {
+        let hash = parse_hash(hash)?;
+        Ok(pak_service
+            .lookup_name(hash)
+            .map(|lookup| Dynamic::from(lookup.path))
+            .unwrap_or(Dynamic::UNIT))
+    });
+    engine.register_fn("exists", move |path: &str| {
+        pak_service.get_entry_history(&path_query(path)).is_ok()
+    });
+    engine.register_fn("read", move |path: &str| read_entry(pak_service, path));
+
+    engine.register_fn("read_mesh", move |path: &str| -> ScriptResult<Map> {
+        let data = read_entry(pak_service, path)?;
+        let mesh = MeshFile::read_bytes(&data, file_version(path)?).map_err(script_error)?;
+        let mut map = Map::new();
+        map.insert("version".into(), Dynamic::from(mesh.header.version as INT));
+        map.insert(
+            "materials".into(),
+            Dynamic::from_array(mesh.material_names.into_iter().map(Dynamic::from).collect()),
+        );
+        Ok(map)
+    });
+    engine.register_fn("find_mdf", |mesh_path: &str| -> ScriptResult<Dynamic> {
+        match ModelInsightService::get().find_mdf_path(mesh_path) {
+            Ok(path) => Ok(Dynamic::from(path)),
+            Err(Error::PakEntryNotFound(_)) => Ok(Dynamic::UNIT),
+            Err(error) => Err(script_error(error)),
+        }
+    });
+    engine.register_fn("read_mdf", move |path: &str| -> ScriptResult<Array> {
+        let data = read_entry(pak_service, path)?;
+        let mdf = MdfFile::read_bytes(&data, file_version(path)?).map_err(script_error)?;
+        Ok(mdf
+            .materials
+            .iter()
+            .map(|material| {
+                let textures = material
+                    .textures
+                    .iter()
+                    .map(|texture| {
+                        let mut map = Map::new();
+                        map.insert("type".into(), Dynamic::from(texture.texture_type.clone()));
+                        map.insert("path".into(), Dynamic::from(texture.texture_path.clone()));
+                        Dynamic::from_map(map)
+                    })
+                    .collect::<Array>();
+                let mut map = Map::new();
+                map.insert("name".into(), Dynamic::from(material.name().to_string()));
+                map.insert(
+                    "mmtr".into(),
+                    Dynamic::from(material.header.mmtr_path.clone()),
+                );
+                map.insert("textures".into(), Dynamic::from_array(textures));
+                Dynamic::from_map(map)
+            })
+            .collect())
+    });
+
+    engine.register_fn(
+        "extract",
+        move |path: &str, output: &str| -> ScriptResult<()> {
+            pak_service.unpack_file(path, output).map_err(script_error)
+        },
+    );
+    engine.register_fn(
+        "export",
+        move |path: &str, output: &str| -> ScriptResult<()> {
+            let output = Path::new(output);
+            let format = output
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+                .ok_or_else(|| {
+                    format!("missing export format in output path: {}", output.display())
+                })?;
+            let handler = FormatRegistry::get()
+                .detect(path, || {
+                    pak_service
+                        .pak_group()
+                        .lock()
+                        .detect_entry_type(path.hash_mixed(), None)
+                })
+                .filter(|handler| handler.supports_export(&format))
+                .ok_or_else(|| format!("cannot export {path} to {format}"))?;
+            if let Some(parent) = output.parent() {
+                std::fs::create_dir_all(parent).map_err(script_error)?;
+            }
+            pak_service
+                .read_entry(&path_query(path), None, |_, reader| {
+                    handler.export_reader(reader, output, &format)
+                })
+                .map_err(script_error)?
+                .map_err(script_error)
+        },
+    );
+    engine.register_fn("write_pak", write_pak);
+
+    engine
+}
+
+fn search(pak_service: &PakService, pattern: PathPattern) -> ScriptResult<Array> {
+    let result = pak_service
+        .search_entries(&EntrySearchQuery {
+            pattern,
+            limit: Some(usize::MAX),
+            group_variants: None,
+        })
+        .map_err(script_error)?;
+    Ok(result.hits.into_iter().map(entry_map).collect())
+}
+
+fn entry_map(hit: EntrySearchHit) -> Dynamic {
+    let mut map = Map::new();
+    map.insert("path".into(), Dynamic::from(hit.path));
+    map.insert(
+        "hash".into(),
+        Dynamic::from(format_hash(hit.hash.hash_u64())),
+    );
+    map.insert("pak".into(), Dynamic::from(hit.pak_path));
+    map.insert("size".into(), Dynamic::from(hit.uncompressed_size as INT));
+    Dynamic::from_map(map)
+}
+
+fn read_entry(pak_service: &PakService, path: &str) -> ScriptResult<Blob> {
+    pak_service
+        .read_entry(&path_query(path), None, |size, reader| {
+            let mut data = Vec::with_capacity(size as usize);
+            reader.read_to_end(&mut data).map(|_| data)
+        })
+        .map_err(script_error)?
+        .map_err(script_error)
+}
+
+/// Write a pak from a map of entry paths to blobs or local file paths.
+///
+/// The pak is written under a temp name and renamed once complete, so a failed script never
+/// leaves a truncated pak at `output`.
+fn write_pak(output: &str, files: Map) -> ScriptResult<INT> {
+    let output = Path::new(output);
+    let temp_path = output.with_extension(format!("{}.part", uuid::Uuid::new_v4()));
+    let result = write_pak_files(&temp_path, &files)
+        .and_then(|_| std::fs::rename(&temp_path, output).map_err(script_error));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result.map(|_| files.len() as INT)
+}
+
+fn write_pak_files(pak_path: &Path, files: &Map) -> ScriptResult<()> {
+    let writer = BufWriter::new(File::create(pak_path).map_err(script_error)?);
+    let mut pak_writer = PakWriter::new(writer, files.len() as u64);
+    let mut write_files = || -> ScriptResult<()> {
+        for (path, content) in files {
+            pak_writer
+                .start_file_hash(path.as_str().hash_mixed(), FileOptions::default())
+                .map_err(script_error)?;
+            if content.is_blob() {
+                let data = content.clone().into_blob().map_err(script_error)?;
+                std::io::Write::write_all(&mut pak_writer, &data).map_err(script_error)?;
+            } else {
+                let source = content.clone().into_string().map_err(script_error)?;
+                let mut reader = BufReader::new(File::open(&source).map_err(script_error)?);
+                std::io::copy(&mut reader, &mut pak_writer).map_err(script_error)?;
+            }
+        }
+        Ok(())
+    };
+
+    let result = write_files();
+    pak_writer.finish().map_err(script_error)?;
+    result
+}
+
+fn path_query(path: &str) -> EntryQuery {
+    EntryQuery {
+        hash: None,
+        path: Some(path.to_string()),
+    }
+}
+
+/// File version from the version suffix of a path, e.g. `2109148288` in `a.mesh.2109148288`.
+fn file_version(path: &str) -> ScriptResult<u32> {
+    entry_version(path)
+        .and_then(|version| u32::try_from(version).ok())
+        .ok_or_else(|| format!("missing file version in path: {path}").into())
+}
+
+fn format_hash(hash: u64) -> String {
+    format!("{hash:016X}")
+}
+
+fn parse_hash(hash: &str) -> ScriptResult<u64> {
+    u64::from_str_radix(hash, 16).map_err(|_| format!("invalid hash: {hash}").into())
+}
+
+fn script_error(error: impl std::fmt::Display) -> Box<EvalAltResult> {
+    error.to_string().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_version_is_read_from_path_suffix() {
+        assert_eq!(
+            file_version("natives/STM/a.mesh.2109148288").unwrap(),
+            2109148288
+        );
+        assert_eq!(file_version("natives/STM/a.mdf2.45.X64").unwrap(), 45);
+        assert!(file_version("natives/STM/a.mesh").is_err());
+    }
+}
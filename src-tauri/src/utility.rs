@@ -22,6 +22,17 @@ pub fn message_box_error(message: &str) {
     }
 }
 
+/// Attach to the console of the parent process, so a release build started from a
+/// terminal can print to it.
+#[cfg(target_os = "windows")]
+pub fn attach_parent_console() {
+    use windows::Win32::System::Console::{ATTACH_PARENT_PROCESS, AttachConsole};
+
+    unsafe {
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
 #[cfg(target_os = "windows")]
 fn to_utf16_with_nul(s: &str) -> Vec<u16> {
     s.encode_utf16().chain(Some(0)).collect::<Vec<_>>()
@@ -1,20 +1,118 @@
 import { invoke } from '@tauri-apps/api/core'
+import type { JsSafeHash } from './pak'
 
 export interface FileListInfo {
   name: string
   absPath: string
 }
 
+export interface SuffixResolveOptions {
+  // base paths without version, e.g. natives/STM/Art/foo.tex
+  paths?: string[]
+  // text file with one base path per line
+  listPath?: string
+  // versions to try per extension, versions of the loaded file list are always tried
+  versions?: Record<string, number[]>
+  // try language suffixes, defaults to true
+  withLanguages?: boolean
+  // try platform and arch suffixes, defaults to true
+  withPlatforms?: boolean
+}
+
+export interface SuffixResolveReport {
+  basePaths: number
+  candidates: number
+  // resolved paths, pushed into the discovered name layer
+  resolved: string[]
+  // entries still missing from the file name table
+  unknownLeft: number
+}
+
+export type NameLayerSource = 'file' | 'discovered' | 'manual'
+
+export interface NameLayerInfo {
+  name: string
+  source: NameLayerSource
+  // list file of file layers
+  path?: string
+  enabled: boolean
+  nameCount: number
+}
+
+export interface NameLookup {
+  path: string
+  // layer the name was resolved from
+  layer: string
+}
+
+export interface DuplicatePath {
+  path: string
+  count: number
+}
+
+export interface HashGroup {
+  hash: JsSafeHash
+  // distinct paths in list order
+  paths: string[]
+  // paks containing an entry with this hash
+  paks: string[]
+  // name the loaded file name table resolves this hash to
+  resolvedAs?: string
+}
+
+export interface ListAnalysisReport {
+  totalPaths: number
+  uniqueHashes: number
+  exactDuplicates: DuplicatePath[]
+  // paths differing only in case, they share a hash
+  caseDuplicates: HashGroup[]
+  // different paths with the same hash
+  collisions: HashGroup[]
+}
+
 export class FilePathList {
   static async getList(): Promise<FileListInfo[]> {
     return invoke('file_table_get_list')
   }
 
-  static async load(path: string): Promise<void> {
-    return invoke('file_table_load', { path })
+  // load a list file as a name layer, replaces the `base` layer if no name is given
+  // plain, gzip/zstd compressed, zip archives of lists and `.cache` files are accepted
+  static async load(path: string, name?: string): Promise<void> {
+    return invoke('file_table_load', { path, name })
   }
 
   static async pushPaths(filePathList: string[]): Promise<void> {
     return invoke('file_table_push_paths', { filePathList })
   }
+
+  // layers from bottom to top, upper layers take precedence
+  static async listLayers(): Promise<NameLayerInfo[]> {
+    return invoke('file_table_list_layers')
+  }
+
+  static async setLayerEnabled(name: string, enabled: boolean): Promise<void> {
+    return invoke('file_table_set_layer_enabled', { name, enabled })
+  }
+
+  static async removeLayer(name: string): Promise<void> {
+    return invoke('file_table_remove_layer', { name })
+  }
+
+  static async lookup(hash: JsSafeHash): Promise<NameLookup | null> {
+    return invoke('file_table_lookup', { hash })
+  }
+
+  // export names of enabled layers as one list file, returns the number of names
+  static async export(outputPath: string): Promise<number> {
+    return invoke('file_table_export', { outputPath })
+  }
+
+  // report duplicates and hash collisions in a list file
+  static async analyze(path: string): Promise<ListAnalysisReport> {
+    return invoke('file_table_analyze', { path })
+  }
+
+  static async resolveSuffixes(options: SuffixResolveOptions): Promise<SuffixResolveReport> {
+    return invoke('file_table_resolve_suffixes', { options })
+  }
 }
@@ -8,6 +8,7 @@ export type JsSafeHash = [number, number]
 export interface PakInfo {
   id: PakId
   path: string
+  archivePath?: string | null
 }
 
 export interface FileTree {
@@ -30,11 +31,94 @@ export interface NodeInfo {
   compressedSize: number
 }
 
+export type TreeSortKey =
+  | 'name'
+  | 'uncompressedSize'
+  | 'compressedSize'
+  | 'compressionRatio'
+  | 'extension'
+  | 'pak'
+
+export type TreeSortOrder = 'ascending' | 'descending'
+
+export type TreeMode = 'combined' | 'perPak'
+
 export interface RenderTreeOptions {
   sortByName?: boolean
   sortBySize?: boolean
+  sortKey?: TreeSortKey
+  sortOrder?: TreeSortOrder
+  directoriesFirst?: boolean
+  showOverridden?: boolean
+  mode?: TreeMode
+  // paks to render in perPak mode, all paks if omitted
+  pakIds?: PakId[]
+  // collapse language and platform variants of a file into one node
+  groupVariants?: boolean
+}
+
+export interface AssetVariant {
+  name: string
+  language?: string
+  platform?: string
+  hash?: JsSafeHash
+  compressedSize: number
+  uncompressedSize: number
+  isCompressed: boolean
+  belongsTo?: PakId
+  isOverridden: boolean
+}
+
+export interface StreamingPair {
+  hash: JsSafeHash
+  belongsTo: PakId
+  compressedSize: number
+  uncompressedSize: number
+  // natives and streaming entry together
+  combinedCompressedSize: number
+  combinedUncompressedSize: number
+}
+
+export interface VariantTagCount {
+  tag: string
+  fileCount: number
 }
 
+export interface VariantSummary {
+  languages: VariantTagCount[]
+  platforms: VariantTagCount[]
+}
+
+export interface PakTreeStats {
+  fileCount: number
+  compressedSize: number
+  uncompressedSize: number
+  overriddenCount: number
+  overriddenCompressedSize: number
+  overriddenUncompressedSize: number
+}
+
+export type FileType =
+  | 'tex'
+  | 'mesh'
+  | 'mdf2'
+  | 'bnk'
+  | 'pck'
+  | 'user'
+  | 'rsz'
+  | 'scn'
+  | 'pfb'
+  | 'msg'
+  | 'mot'
+  | 'motlist'
+  | 'motbank'
+  | 'efx'
+  | 'gui'
+  | 'rcol'
+  | 'cfil'
+  | 'dds'
+  | 'png'
+
 export interface RenderTreeNode {
   isDir: boolean
   name: string
@@ -43,15 +127,131 @@ export interface RenderTreeNode {
   uncompressedSize: number
   isCompressed: boolean
   belongsTo?: PakId
+  isOverridden: boolean
+  // type detected from the magic, only for entries missing from the file list
+  fileType?: FileType
+  pakStats?: PakTreeStats
+  variants?: AssetVariant[]
+  streaming?: StreamingPair
   children: RenderTreeNode[]
 }
 
+export type TreeNodeId = number
+
+export interface TreeChildrenQuery {
+  nodeId?: TreeNodeId
+  generation?: number
+  offset?: number
+  limit?: number
+  options?: RenderTreeOptions
+}
+
+export interface LazyTreeNode {
+  id: TreeNodeId
+  isDir: boolean
+  name: string
+  hash?: JsSafeHash
+  compressedSize: number
+  uncompressedSize: number
+  isCompressed: boolean
+  belongsTo?: PakId
+  isOverridden: boolean
+  // type detected from the magic, only for entries missing from the file list
+  fileType?: FileType
+  childCount: number
+  variants?: AssetVariant[]
+  streaming?: StreamingPair
+}
+
+export interface TreeChildrenPage {
+  generation: number
+  parent: LazyTreeNode
+  children: LazyTreeNode[]
+  offset: number
+  total: number
+}
+
+export interface PakStatsOptions {
+  pakIds?: PakId[]
+  topDirectories?: number
+  directoryDepth?: number
+}
+
+export interface StatsBucket {
+  fileCount: number
+  compressedSize: number
+  uncompressedSize: number
+  compressionRatio: number
+}
+
+export interface ExtensionStats extends StatsBucket {
+  extension: string | null
+}
+
+export interface DirectoryStats extends StatsBucket {
+  path: string
+}
+
+export interface PakStats extends StatsBucket {
+  pakId: PakId
+  pakPath: string
+}
+
+export interface PakGroupStats {
+  total: StatsBucket
+  byExtension: ExtensionStats[]
+  topDirectories: DirectoryStats[]
+  byPak: PakStats[]
+}
+
 export interface ExtractOptions {
   outputPath: string
   override: boolean
   mode: ExtractMode
   extractAll: boolean
   extractFiles: ExtractFileInfo[]
+  // only extract localized files of these languages, untagged files are always extracted
+  languages?: string[]
+  // also extract streaming counterparts of selected natives files
+  withStreaming?: boolean
+  // applied to all or selected files
+  filter?: ExtractFilterOptions
+  // write into an archive at outputPath instead of a directory
+  archive?: ExtractArchiveFormat
+  // save the job report as JSON next to the output
+  saveReport?: boolean
+  // leave existing files with the same content untouched, requires override
+  skipUnchanged?: boolean
+  // continue an interrupted extraction into the same directory
+  resume?: boolean
+  // number of extraction workers, defaults to available parallelism
+  workers?: number
+  // convert entries as they are extracted, ignored when writing an archive
+  converters?: ExtractConverter[]
+  // keep the extracted file after conversion, defaults to true
+  keepOriginal?: boolean
+}
+
+export type ExtractConverter =
+  // .tex to an image next to the extracted file
+  | { kind: 'texture'; format: 'png' | 'dds' }
+  // .bnk/.pck to one wav per wem in a `<file name>_wav` directory
+  | { kind: 'audio' }
+
+export type ExtractArchiveFormat = 'zip' | 'tar'
+
+// case-insensitive, matched against the whole path
+export type PathPattern = { kind: 'glob'; pattern: string } | { kind: 'regex'; pattern: string }
+
+export interface ExtractFilterOptions {
+  include?: PathPattern[]
+  exclude?: PathPattern[]
+  // extensions without version suffix, e.g. ['mesh', 'tex'],
+  // unknown entries match by the type detected from their magic
+  extensions?: string[]
+  minSize?: number
+  maxSize?: number
+  unknownOnly?: boolean
 }
 
 export type ExtractMode = 'relativePath' | 'absolutePath'
@@ -62,6 +262,29 @@ export interface ExtractFileInfo {
   relativeRoot?: string
 }
 
+export interface EntryQuery {
+  hash?: JsSafeHash
+  path?: string
+}
+
+export interface EntryVersionInfo {
+  belongsTo: PakId
+  pakPath: string
+  loadIndex: number
+  compressedSize: number
+  uncompressedSize: number
+  compressionType: number
+  isCompressed: boolean
+  checksum: string
+  isActive: boolean
+}
+
+export interface EntryHistory {
+  hash: JsSafeHash
+  path?: string | null
+  versions: EntryVersionInfo[]
+}
+
 export type WorkProgressEvent<T> =
   | {
       event: 'workStart'
@@ -88,6 +311,31 @@ type UnpackProgressData = {
   path: string
   hash: JsSafeHash
   finishCount: number
+  // set on fileDone of a failed file
+  error?: string
+  // set on workFinished
+  report?: JobReport
+}
+
+export interface JobReportEntry {
+  hash: JsSafeHash
+  path: string
+  reason: string
+}
+
+export interface JobReport {
+  succeeded: number
+  // not written because the output already exists
+  skipped: JobReportEntry[]
+  // existing files with the same content
+  unchanged: number
+  // entries done by an interrupted run
+  resumed: number
+  // files produced by converters
+  converted: number
+  failed: JobReportEntry[]
+  // files rewritten from their first change count the rewritten part
+  bytesWritten: number
 }
 
 type PackProgressData = {
@@ -182,7 +430,7 @@ export function pak_list_all(): Promise<PakInfo[]> {
   return invoke('pak_list_all')
 }
 
-export function pak_open(path: string): Promise<PakId> {
+export function pak_open(path: string): Promise<PakId[]> {
   return invoke('pak_open', { path })
 }
 
@@ -205,6 +453,46 @@ export function pak_extract_all(
   return invoke('pak_extract_all', { options, onEvent })
 }
 
+export interface ExtractListOptions {
+  // one path per line, plain or compressed like file lists
+  listPath: string
+  outputPath: string
+  override: boolean
+  mode: ExtractMode
+  // match paths with any version suffix
+  ignoreVersion?: boolean
+  archive?: ExtractArchiveFormat
+  saveReport?: boolean
+}
+
+export interface ExtractListReport {
+  requested: number
+  // distinct entries the paths resolved to
+  resolved: number
+  notFound: string[]
+  // outcome of extracting the resolved entries
+  job: JobReport
+}
+
+export function pak_extract_from_list(
+  options: ExtractListOptions,
+  onEvent: Channel<UnpackProgressEvent>
+): Promise<ExtractListReport> {
+  return invoke('pak_extract_from_list', { options, onEvent })
+}
+
+export function pak_get_entry_history(query: EntryQuery): Promise<EntryHistory> {
+  return invoke('pak_get_entry_history', { query })
+}
+
+export function pak_extract_entry_version(
+  hash: JsSafeHash,
+  belongsTo: PakId,
+  outputPath: string
+): Promise<void> {
+  return invoke('pak_extract_entry_version', { hash, belongsTo, outputPath })
+}
+
 export function pak_terminate_extraction(): Promise<void> {
   return invoke('pak_terminate_extraction')
 }
@@ -243,6 +531,18 @@ export function pak_read_file_tree_optimized(
   })
 }
 
+export function pak_read_tree_children(query: TreeChildrenQuery): Promise<TreeChildrenPage> {
+  return invoke('pak_read_tree_children', { query })
+}
+
+export function pak_list_variants(): Promise<VariantSummary> {
+  return invoke('pak_list_variants')
+}
+
+export function pak_get_statistics(options?: PakStatsOptions): Promise<PakGroupStats> {
+  return invoke('pak_get_statistics', { options })
+}
+
 // Pack related APIs
 
 export interface PakHeaderInfo {
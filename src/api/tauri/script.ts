@@ -0,0 +1,37 @@
+import { Channel, invoke } from '@tauri-apps/api/core'
+import type { WorkProgressEvent } from './pak'
+
+export interface ScriptRunOptions {
+  // script file, used if `source` is not set
+  path?: string
+  source?: string
+  // exposed to the script as `ARGS`
+  args?: string[]
+}
+
+export interface ScriptRunReport {
+  // value of the last statement, empty if it has none
+  result: string
+  elapsedMs: number
+}
+
+export interface ScriptProgressData {
+  // line printed by the script
+  output?: string
+  done: number
+  total: number
+  message?: string
+}
+
+export type ScriptProgressEvent = WorkProgressEvent<ScriptProgressData>
+
+export function scriptRun(
+  options: ScriptRunOptions,
+  onEvent: Channel<ScriptProgressEvent>
+): Promise<ScriptRunReport> {
+  return invoke('script_run', { options, onEvent })
+}
+
+export function scriptTerminate(): Promise<void> {
+  return invoke('script_terminate')
+}
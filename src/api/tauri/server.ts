@@ -0,0 +1,21 @@
+import { invoke } from '@tauri-apps/api/core'
+
+export interface ApiServerInfo {
+  url: string
+  port: number
+  // sent as `Authorization: Bearer <token>` or the `token` query parameter
+  token: string
+}
+
+// start the local HTTP API, uses the default port if not set, 0 picks a free port
+export function apiServerStart(port?: number): Promise<ApiServerInfo> {
+  return invoke('api_server_start', { port })
+}
+
+export function apiServerStop(): Promise<void> {
+  return invoke('api_server_stop')
+}
+
+export function apiServerStatus(): Promise<ApiServerInfo | null> {
+  return invoke('api_server_status')
+}
@@ -1,5 +1,5 @@
 import { Channel, invoke } from '@tauri-apps/api/core'
-import type { ExtractFileInfo, JsSafeHash, WorkProgressEvent } from './pak'
+import type { ExtractFileInfo, JobReport, JsSafeHash, WorkProgressEvent } from './pak'
 
 export interface CompileInfo {
   version: string
@@ -58,17 +58,68 @@ export interface TextureExportOptions {
   outputPath: string
   format: TextureExportFormat
   files: ExtractFileInfo[]
+  // save the job report as JSON in the output directory
+  saveReport?: boolean
 }
 
 export type TextureExportProgressData = {
   path: string
   finishCount: number
+  // set on workFinished
+  report?: JobReport
 }
 
 export type TextureExportProgressEvent = WorkProgressEvent<TextureExportProgressData>
 
-export function getPreviewFile(hash: JsSafeHash): Promise<string> {
-  return invoke('get_preview_file', { hash })
+export function getPreviewFile(hash: JsSafeHash, belongsTo?: string): Promise<string> {
+  return invoke('get_preview_file', { hash, belongsTo })
+}
+
+export interface PreviewCacheStatus {
+  path: string
+  fileCount: number
+  usedBytes: number
+  // least recently used files are evicted past this size
+  sizeLimit: number
+}
+
+export function previewCacheStatus(): Promise<PreviewCacheStatus> {
+  return invoke('preview_cache_status')
+}
+
+export function previewCacheSetLimit(sizeLimit: number): Promise<PreviewCacheStatus> {
+  return invoke('preview_cache_set_limit', { sizeLimit })
+}
+
+export function previewCacheClear(): Promise<PreviewCacheStatus> {
+  return invoke('preview_cache_clear')
+}
+
+export interface FormatCapabilities {
+  // handler id, e.g. 'texture'
+  id: string
+  extensions: string[]
+  preview: boolean
+  // extensions of the single files an entry exports to, e.g. 'png'
+  exportFormats: string[]
+  // formats extracted files are converted to, possibly into several files, e.g. 'wav'
+  convertFormats: string[]
+  metadata: boolean
+}
+
+export interface FileMetadata {
+  // id of the format handler
+  format: string
+  path: string
+  metadata: Record<string, unknown>
+}
+
+export function formatListHandlers(): Promise<FormatCapabilities[]> {
+  return invoke('format_list_handlers')
+}
+
+export function getFileMetadata(hash: JsSafeHash, belongsTo?: string): Promise<FileMetadata> {
+  return invoke('get_file_metadata', { hash, belongsTo })
 }
 
 export function exportTextureFiles(